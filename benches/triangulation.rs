@@ -0,0 +1,39 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use geometer::{
+    triangulation::{
+        EarClipping, MonotonePartition, Seidel, TriangulationComputer, ZOrderEarClipping,
+    },
+    util::polygon_map_by_num_vertices,
+};
+
+fn benchmark_triangulation(c: &mut Criterion) {
+    let polygon_map = polygon_map_by_num_vertices(200usize).unwrap();
+    let mut group = c.benchmark_group("Triangulation");
+    group.sample_size(10);
+
+    for (name, polygon) in polygon_map.iter() {
+        group.bench_with_input(
+            BenchmarkId::new("ear_clipping", name),
+            polygon,
+            |b, polygon| b.iter(|| EarClipping.triangulation(polygon).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("ear_clipping_z_order", name),
+            polygon,
+            |b, polygon| b.iter(|| ZOrderEarClipping.triangulation(polygon).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("monotone_partition", name),
+            polygon,
+            |b, polygon| b.iter(|| MonotonePartition.triangulation(polygon).unwrap()),
+        );
+        group.bench_with_input(BenchmarkId::new("seidel", name), polygon, |b, polygon| {
+            b.iter(|| Seidel.triangulation(polygon).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_triangulation);
+criterion_main!(benches);
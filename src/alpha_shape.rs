@@ -0,0 +1,289 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use ordered_float::OrderedFloat as OF;
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+
+use crate::{
+    data_structure::UnionFind,
+    delaunay::Delaunay,
+    polygon::{Polygon, PolygonWithHoles},
+    triangle::Triangle,
+    triangulation::{TriangleVertexIds, Triangulation},
+    vertex::{Vertex, VertexId},
+};
+
+/// Alpha shapes of a point set, a family of concave hulls that hug the
+/// points more tightly as alpha decreases. The shape for a given alpha is
+/// the union of the Delaunay triangles with circumradius at most alpha,
+/// so a large enough alpha recovers the convex hull.
+pub struct AlphaShape {
+    vertex_map: HashMap<VertexId, Vertex>,
+    delaunay: Triangulation,
+}
+
+impl AlphaShape {
+    pub fn from_vertices<'a>(vertices: impl IntoIterator<Item = &'a Vertex>) -> Self {
+        let vertices = vertices.into_iter().cloned().collect_vec();
+        let delaunay = Delaunay::from_vertices(&vertices);
+        // Duplicate points are not part of the triangulation, so leave
+        // them out of the shape entirely
+        let mut vertex_map: HashMap<_, _> = vertices.into_iter().map(|v| (v.id, v)).collect();
+        for id in delaunay.duplicates() {
+            vertex_map.remove(id);
+        }
+        let delaunay = delaunay.into_triangulation();
+        AlphaShape {
+            vertex_map,
+            delaunay,
+        }
+    }
+
+    pub fn delaunay(&self) -> &Triangulation {
+        &self.delaunay
+    }
+
+    /// Boundary polygons of the alpha shape, one per connected region,
+    /// with the holes inside each region. Regions that only touch at a
+    /// vertex are returned as separate polygons, and likewise a hole that
+    /// touches the outer boundary or another hole at a vertex is kept
+    /// apart from it.
+    pub fn polygons(&self, alpha: f64) -> Vec<PolygonWithHoles> {
+        info!("Computing alpha shape for alpha={alpha}");
+
+        // Boundary edges are the edges of kept triangles whose twin edge
+        // is not also in a kept triangle. Triangles are CCW so the shape
+        // interior is always to the left of a boundary edge.
+        let triangles = self.triangles(alpha).collect_vec();
+        let mut regions = UnionFind::new(triangles.len());
+        let mut edge_owner: HashMap<(VertexId, VertexId), usize> = HashMap::new();
+        for (i, t) in triangles.iter().enumerate() {
+            regions.add();
+            for (a, b) in [(t.0, t.1), (t.1, t.2), (t.2, t.0)] {
+                edge_owner.insert((a, b), i);
+            }
+        }
+        let mut outgoing: HashMap<VertexId, Vec<VertexId>> = HashMap::new();
+        for ((a, b), i) in edge_owner.iter() {
+            match edge_owner.get(&(*b, *a)) {
+                Some(j) => regions.union(*i, *j),
+                None => outgoing.entry(*a).or_default().push(*b),
+            }
+        }
+
+        // Each region is joined up through its edges, so it has a single
+        // outer boundary going CCW, and its holes come back going CW
+        let mut outers = HashMap::new();
+        let mut holes: HashMap<usize, Vec<Polygon>> = HashMap::new();
+        while let Some(start) = outgoing.keys().min().copied() {
+            let ids = self.trace_boundary(start, &mut outgoing);
+            let region = regions.find(edge_owner[&(ids[0], ids[1])]);
+            let boundary = self.get_vertices(&ids);
+            let area: f64 = boundary
+                .iter()
+                .tuple_windows()
+                .map(|(v1, v2)| Triangle::from_vertices(&boundary[0], v1, v2).area())
+                .sum();
+            if area > 0.0 {
+                outers.insert(region, Polygon::from_vertices(boundary));
+            } else {
+                debug!("Hole boundary: {boundary:?}");
+                let boundary = boundary.into_iter().rev().collect_vec();
+                holes
+                    .entry(region)
+                    .or_default()
+                    .push(Polygon::from_vertices(boundary));
+            }
+        }
+
+        let polygons = outers
+            .into_iter()
+            .sorted_by_key(|(region, _)| *region)
+            .map(|(region, outer)| {
+                PolygonWithHoles::new(outer, holes.remove(&region).unwrap_or_default())
+            })
+            .collect_vec();
+        info!("Computed alpha shape with {} regions", polygons.len());
+        polygons
+    }
+
+    /// Smallest alpha for which the alpha shape is a single region that
+    /// covers every point, or `None` if there are not enough points to
+    /// triangulate.
+    pub fn optimal_alpha(&self) -> Option<f64> {
+        // Adding triangles in order of circumradius is the same as sweeping
+        // alpha upwards, so track connected regions with union-find as
+        // triangles are added and stop once everything is in one region
+        let triangles = self
+            .delaunay
+            .iter()
+            .map(|t| (self.circumradius(t), t))
+            .sorted_by_key(|(r, _)| OF(*r))
+            .collect_vec();
+
+        let mut regions = UnionFind::new(triangles.len());
+        let mut edge_owner: HashMap<(VertexId, VertexId), usize> = HashMap::new();
+        let mut covered = HashSet::new();
+        for (i, (radius, t)) in triangles.iter().enumerate() {
+            regions.add();
+            for (a, b) in [(t.0, t.1), (t.1, t.2), (t.2, t.0)] {
+                if let Some(j) = edge_owner.get(&(b, a)) {
+                    regions.union(i, *j);
+                }
+                edge_owner.insert((a, b), i);
+                covered.insert(a);
+            }
+            trace!(radius:?, regions:?=regions.num_sets(); "Added triangle");
+            if regions.num_sets() == 1 && covered.len() == self.vertex_map.len() {
+                return Some(*radius);
+            }
+        }
+        None
+    }
+
+    fn triangles(&self, alpha: f64) -> impl Iterator<Item = &TriangleVertexIds> {
+        self.delaunay
+            .iter()
+            .filter(move |t| self.circumradius(t) <= alpha)
+    }
+
+    fn circumradius(&self, t: &TriangleVertexIds) -> f64 {
+        let v1 = &self.vertex_map[&t.0];
+        let v2 = &self.vertex_map[&t.1];
+        let v3 = &self.vertex_map[&t.2];
+        Triangle::from_vertices(v1, v2, v3).circumradius()
+    }
+
+    fn get_vertices(&self, ids: &[VertexId]) -> Vec<Vertex> {
+        ids.iter()
+            .map(|id| self.vertex_map[id].clone())
+            .collect_vec()
+    }
+
+    fn trace_boundary(
+        &self,
+        start: VertexId,
+        outgoing: &mut HashMap<VertexId, Vec<VertexId>>,
+    ) -> Vec<VertexId> {
+        let mut boundary = vec![start];
+        let mut prev = start;
+        let mut current = self.take_edge(start, None, outgoing);
+        while current != start {
+            boundary.push(current);
+            let next = self.take_edge(current, Some(prev), outgoing);
+            prev = current;
+            current = next;
+        }
+        trace!("Traced boundary: {boundary:?}");
+        boundary
+    }
+
+    fn take_edge(
+        &self,
+        id: VertexId,
+        prev: Option<VertexId>,
+        outgoing: &mut HashMap<VertexId, Vec<VertexId>>,
+    ) -> VertexId {
+        let candidates = outgoing.get_mut(&id).unwrap();
+        // Where regions pinch together at a vertex there is more than one
+        // way out. Taking the first edge clockwise from the incoming edge
+        // stays in the same region, which splits pinched regions apart.
+        let i = match prev {
+            Some(prev) if candidates.len() > 1 => {
+                let v = &self.vertex_map[&id];
+                let u = &self.vertex_map[&prev];
+                let back = (u.y - v.y).atan2(u.x - v.x);
+                candidates
+                    .iter()
+                    .position_min_by_key(|w| {
+                        let w = &self.vertex_map[w];
+                        let angle = back - (w.y - v.y).atan2(w.x - v.x);
+                        OF(angle.rem_euclid(2.0 * PI))
+                    })
+                    .unwrap()
+            }
+            _ => 0,
+        };
+        let next = candidates.swap_remove(i);
+        if candidates.is_empty() {
+            outgoing.remove(&id);
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convex_hull::{ConvexHullComputer, GrahamScan};
+    use crate::geometry::Geometry;
+    use crate::test_util::*;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    #[test]
+    fn test_square_with_center() {
+        let coords = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0), (2.0, 1.0)];
+        let vertices = vertices_from_coords(&coords);
+        let shape = AlphaShape::from_vertices(&vertices);
+        assert_eq!(shape.delaunay().len(), 4);
+        assert!(shape.polygons(1.0).is_empty());
+
+        let polygons = shape.polygons(f64::MAX);
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(polygons[0].area(), 16.0);
+        assert!(polygons[0].holes.is_empty());
+    }
+
+    #[test]
+    fn test_grid_with_hole() {
+        // A 5x5 grid without its center point, small alpha keeps the unit
+        // cells away from the middle but not the triangles spanning the gap in the middle
+        let coords = (0..5)
+            .cartesian_product(0..5)
+            .filter(|(x, y)| (*x, *y) != (2, 2))
+            .map(|(x, y)| (x as f64, y as f64))
+            .collect_vec();
+        let vertices = vertices_from_coords(&coords);
+        let shape = AlphaShape::from_vertices(&vertices);
+
+        let polygons = shape.polygons(0.75);
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(polygons[0].outer.area(), 16.0);
+        assert_eq!(polygons[0].holes.len(), 1);
+        // How much of the cells around the gap is kept depends on which
+        // way the cocircular cells were split
+        assert!((2.0..=4.0).contains(&polygons[0].holes[0].area()));
+        assert!(!polygons[0].contains(&Vertex::new(VertexId::from(100u32), 2.0, 2.0)));
+        assert!(polygons[0].contains(&Vertex::new(VertexId::from(100u32), 0.5, 0.5)));
+
+        let polygons = shape.polygons(f64::MAX);
+        assert_eq!(polygons.len(), 1);
+        assert!(polygons[0].holes.is_empty());
+        assert_eq!(polygons[0].area(), 16.0);
+    }
+
+    #[apply(all_polygons)]
+    fn test_alpha_shape(case: PolygonTestCase) {
+        let shape = AlphaShape::from_vertices(case.polygon.vertices());
+        let hull = GrahamScan.convex_hull(&case.polygon, &mut None);
+
+        let alpha = shape.optimal_alpha().unwrap();
+        let polygons = shape.polygons(alpha);
+        assert_eq!(polygons.len(), 1);
+        assert!(polygons[0].outer.area() <= hull.area());
+
+        let polygons = shape.polygons(f64::MAX);
+        assert_eq!(polygons.len(), 1);
+        assert!(polygons[0].outer.area() <= hull.area());
+    }
+
+    #[test]
+    fn test_alpha_shape_tighter_than_hull() {
+        let case = skimage_horse();
+        let shape = AlphaShape::from_vertices(case.polygon.vertices());
+        let hull = GrahamScan.convex_hull(&case.polygon, &mut None);
+        let polygons = shape.polygons(shape.optimal_alpha().unwrap());
+        assert!(polygons[0].area() < 0.9 * hull.area());
+    }
+}
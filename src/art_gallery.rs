@@ -0,0 +1,278 @@
+use itertools::Itertools;
+use log::{debug, info};
+use ordered_float::OrderedFloat as OF;
+use std::collections::HashMap;
+
+use crate::{
+    clipping::SutherlandHodgman,
+    error::QueryPointError,
+    geometry::Geometry,
+    mesh::TriangleMesh,
+    overlay::{loop_area, loop_contains, Overlay},
+    polygon::Polygon,
+    triangulation::{EarClipping, Triangulation, TriangulationComputer},
+    vertex::VertexId,
+    visibility::AngularSweep,
+};
+
+/// Area of a triangle left unseen, relative to the square of the polygon's
+/// size, below which it is taken to be rounding error.
+const UNSEEN_AREA_TOLERANCE: f64 = 1e-9;
+
+/// A coloring of the vertices of a triangulated polygon with three colors
+/// such that the corners of every triangle have different colors, which
+/// is how Fisk proved that $\lfloor n/3 \rfloor$ guards are sufficient to
+/// see a polygon with $n$ vertices. Every triangle has a corner of each
+/// color, so the vertices of any one color see the whole polygon.
+pub struct ThreeColoring {
+    colors: HashMap<VertexId, usize>,
+}
+
+impl ThreeColoring {
+    /// Colors the vertices by walking the dual tree of the triangulation.
+    /// Each triangle after the first shares an edge with the triangle it
+    /// was reached from, so only its third corner needs a color and there
+    /// is exactly one left for it.
+    pub fn from_triangulation(triangulation: &Triangulation) -> Self {
+        info!("Computing three coloring of triangulation");
+        let mesh = TriangleMesh::from_triangulation(triangulation);
+        let mut colors = HashMap::new();
+        if mesh.num_triangles() > 0 {
+            for (t, _parent) in mesh.dual_traversal(0) {
+                let corners = mesh.triangle(t);
+                let corners = [corners.0, corners.1, corners.2];
+                let mut free = (0..3)
+                    .filter(|c| !corners.iter().any(|v| colors.get(v) == Some(c)))
+                    .collect_vec();
+                for v in corners {
+                    colors.entry(v).or_insert_with(|| free.pop().unwrap());
+                }
+            }
+        }
+        info!("Computed three coloring of {} vertices", colors.len());
+        ThreeColoring { colors }
+    }
+
+    /// The color of the vertex, which is 0, 1 or 2.
+    pub fn color(&self, id: &VertexId) -> Option<usize> {
+        self.colors.get(id).copied()
+    }
+
+    /// Vertices with the color, sorted by ID.
+    pub fn color_class(&self, color: usize) -> Vec<VertexId> {
+        self.colors
+            .iter()
+            .filter(|(_, c)| **c == color)
+            .map(|(id, _)| *id)
+            .sorted()
+            .collect_vec()
+    }
+
+    /// The smallest color class, which sees the whole polygon with at most
+    /// $\lfloor n/3 \rfloor$ guards.
+    pub fn guards(&self) -> Vec<VertexId> {
+        (0..3)
+            .map(|c| self.color_class(c))
+            .min_by_key(|class| class.len())
+            .unwrap()
+    }
+}
+
+/// Looks for a point of the polygon that none of the guards can see,
+/// returning `None` when the guards see all of it.
+///
+/// A guard that sees the three corners of a triangle inside the polygon
+/// sees the whole triangle, so the polygon is covered when each triangle of
+/// a triangulation of it has a guard seeing all its corners. Any other
+/// triangle is clipped out of the union of the guards' visibility polygons,
+/// and whatever is left of it is unseen. Pieces left over with an area
+/// under [`UNSEEN_AREA_TOLERANCE`] relative to the polygon's size are put
+/// down to rounding in the visibility polygons and ignored.
+pub fn unseen_point(
+    polygon: &Polygon,
+    guards: &[VertexId],
+) -> Result<Option<(f64, f64)>, QueryPointError> {
+    info!(
+        "Checking visibility of polygon from {} guards",
+        guards.len()
+    );
+    let guards = polygon.get_vertices(guards.iter().copied());
+    let triangulation = EarClipping.triangulation(polygon)?;
+    let mut visibility = None;
+    let bbox = polygon.bounding_box();
+    let size = (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+    let tolerance = UNSEEN_AREA_TOLERANCE * size * size;
+
+    for ids in triangulation.iter() {
+        let corners = [ids.0, ids.1, ids.2].map(|id| polygon.get_vertex(&id).unwrap());
+        if guards
+            .iter()
+            .any(|g| corners.iter().all(|v| polygon.sees(g, v)))
+        {
+            continue;
+        }
+
+        // Visibility polygons are only needed for triangles no one guard
+        // sees all of, so they are computed once the first one comes up
+        if visibility.is_none() {
+            let polygons = guards
+                .iter()
+                .map(|g| AngularSweep.visibility_polygon(polygon, g))
+                .collect::<Result<Vec<_>, _>>()?;
+            visibility = Some(polygons);
+        }
+        let visibility = visibility.as_ref().unwrap();
+
+        let triangle = Polygon::from_vertices(corners.map(|v| v.clone()).to_vec());
+        let window = triangle.bounding_box();
+        let mut overlay = Overlay::new(2);
+        overlay.add_loop(0, corners.map(|v| v.coords()).to_vec());
+        for seen in visibility.iter().map(|v| v.polygon()) {
+            let b = seen.bounding_box();
+            if b.max_x < window.min_x
+                || b.min_x > window.max_x
+                || b.max_y < window.min_y
+                || b.min_y > window.max_y
+            {
+                continue;
+            }
+            // The triangle is convex, so it is a valid clip window
+            for piece in SutherlandHodgman.clip(seen, &triangle).unwrap() {
+                let coords = piece
+                    .polygon()
+                    .vertices()
+                    .iter()
+                    .map(|v| v.coords())
+                    .collect_vec();
+                overlay.add_loop(1, coords);
+            }
+        }
+        let unseen = overlay.boundary(|w| w[0] > 0 && w[1] == 0);
+        let area: f64 = unseen.iter().map(|l| loop_area(l)).sum();
+        debug!(triangle:?=ids, area; "Area of triangle left unseen");
+        if area > tolerance {
+            let point = interior_point(&unseen);
+            info!("Found point not seen by any guard: {point:?}");
+            return Ok(Some(point));
+        }
+    }
+    info!("Guards see the whole polygon");
+    Ok(None)
+}
+
+/// A point inside a region given by its boundary loops, outer loops CCW
+/// and holes CW, as the centroid of an ear of the largest outer loop. An
+/// ear with no other point of the loops inside it is inside the region,
+/// since a hole overlapping it would have to have a corner in it.
+fn interior_point(loops: &[Vec<(f64, f64)>]) -> (f64, f64) {
+    let outer = loops.iter().max_by_key(|l| OF(loop_area(l))).unwrap();
+    let centroid = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        ((a.0 + b.0 + c.0) / 3.0, (a.1 + b.1 + c.1) / 3.0)
+    };
+    let ears = outer
+        .iter()
+        .circular_tuple_windows()
+        .filter(|(a, b, c)| loop_area(&[**a, **b, **c]) > 0.0)
+        .collect_vec();
+    ears.iter()
+        .find(|(a, b, c)| {
+            let ear = [**a, **b, **c];
+            loops
+                .iter()
+                .flatten()
+                .filter(|p| !ear.contains(p))
+                .all(|p| !loop_contains(&ear, *p))
+        })
+        .or(ears.first())
+        .map_or(outer[0], |(a, b, c)| centroid(**a, **b, **c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::vertex::Vertex;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    #[apply(all_polygons)]
+    fn test_art_gallery(case: PolygonTestCase) {
+        let triangulation = EarClipping.triangulation(&case.polygon).unwrap();
+        let coloring = ThreeColoring::from_triangulation(&triangulation);
+        let n = case.metadata.num_vertices;
+
+        for ids in triangulation.iter() {
+            let colors = [ids.0, ids.1, ids.2].map(|id| coloring.color(&id).unwrap());
+            assert!(colors.iter().all_unique());
+        }
+        let class_sizes = (0..3).map(|c| coloring.color_class(c).len()).collect_vec();
+        assert_eq!(class_sizes.iter().sum::<usize>(), n);
+
+        let guards = coloring.guards();
+        assert!(guards.len() <= n / 3);
+        assert_eq!(unseen_point(&case.polygon, &guards).unwrap(), None);
+    }
+
+    #[test]
+    fn test_unseen_point() {
+        // A comb with three teeth, where a guard at the tip of one tooth
+        // can't see into the others
+        let coords = vec![
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (9.0, 10.0),
+            (8.0, 2.0),
+            (6.0, 2.0),
+            (5.0, 10.0),
+            (4.0, 10.0),
+            (3.0, 2.0),
+            (1.0, 2.0),
+            (0.0, 10.0),
+        ];
+        let polygon = Polygon::from_coords(coords);
+        let guard = VertexId::from(2usize);
+        let (x, y) = unseen_point(&polygon, &[guard]).unwrap().unwrap();
+        let point = Vertex::new(VertexId::default(), x, y);
+        assert!(!polygon.sees(polygon.get_vertex(&guard).unwrap(), &point));
+
+        let guards = [0usize, 4, 8].map(VertexId::from);
+        assert_eq!(unseen_point(&polygon, &guards).unwrap(), None);
+    }
+
+    #[apply(all_polygons)]
+    fn test_unseen_point_with_few_guards(case: PolygonTestCase) {
+        // Every seventh vertex usually leaves parts of the polygon unseen,
+        // and when it doesn't the visibility polygons cover all of it
+        let polygon = &case.polygon;
+        let guards = polygon.vertex_ids().into_iter().step_by(7).collect_vec();
+        match unseen_point(polygon, &guards).unwrap() {
+            Some((x, y)) => {
+                let point = Vertex::new(VertexId::default(), x, y);
+                assert!(polygon.contains(&point));
+                for g in polygon.get_vertices(guards.clone()).iter() {
+                    assert!(!polygon.sees(g, &point));
+                }
+            }
+            None => {
+                let mut overlay = Overlay::new(1);
+                for g in polygon.get_vertices(guards.clone()).iter() {
+                    let seen = AngularSweep.visibility_polygon(polygon, g).unwrap();
+                    let coords = seen
+                        .polygon()
+                        .vertices()
+                        .iter()
+                        .map(|v| v.coords())
+                        .collect_vec();
+                    overlay.add_loop(0, coords);
+                }
+                let area: f64 = overlay
+                    .boundary(|w| w[0] > 0)
+                    .iter()
+                    .map(|l| loop_area(l))
+                    .sum();
+                assert!((area - polygon.area()).abs() <= 1e-6 * polygon.area());
+            }
+        }
+    }
+}
@@ -3,24 +3,35 @@ use itertools::Itertools;
 use random_color::RandomColor;
 
 use geometer::{
+    art_gallery::{unseen_point, ThreeColoring},
+    bounding_box::BoundingBox,
     convex_hull::{
         ConvexHullComputer, ConvexHullTracer, ConvexHullTracerStep, GrahamScan, Incremental,
         QuickHull,
     },
-    error::FileError,
+    convex_layers::ConvexLayers,
+    error::{FileError, QueryPointError, TriangulationError},
     geometry::Geometry,
     polygon::Polygon,
-    triangulation::{EarClipping, Triangulation, TriangulationComputer},
+    triangulation::{
+        EarClipping, EarRejection, TriangleVertexIds, Triangulation, TriangulationComputer,
+        TriangulationTracer,
+    },
     util::load_polygon,
     vertex::Vertex,
+    voronoi::{Voronoi, VoronoiEvent, VoronoiTracer},
 };
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum Visualization {
+    ArtGallery,
     ConvexHull,
     ConvexHullGrahamScan,
     ConvexHullIncremental,
+    ConvexLayers,
     Triangulation,
+    TriangulationEarClipping,
+    Voronoi,
 }
 
 /// Visualize polygons and algorithms using Rerun.io``
@@ -44,6 +55,8 @@ struct Args {
 pub enum VisualizationError {
     File(FileError),
     Rerun(rerun::RecordingStreamError),
+    Triangulation(TriangulationError),
+    QueryPoint(QueryPointError),
 }
 
 impl From<FileError> for VisualizationError {
@@ -58,6 +71,18 @@ impl From<rerun::RecordingStreamError> for VisualizationError {
     }
 }
 
+impl From<TriangulationError> for VisualizationError {
+    fn from(value: TriangulationError) -> Self {
+        VisualizationError::Triangulation(value)
+    }
+}
+
+impl From<QueryPointError> for VisualizationError {
+    fn from(value: QueryPointError) -> Self {
+        VisualizationError::QueryPoint(value)
+    }
+}
+
 pub struct RerunVisualizer {
     rec: rerun::RecordingStream,
 }
@@ -119,10 +144,8 @@ impl RerunVisualizer {
         name: &String,
     ) -> Result<(), VisualizationError> {
         let name = format!("{name}/triangulation");
-        let triangulation = EarClipping.triangulation(polygon);
-        let rerun_meshes = self.triangulation_to_rerun_meshes(&triangulation, polygon);
-
         let polygon_color = [132, 90, 109, 255];
+        let error_color = [163, 0, 0, 255];
         self.visualize_vertex_chain(
             &polygon.vertices().into_iter().cloned().collect_vec(),
             &name,
@@ -134,6 +157,28 @@ impl RerunVisualizer {
             true,
         )?;
 
+        let triangulation = match EarClipping.triangulation(polygon) {
+            Ok(triangulation) => triangulation,
+            Err(error) => {
+                // Highlight what was left of the polygon when no ear could
+                // be found, since that is where it fails to be simple
+                if let TriangulationError::EarNotFound(remaining) = &error {
+                    self.visualize_vertex_chain(
+                        remaining,
+                        &format!("{name}/remaining"),
+                        Some(0.8),
+                        Some(error_color),
+                        Some(0.3),
+                        Some(error_color),
+                        Some(100.0),
+                        true,
+                    )?;
+                }
+                return Err(error.into());
+            }
+        };
+        let rerun_meshes = self.triangulation_to_rerun_meshes(&triangulation, polygon);
+
         for (i, mesh) in rerun_meshes.iter().enumerate() {
             self.rec.log(format!("{name}/triangle_{i}"), mesh)?;
         }
@@ -141,6 +186,151 @@ impl RerunVisualizer {
         Ok(())
     }
 
+    pub fn visualize_triangulation_ear_clipping(
+        &self,
+        polygon: &Polygon,
+        name: &String,
+    ) -> Result<(), VisualizationError> {
+        let tracer = &mut Some(TriangulationTracer::default());
+        let result = EarClipping.triangulation_with_tracer(polygon, tracer);
+
+        let polygon_color = [132, 90, 109, 255];
+        let remaining_color = [25, 100, 126, 255];
+        let check_color = [242, 192, 53, 255];
+        let valid_color = [52, 163, 82, 255];
+        let invalid_color = [163, 0, 0, 255];
+
+        let mut frame: i64 = 0;
+        self.rec.set_time_sequence("frame", frame);
+
+        self.visualize_nominal_polygon(polygon, name, polygon_color)?;
+
+        // For each step show every candidate tested, marking why the
+        // rejected ones failed, followed by the ear that was clipped and
+        // what is left of the polygon after clipping it
+        for (i, step) in tracer.as_ref().unwrap().steps.iter().enumerate() {
+            for (j, candidate) in step.rejected.iter().enumerate() {
+                self.increment_frame(&mut frame);
+                let candidate_name = format!("{name}/alg_{i}/candidate_{j}");
+                self.rec.log(
+                    candidate_name.clone(),
+                    &self.triangle_to_rerun_mesh(&candidate.triangle, polygon, check_color),
+                )?;
+
+                self.increment_frame(&mut frame);
+                self.rec.log(
+                    candidate_name.clone(),
+                    &self.triangle_to_rerun_mesh(&candidate.triangle, polygon, invalid_color),
+                )?;
+                if let EarRejection::ContainsVertex(id) = candidate.rejection {
+                    let v = polygon.get_vertex(&id).unwrap();
+                    self.rec.log(
+                        format!("{candidate_name}/blocking_vertex"),
+                        &rerun::Points2D::new([(v.x as f32, v.y as f32)])
+                            .with_radii([1.0])
+                            .with_colors([invalid_color])
+                            .with_draw_order(100.0),
+                    )?;
+                }
+
+                self.increment_frame(&mut frame);
+                self.clear_recursive(candidate_name)?;
+            }
+
+            self.increment_frame(&mut frame);
+            self.rec.log(
+                format!("{name}/ear_{i}"),
+                &self.triangle_to_rerun_mesh(&step.triangle, polygon, valid_color),
+            )?;
+
+            self.clear_recursive(format!("{name}/remaining"))?;
+            if !step.remaining.is_empty() {
+                self.visualize_vertex_chain(
+                    &polygon.get_vertices(step.remaining.clone()),
+                    &format!("{name}/remaining"),
+                    Some(0.8),
+                    Some(remaining_color),
+                    Some(0.3),
+                    Some(remaining_color),
+                    Some(50.0),
+                    true,
+                )?;
+            }
+        }
+
+        result?;
+        Ok(())
+    }
+
+    pub fn visualize_art_gallery(
+        &self,
+        polygon: &Polygon,
+        name: &String,
+    ) -> Result<(), VisualizationError> {
+        let name = format!("{name}/art_gallery");
+        let polygon_color = [132, 90, 109, 255];
+        let diagonal_color = [90, 90, 90, 255];
+        let class_colors = [[25, 100, 126, 255], [242, 192, 53, 255], [52, 163, 82, 255]];
+        let guard_color = [255, 255, 255, 255];
+        let error_color = [163, 0, 0, 255];
+
+        self.visualize_nominal_polygon(polygon, &name, polygon_color)?;
+
+        let triangulation = EarClipping.triangulation(polygon)?;
+        let mut diagonals = Vec::new();
+        for ids in triangulation.iter() {
+            let t = polygon.get_triangle(&ids.0, &ids.1, &ids.2).unwrap();
+            for ls in t.to_line_segments() {
+                if polygon.next_vertex_id(&ls.v1.id) != Some(ls.v2.id) && ls.v1.id < ls.v2.id {
+                    diagonals.push([
+                        (ls.v1.x as f32, ls.v1.y as f32),
+                        (ls.v2.x as f32, ls.v2.y as f32),
+                    ]);
+                }
+            }
+        }
+        self.rec.log(
+            format!("{name}/diagonals"),
+            &rerun::LineStrips2D::new(diagonals)
+                .with_radii([0.05])
+                .with_colors([diagonal_color]),
+        )?;
+
+        let coloring = ThreeColoring::from_triangulation(&triangulation);
+        for (color, class_color) in class_colors.iter().enumerate() {
+            let class = polygon.get_vertices(coloring.color_class(color));
+            self.rec.log(
+                format!("{name}/color_{color}"),
+                &rerun::Points2D::new(class.iter().map(|v| (v.x as f32, v.y as f32)))
+                    .with_radii([0.8])
+                    .with_colors([*class_color])
+                    .with_draw_order(50.0),
+            )?;
+        }
+
+        // Guards are drawn as a ring around the vertices of their color
+        let guards = polygon.get_vertices(coloring.guards());
+        self.rec.log(
+            format!("{name}/guards"),
+            &rerun::Points2D::new(guards.iter().map(|v| (v.x as f32, v.y as f32)))
+                .with_radii([1.2])
+                .with_colors([guard_color])
+                .with_draw_order(40.0),
+        )?;
+
+        if let Some((x, y)) = unseen_point(polygon, &coloring.guards())? {
+            self.rec.log(
+                format!("{name}/unseen_point"),
+                &rerun::Points2D::new([(x as f32, y as f32)])
+                    .with_radii([1.0])
+                    .with_colors([error_color])
+                    .with_draw_order(100.0),
+            )?;
+        }
+
+        Ok(())
+    }
+
     pub fn visualize_convex_hull(
         &self,
         polygon: &Polygon,
@@ -425,6 +615,211 @@ impl RerunVisualizer {
         Ok(())
     }
 
+    pub fn visualize_convex_layers(
+        &self,
+        polygon: &Polygon,
+        name: &String,
+    ) -> Result<(), VisualizationError> {
+        let polygon_color = [132, 90, 109, 255];
+
+        let mut frame: i64 = 0;
+        self.rec.set_time_sequence("frame", frame);
+
+        self.visualize_nominal_polygon(polygon, name, polygon_color)?;
+
+        // Peel one layer per frame from the outside in, each in its own color
+        let layers = ConvexLayers::from_vertices(polygon.vertices());
+        for (i, layer) in layers.layers().iter().enumerate() {
+            self.increment_frame(&mut frame);
+            let layer_color = RandomColor::new().to_rgba_array();
+            self.visualize_vertex_chain(
+                &polygon.get_vertices(layer.clone()),
+                &format!("{name}/convex_layers/layer_{i}"),
+                Some(1.0),
+                Some(layer_color),
+                Some(0.3),
+                Some(layer_color),
+                Some(100.0),
+                true,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn visualize_voronoi(
+        &self,
+        polygon: &Polygon,
+        name: &String,
+    ) -> Result<(), VisualizationError> {
+        let name = format!("{name}/voronoi");
+        let tracer = &mut Some(VoronoiTracer::default());
+        let voronoi = Voronoi::from_vertices_with_tracer(polygon.vertices(), tracer);
+
+        let site_color = [132, 90, 109, 255];
+        let sweep_color = [242, 192, 53, 255];
+        let beach_color = [25, 100, 126, 255];
+        let edge_color = [52, 163, 82, 255];
+        let vertex_color = [255, 255, 255, 255];
+        let circle_color = [163, 0, 0, 255];
+
+        // Leave some room around the sites for the unbounded cells
+        let bbox = polygon.bounding_box();
+        let margin = 0.1 * (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+        let bbox = BoundingBox::new(
+            bbox.min_x - margin,
+            bbox.max_x + margin,
+            bbox.min_y - margin,
+            bbox.max_y + margin,
+        );
+
+        let mut frame: i64 = 0;
+        self.rec.set_time_sequence("frame", frame);
+
+        let sites = polygon.vertices();
+        self.rec.log(
+            format!("{name}/sites"),
+            &rerun::Points2D::new(sites.iter().map(|v| (v.x as f32, v.y as f32)))
+                .with_radii([0.5])
+                .with_colors([site_color])
+                .with_draw_order(50.0),
+        )?;
+
+        // For each event show the sweep line, the beach line above it and
+        // the edges its breakpoints have traced so far, marking the site
+        // reached or the circle whose bottom the sweep line reached
+        let mut found = Vec::new();
+        for step in tracer.as_ref().unwrap().steps.iter() {
+            self.increment_frame(&mut frame);
+            let sweep = step.sweep;
+            self.rec.log(
+                format!("{name}/sweep_line"),
+                &rerun::LineStrips2D::new([[
+                    (bbox.min_x as f32, sweep as f32),
+                    (bbox.max_x as f32, sweep as f32),
+                ]])
+                .with_radii([0.2])
+                .with_colors([sweep_color]),
+            )?;
+
+            let mut arcs = Vec::new();
+            for arc in step.beach_line.iter() {
+                let site = polygon.get_vertex(&arc.site).unwrap();
+                let h = site.y - sweep;
+                let (start, end) = (arc.start.max(bbox.min_x), arc.end.min(bbox.max_x));
+                if h <= 0.0 || start >= end {
+                    continue;
+                }
+                let num_samples = 32;
+                arcs.push(
+                    (0..=num_samples)
+                        .map(|k| {
+                            let x = start + (end - start) * k as f64 / num_samples as f64;
+                            let y = sweep + ((x - site.x).powi(2) + h * h) / (2.0 * h);
+                            (x as f32, y.min(bbox.max_y) as f32)
+                        })
+                        .collect_vec(),
+                );
+            }
+            self.rec.log(
+                format!("{name}/beach_line"),
+                &rerun::LineStrips2D::new(arcs)
+                    .with_radii([0.2])
+                    .with_colors([beach_color])
+                    .with_draw_order(40.0),
+            )?;
+
+            self.rec.log(
+                format!("{name}/edges"),
+                &rerun::LineStrips2D::new(
+                    step.edges
+                        .iter()
+                        .map(|[p, q]| [(p.0 as f32, p.1 as f32), (q.0 as f32, q.1 as f32)]),
+                )
+                .with_radii([0.15])
+                .with_colors([edge_color])
+                .with_draw_order(30.0),
+            )?;
+
+            self.clear_recursive(format!("{name}/event"))?;
+            match step.event {
+                VoronoiEvent::Site(id) => {
+                    let v = polygon.get_vertex(&id).unwrap();
+                    self.rec.log(
+                        format!("{name}/event/site"),
+                        &rerun::Points2D::new([(v.x as f32, v.y as f32)])
+                            .with_radii([1.0])
+                            .with_colors([sweep_color])
+                            .with_draw_order(100.0),
+                    )?;
+                }
+                VoronoiEvent::Circle(id) => {
+                    let v = voronoi.vertex(id);
+                    let num_samples = 64;
+                    let circle = (0..=num_samples)
+                        .map(|k| {
+                            let angle = 2.0 * std::f64::consts::PI * k as f64 / num_samples as f64;
+                            let x = v.x + v.radius * angle.cos();
+                            let y = v.y + v.radius * angle.sin();
+                            (x as f32, y as f32)
+                        })
+                        .collect_vec();
+                    self.rec.log(
+                        format!("{name}/event/circle"),
+                        &rerun::LineStrips2D::new([circle])
+                            .with_radii([0.1])
+                            .with_colors([circle_color])
+                            .with_draw_order(90.0),
+                    )?;
+                    found.push((v.x as f32, v.y as f32));
+                    self.rec.log(
+                        format!("{name}/vertices"),
+                        &rerun::Points2D::new(found.clone())
+                            .with_radii([0.6])
+                            .with_colors([vertex_color])
+                            .with_draw_order(60.0),
+                    )?;
+                }
+            }
+        }
+
+        // Once the sweep is done show every cell clipped to the box
+        self.increment_frame(&mut frame);
+        self.clear_recursive(format!("{name}/event"))?;
+        self.clear(format!("{name}/sweep_line"))?;
+        self.clear(format!("{name}/beach_line"))?;
+        self.clear(format!("{name}/edges"))?;
+        for (id, cell) in voronoi.clipped_cells(&bbox) {
+            let cell_color = RandomColor::new().to_rgba_array();
+            let vertices = cell.vertices().into_iter().cloned().collect_vec();
+            self.visualize_vertex_chain(
+                &vertices,
+                &format!("{name}/cells/cell_{id}"),
+                Some(0.3),
+                Some(edge_color),
+                Some(0.15),
+                Some(edge_color),
+                Some(30.0),
+                true,
+            )?;
+            // Cells are convex so fan out triangles from the first vertex
+            let points = vertices
+                .iter()
+                .skip(1)
+                .tuple_windows()
+                .flat_map(|(u, v)| [&vertices[0], u, v])
+                .map(|v| [v.x as f32, v.y as f32, 0.0])
+                .collect_vec();
+            let colors = vec![cell_color; points.len()];
+            self.rec.log(
+                format!("{name}/cells/cell_{id}/fill"),
+                &rerun::Mesh3D::new(points).with_vertex_colors(colors),
+            )?;
+        }
+
+        Ok(())
+    }
+
     fn visualize_nominal_polygon(
         &self,
         polygon: &Polygon,
@@ -483,19 +878,28 @@ impl RerunVisualizer {
         triangulation: &Triangulation,
         polygon: &Polygon,
     ) -> Vec<rerun::Mesh3D> {
-        let mut meshes = Vec::new();
-        for ids in triangulation.iter() {
-            let color = RandomColor::new().to_rgb_array();
-            let t = polygon.get_triangle(&ids.0, &ids.1, &ids.2).unwrap();
-            let points = [
-                [t.v1.x as f32, t.v1.y as f32, 0.0],
-                [t.v2.x as f32, t.v2.y as f32, 0.0],
-                [t.v3.x as f32, t.v3.y as f32, 0.0],
-            ];
-            let mesh = rerun::Mesh3D::new(points).with_vertex_colors([color, color, color]);
-            meshes.push(mesh);
-        }
-        meshes
+        triangulation
+            .iter()
+            .map(|ids| {
+                let color = RandomColor::new().to_rgba_array();
+                self.triangle_to_rerun_mesh(ids, polygon, color)
+            })
+            .collect_vec()
+    }
+
+    fn triangle_to_rerun_mesh(
+        &self,
+        ids: &TriangleVertexIds,
+        polygon: &Polygon,
+        color: [u8; 4],
+    ) -> rerun::Mesh3D {
+        let t = polygon.get_triangle(&ids.0, &ids.1, &ids.2).unwrap();
+        let points = [
+            [t.v1.x as f32, t.v1.y as f32, 0.0],
+            [t.v2.x as f32, t.v2.y as f32, 0.0],
+            [t.v3.x as f32, t.v3.y as f32, 0.0],
+        ];
+        rerun::Mesh3D::new(points).with_vertex_colors([color, color, color])
     }
 }
 
@@ -507,6 +911,7 @@ fn main() -> Result<(), VisualizationError> {
     let name = format!("{}/{}", args.polygon, args.folder);
 
     match args.visualization {
+        Visualization::ArtGallery => visualizer?.visualize_art_gallery(&polygon, &name)?,
         Visualization::ConvexHull => visualizer?.visualize_convex_hull(&polygon, &name)?,
         Visualization::ConvexHullGrahamScan => {
             visualizer?.visualize_convex_hull_graham_scan(&polygon, &name)?
@@ -514,7 +919,12 @@ fn main() -> Result<(), VisualizationError> {
         Visualization::ConvexHullIncremental => {
             visualizer?.visualize_convex_hull_incremental(&polygon, &name)?
         }
+        Visualization::ConvexLayers => visualizer?.visualize_convex_layers(&polygon, &name)?,
         Visualization::Triangulation => visualizer?.visualize_triangulation(&polygon, &name)?,
+        Visualization::TriangulationEarClipping => {
+            visualizer?.visualize_triangulation_ear_clipping(&polygon, &name)?
+        }
+        Visualization::Voronoi => visualizer?.visualize_voronoi(&polygon, &name)?,
     };
 
     Ok(())
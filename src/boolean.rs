@@ -0,0 +1,1155 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use ordered_float::OrderedFloat as OF;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::f64::consts::PI;
+
+use crate::{
+    data_structure::{ListHandle, OrderedList},
+    geometry::Geometry,
+    overlay::loop_area,
+    polygon::{Polygon, PolygonWithHoles},
+    predicates::orientation,
+    vertex::{Vertex, VertexId},
+};
+
+/// Relative distance from a point to the line through its neighbors below
+/// which it is dropped from a boundary, scaled by the size of the polygons.
+/// Rounding where edges cross can leave points this close to being in line.
+const COLLINEAR_TOLERANCE: f64 = 1e-12;
+
+type Point = (f64, f64);
+
+fn orient(a: Point, b: Point, c: Point) -> f64 {
+    let vertex = |(x, y)| Vertex::new(VertexId::default(), x, y);
+    orientation(&vertex(a), &vertex(b), &vertex(c))
+}
+
+/// Points in the order the sweep meets them, by x and then by y.
+fn point_order(p: Point, q: Point) -> Ordering {
+    p.0.total_cmp(&q.0).then(p.1.total_cmp(&q.1))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BooleanOp {
+    Union,
+    Intersection,
+    /// The points of the first polygon not in the second.
+    Difference,
+    /// The points in exactly one of the polygons.
+    Xor,
+}
+
+impl BooleanOp {
+    fn keeps(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            BooleanOp::Union => in_a || in_b,
+            BooleanOp::Intersection => in_a && in_b,
+            BooleanOp::Difference => in_a && !in_b,
+            BooleanOp::Xor => in_a != in_b,
+        }
+    }
+}
+
+/// How an edge relates to an edge of the other polygon lying on top of it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EdgeType {
+    Normal,
+    /// The second of two overlapping edges, which the first stands in for.
+    NonContributing,
+    /// Overlapping edges with both polygons on the same side.
+    SameTransition,
+    /// Overlapping edges with the polygons on opposite sides.
+    DifferentTransition,
+}
+
+/// One end of an edge. The sweep line goes left to right, and reaches the
+/// left end of each edge first, breaking ties of x by y.
+#[derive(Debug)]
+struct SweepEvent {
+    point: Point,
+    left: bool,
+    /// The event at the other end of the edge.
+    other: usize,
+    /// The ends of the input edge this one was split from, left first.
+    /// Orientation goes by it, so that rounded crossings splitting the
+    /// edge do not move points off it.
+    line: (Point, Point),
+    operand: usize,
+    /// The boundary the edge came from, to order overlapping edges of one
+    /// polygon.
+    contour: usize,
+    edge_type: EdgeType,
+    /// Whether going up across the edge leaves its own polygon.
+    in_out: bool,
+    /// Whether the edge is outside the other polygon, which is whether
+    /// going up across the nearest edge of it below leaves it.
+    other_in_out: bool,
+    in_result: bool,
+    /// For edges in the result, whether the result is above the edge.
+    result_above: bool,
+    /// The nearest edge below in the result, when this edge was reached.
+    prev_in_result: Option<usize>,
+    status: Option<ListHandle>,
+    processed: bool,
+    /// Bumped when the event changes while it is queued, so that its old
+    /// entry in the queue is skipped.
+    version: u32,
+}
+
+/// What the event queue orders events by, taken when they are pushed.
+#[derive(Clone, Copy, Debug)]
+struct EventKey {
+    point: Point,
+    line: (Point, Point),
+    left: bool,
+    operand: usize,
+    index: usize,
+}
+
+impl EventKey {
+    /// Orientation of a point against the line of the edge.
+    fn side(&self, p: Point) -> f64 {
+        orient(self.line.0, self.line.1, p)
+    }
+}
+
+impl Ord for EventKey {
+    /// Events at one point go right ends first, then lower edges first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        point_order(self.point, other.point)
+            .then_with(|| self.left.cmp(&other.left))
+            .then_with(|| {
+                let far = if other.left {
+                    other.line.1
+                } else {
+                    other.line.0
+                };
+                match self.side(far) {
+                    s if s > 0.0 => Ordering::Less,
+                    s if s < 0.0 => Ordering::Greater,
+                    _ => self.operand.cmp(&other.operand),
+                }
+            })
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+impl PartialOrd for EventKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for EventKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for EventKey {}
+
+/// Boolean operation between two polygons with holes, giving the pieces of
+/// the result sorted by decreasing area.
+///
+/// This is the plane sweep of Martinez, Rueda and Feito. The edges of both
+/// polygons are swept left to right, split where they cross each other,
+/// and the status keeps the edges crossing the sweep line from bottom to
+/// top. When an edge is reached, the edge below it tells whether it is
+/// inside its own polygon and the other one, which decides whether the
+/// result is on exactly one side of it. Overlapping edges of the two
+/// polygons are kept once, marked by whether they have the polygons on the
+/// same or opposite sides. The edges in the result are then joined into
+/// boundaries, and the nearest edge of the result below each hole tells
+/// which polygon it is in. This takes
+/// $O((n + k) \log n)$ for $k$ crossings.
+///
+/// Parts of the result touching at a vertex are kept apart as separate
+/// polygons or as a hole touching the outer boundary.
+pub fn boolean(a: &PolygonWithHoles, b: &PolygonWithHoles, op: BooleanOp) -> Vec<PolygonWithHoles> {
+    info!("Computing {op:?} of polygons");
+    let bounds = [a, b].map(|polygon| polygon.outer.bounding_box());
+    let size = bounds
+        .iter()
+        .map(|bbox| (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y))
+        .fold(0.0, f64::max);
+    let mut sweep = Sweep::new(op, COLLINEAR_TOLERANCE * size);
+    let mut contour = 0;
+    for (operand, polygon) in [a, b].into_iter().enumerate() {
+        for boundary in [&polygon.outer].into_iter().chain(polygon.holes.iter()) {
+            let points = boundary.vertices().iter().map(|v| v.coords()).collect_vec();
+            for (p, q) in points.into_iter().circular_tuple_windows() {
+                sweep.add_edge(p, q, operand, contour);
+            }
+            contour += 1;
+        }
+    }
+
+    // Past the right end of the first polygon there is nothing left of a
+    // difference, and past either right end nothing of an intersection
+    let right_end = match op {
+        BooleanOp::Intersection => Some(bounds[0].max_x.min(bounds[1].max_x)),
+        BooleanOp::Difference => Some(bounds[0].max_x),
+        BooleanOp::Union | BooleanOp::Xor => None,
+    };
+    let sorted = sweep.run(right_end);
+    let pieces = sweep.connect(&sorted);
+    info!(
+        "Computed {op:?} with {} polygons and {} holes",
+        pieces.len(),
+        pieces.iter().map(|p| p.holes.len()).sum::<usize>()
+    );
+    pieces
+}
+
+struct Sweep {
+    op: BooleanOp,
+    events: Vec<SweepEvent>,
+    queue: BinaryHeap<Reverse<(EventKey, u32)>>,
+    status: OrderedList<usize>,
+    num_splits: usize,
+    tolerance: f64,
+}
+
+impl Sweep {
+    fn new(op: BooleanOp, tolerance: f64) -> Self {
+        Sweep {
+            op,
+            events: Vec::new(),
+            queue: BinaryHeap::new(),
+            status: OrderedList::new(),
+            num_splits: 0,
+            tolerance,
+        }
+    }
+
+    fn key(&self, e: usize) -> EventKey {
+        let event = &self.events[e];
+        EventKey {
+            point: event.point,
+            line: event.line,
+            left: event.left,
+            operand: event.operand,
+            index: e,
+        }
+    }
+
+    fn other_point(&self, e: usize) -> Point {
+        self.events[self.events[e].other].point
+    }
+
+    fn vertical(&self, e: usize) -> bool {
+        self.events[e].point.0 == self.other_point(e).0
+    }
+
+    fn new_event(&mut self, point: Point, left: bool, other: usize, like: usize) -> usize {
+        let like = &self.events[like];
+        let (line, operand, contour) = (like.line, like.operand, like.contour);
+        self.events.push(SweepEvent {
+            point,
+            left,
+            other,
+            line,
+            operand,
+            contour,
+            edge_type: EdgeType::Normal,
+            in_out: false,
+            other_in_out: false,
+            in_result: false,
+            result_above: false,
+            prev_in_result: None,
+            status: None,
+            processed: false,
+            version: 0,
+        });
+        self.events.len() - 1
+    }
+
+    fn add_edge(&mut self, p: Point, q: Point, operand: usize, contour: usize) {
+        if p == q {
+            return;
+        }
+        let (e, f) = (self.events.len(), self.events.len() + 1);
+        let left = point_order(p, q) == Ordering::Less;
+        let line = if left { (p, q) } else { (q, p) };
+        for (point, left, other) in [(p, left, f), (q, !left, e)] {
+            self.events.push(SweepEvent {
+                point,
+                left,
+                other,
+                line,
+                operand,
+                contour,
+                edge_type: EdgeType::Normal,
+                in_out: false,
+                other_in_out: false,
+                in_result: false,
+                result_above: false,
+                prev_in_result: None,
+                status: None,
+                processed: false,
+                version: 0,
+            });
+        }
+        self.push(e);
+        self.push(f);
+    }
+
+    fn push(&mut self, e: usize) {
+        self.queue
+            .push(Reverse((self.key(e), self.events[e].version)));
+    }
+
+    /// Requeues an event that changed while it was waiting in the queue.
+    fn touch(&mut self, e: usize) {
+        if !self.events[e].processed {
+            self.events[e].version += 1;
+            self.push(e);
+        }
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        while let Some(Reverse((key, version))) = self.queue.pop() {
+            if self.events[key.index].version == version && !self.events[key.index].processed {
+                return Some(key.index);
+            }
+        }
+        None
+    }
+
+    /// Runs the sweep, up to the x-coordinate if there is one, and gives
+    /// back the events in the order they were reached.
+    fn run(&mut self, right_end: Option<f64>) -> Vec<usize> {
+        let mut sorted = Vec::new();
+        while let Some(e) = self.pop() {
+            self.events[e].processed = true;
+            if right_end.is_some_and(|x| self.events[e].point.0 > x) {
+                trace!("Stopping the sweep at {:?}", self.events[e].point);
+                break;
+            }
+            sorted.push(e);
+            if self.events[e].left {
+                let at = self
+                    .status
+                    .partition_point(|h| self.segment_order(self.status[h], e) == Ordering::Less);
+                let handle = self.status.insert_before(at, e);
+                self.events[e].status = Some(handle);
+                let prev = self.status.prev(handle);
+                let next = self.status.next(handle).map(|h| self.status[h]);
+                self.compute_fields(e, prev.map(|h| self.status[h]));
+                if let Some(next) = next {
+                    if self.possible_intersection(e, next) == Crossing::Overlap {
+                        self.compute_fields(e, prev.map(|h| self.status[h]));
+                        self.compute_fields(next, Some(e));
+                    }
+                }
+                if let Some(prev) = prev {
+                    let below = self.status[prev];
+                    if self.possible_intersection(below, e) == Crossing::Overlap {
+                        let prev_prev = self.status.prev(prev).map(|h| self.status[h]);
+                        self.compute_fields(below, prev_prev);
+                        self.compute_fields(e, Some(below));
+                    }
+                }
+                // An edge going on past the point was split there, and its
+                // part past it is not in the status yet, so the event goes
+                // back to be reached again after the new right end
+                let point = self.events[e].point;
+                let neighbours = [prev.map(|h| self.status[h]), next];
+                if neighbours
+                    .into_iter()
+                    .flatten()
+                    .any(|n| self.other_point(n) == point)
+                {
+                    self.status.remove(handle);
+                    self.events[e].status = None;
+                    self.events[e].processed = false;
+                    sorted.pop();
+                    self.touch(e);
+                }
+            } else {
+                let left = self.events[e].other;
+                if let Some(handle) = self.events[left].status.take() {
+                    let prev = self.status.prev(handle).map(|h| self.status[h]);
+                    let next = self.status.next(handle).map(|h| self.status[h]);
+                    self.status.remove(handle);
+                    if let (Some(prev), Some(next)) = (prev, next) {
+                        self.possible_intersection(prev, next);
+                    }
+                }
+            }
+        }
+        debug!(
+            "Swept {} events after splitting edges {} times",
+            sorted.len(),
+            self.num_splits
+        );
+        sorted
+    }
+
+    /// Orientation of a point against the line of an edge. The ends of the
+    /// edge, and points between them within the tolerance of the line, are
+    /// taken to be on it.
+    fn side(&self, e: usize, p: Point) -> f64 {
+        let (q, r) = (self.events[e].point, self.other_point(e));
+        if p == q || p == r {
+            return 0.0;
+        }
+        let (a, b) = self.events[e].line;
+        let o = orient(a, b, p);
+        let between = point_order(a, p) != point_order(b, p);
+        if between && o.abs() <= self.tolerance * (b.0 - a.0).hypot(b.1 - a.1) {
+            0.0
+        } else {
+            o
+        }
+    }
+
+    /// Whether the edges are in line, which is when two of their ends are
+    /// on the other edge, between its ends.
+    fn in_line(&self, e: usize, f: usize) -> bool {
+        let ends = [
+            (e, self.events[f].point),
+            (e, self.other_point(f)),
+            (f, self.events[e].point),
+            (f, self.other_point(e)),
+        ];
+        let on = ends.map(|(e, p)| {
+            let (q, r) = (self.events[e].point, self.other_point(e));
+            point_order(q, p) != Ordering::Greater
+                && point_order(p, r) != Ordering::Greater
+                && self.side(e, p) == 0.0
+        });
+        let distinct = (0..4)
+            .filter(|i| on[*i] && !(0..*i).any(|j| on[j] && ends[j].1 == ends[*i].1))
+            .count();
+        distinct >= 2
+    }
+
+    /// Order of the edges of two left events along the sweep line, from
+    /// bottom to top.
+    fn segment_order(&self, e: usize, f: usize) -> Ordering {
+        if e == f {
+            return Ordering::Equal;
+        }
+        let (ep, eq) = (self.events[e].point, self.other_point(e));
+        let (fp, fq) = (self.events[f].point, self.other_point(f));
+        let (ke, kf) = (self.key(e), self.key(f));
+        if !self.in_line(e, f) {
+            // Not in line, so compare where the edge reached later starts
+            // against the other edge, or where it ends if it starts on it
+            let above = |e: usize, p: Point, q: Point| {
+                let s = self.side(e, p);
+                if s == 0.0 {
+                    self.side(e, q) > 0.0
+                } else {
+                    s > 0.0
+                }
+            };
+            if ep.0 == fp.0 && ep != fp {
+                return point_order(ep, fp);
+            }
+            return if ke > kf {
+                if above(f, ep, eq) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Less
+                }
+            } else if above(e, fp, fq) {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            };
+        }
+        if self.events[e].operand != self.events[f].operand {
+            return self.events[e].operand.cmp(&self.events[f].operand);
+        }
+        if ep == fp {
+            if eq == fq {
+                return e.cmp(&f);
+            }
+            return self.events[e]
+                .contour
+                .cmp(&self.events[f].contour)
+                .then(e.cmp(&f));
+        }
+        ke.cmp(&kf)
+    }
+
+    /// Sets whether the edge of the left event is inside each polygon and
+    /// in the result, from the edge right below it.
+    fn compute_fields(&mut self, e: usize, prev: Option<usize>) {
+        match prev {
+            None => {
+                self.events[e].in_out = false;
+                self.events[e].other_in_out = true;
+            }
+            Some(prev) => {
+                let below = &self.events[prev];
+                let (in_out, other_in_out) = if below.operand == self.events[e].operand {
+                    (!below.in_out, below.other_in_out)
+                } else if self.vertical(prev) {
+                    (!below.other_in_out, !below.in_out)
+                } else {
+                    (!below.other_in_out, below.in_out)
+                };
+                let prev_in_result = if !below.in_result || self.vertical(prev) {
+                    below.prev_in_result
+                } else {
+                    Some(prev)
+                };
+                let event = &mut self.events[e];
+                event.in_out = in_out;
+                event.other_in_out = other_in_out;
+                event.prev_in_result = prev_in_result;
+            }
+        }
+
+        // Whether each polygon is above and below the edge, which flips
+        // across it for its own polygon and for the other one only where
+        // they overlap
+        let event = &self.events[e];
+        let own = !event.in_out;
+        let other = match event.edge_type {
+            EdgeType::Normal => Some((!event.other_in_out, !event.other_in_out)),
+            EdgeType::SameTransition => Some((own, !own)),
+            EdgeType::DifferentTransition => Some((!own, own)),
+            EdgeType::NonContributing => None,
+        };
+        let (in_result, result_above) = match other {
+            Some((other_above, other_below)) => {
+                let keeps = |own, other| match event.operand {
+                    0 => self.op.keeps(own, other),
+                    _ => self.op.keeps(other, own),
+                };
+                let above = keeps(own, other_above);
+                (above != keeps(!own, other_below), above)
+            }
+            None => (false, false),
+        };
+        let event = &mut self.events[e];
+        event.in_result = in_result;
+        event.result_above = result_above;
+    }
+
+    /// Where the edges of two left events meet, splitting them there.
+    fn possible_intersection(&mut self, e: usize, f: usize) -> Crossing {
+        let (ep, eq) = (self.events[e].point, self.other_point(e));
+        let (fp, fq) = (self.events[f].point, self.other_point(f));
+        if !self.in_line(e, f) {
+            let (o1, o2) = (self.side(e, fp), self.side(e, fq));
+            let (o3, o4) = (self.side(f, ep), self.side(f, eq));
+            if (o1 > 0.0 && o2 > 0.0)
+                || (o1 < 0.0 && o2 < 0.0)
+                || (o3 > 0.0 && o4 > 0.0)
+                || (o3 < 0.0 && o4 < 0.0)
+            {
+                return Crossing::None;
+            }
+            let point = if o1 == 0.0 {
+                fp
+            } else if o2 == 0.0 {
+                fq
+            } else if o3 == 0.0 {
+                ep
+            } else if o4 == 0.0 {
+                eq
+            } else {
+                // Where the lines cross, rounded, and then kept within
+                // both edges, or taken to be an end of either within the
+                // tolerance of it
+                let (a, b) = self.events[e].line;
+                let (g, h) = self.events[f].line;
+                let (s, t) = (orient(g, h, a), orient(g, h, b));
+                let t = s / (s - t);
+                let (x, y) = (a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1));
+                let clamp = |v: f64, a: f64, b: f64, c: f64, d: f64| {
+                    v.clamp(a.min(b).max(c.min(d)), a.max(b).min(c.max(d)))
+                };
+                let point = (
+                    clamp(x, ep.0, eq.0, fp.0, fq.0),
+                    clamp(y, ep.1, eq.1, fp.1, fq.1),
+                );
+                let distance = |p: &Point| (p.0 - point.0).hypot(p.1 - point.1);
+                let within = |p: Point, q: Point, r: &Point| {
+                    point_order(p, *r) != Ordering::Greater
+                        && point_order(*r, q) != Ordering::Greater
+                };
+                [ep, eq]
+                    .into_iter()
+                    .filter(|p| within(fp, fq, p))
+                    .chain([fp, fq].into_iter().filter(|p| within(ep, eq, p)))
+                    .filter(|p| distance(p) <= self.tolerance)
+                    .min_by_key(|p| OF(distance(p)))
+                    .unwrap_or(point)
+            };
+            if ep == fp || eq == fq {
+                return Crossing::None;
+            }
+            let within = |p: Point, q: Point| {
+                point_order(p, point) == Ordering::Less && point_order(point, q) == Ordering::Less
+            };
+            if within(ep, eq) {
+                self.divide(e, point);
+            }
+            if within(fp, fq) {
+                self.divide(f, point);
+            }
+            return Crossing::Point;
+        }
+
+        // In line, so they overlap if each starts before the other ends
+        let start = if point_order(ep, fp) == Ordering::Less {
+            fp
+        } else {
+            ep
+        };
+        let end = if point_order(eq, fq) == Ordering::Less {
+            eq
+        } else {
+            fq
+        };
+        if point_order(start, end) != Ordering::Less {
+            return Crossing::None;
+        }
+        if self.events[e].operand == self.events[f].operand {
+            trace!("Edges of one polygon overlap: {ep:?}-{eq:?} and {fp:?}-{fq:?}");
+            return Crossing::None;
+        }
+        let (ke, kf) = (self.key(e), self.key(f));
+        let (er, fr) = (self.events[e].other, self.events[f].other);
+        let left_coincide = ep == fp;
+        let right_coincide = eq == fq;
+        let lefts = if ke > kf { (f, e) } else { (e, f) };
+        let rights = if self.key(er) > self.key(fr) {
+            (fr, er)
+        } else {
+            (er, fr)
+        };
+        if left_coincide {
+            // The second edge is left out, and the first one marked by
+            // whether the polygons are on the same side of it
+            self.events[f].edge_type = EdgeType::NonContributing;
+            self.events[e].edge_type = if self.events[e].in_out == self.events[f].in_out {
+                EdgeType::SameTransition
+            } else {
+                EdgeType::DifferentTransition
+            };
+            if !right_coincide {
+                // Split off the part of the longer edge past the shorter
+                let longer = self.events[rights.1].other;
+                let point = self.events[rights.0].point;
+                self.divide(longer, point);
+            }
+            return Crossing::Overlap;
+        }
+        if right_coincide {
+            let point = self.events[lefts.1].point;
+            self.divide(lefts.0, point);
+            return Crossing::Partial;
+        }
+        if lefts.0 != self.events[rights.1].other {
+            // Neither contains the other
+            let (p1, p2) = (self.events[lefts.1].point, self.events[rights.0].point);
+            self.divide(lefts.0, p1);
+            self.divide(lefts.1, p2);
+            return Crossing::Partial;
+        }
+        // The first one contains the second, so it is split at both ends
+        // of it, the second time through the part after the first split
+        let (p1, p2) = (self.events[lefts.1].point, self.events[rights.0].point);
+        self.divide(lefts.0, p1);
+        let middle = self.events[rights.1].other;
+        self.divide(middle, p2);
+        Crossing::Partial
+    }
+
+    /// Splits the edge of the left event at the point, which becomes the
+    /// right end of the edge and the left end of a new one.
+    fn divide(&mut self, e: usize, point: Point) {
+        let right = self.events[e].other;
+        let r = self.new_event(point, false, e, e);
+        let l = self.new_event(point, true, right, e);
+        // Rounding may have put the point past the right end
+        if self.key(l) > self.key(right) {
+            self.events[right].left = true;
+            self.events[l].left = false;
+        }
+        self.events[right].other = l;
+        self.events[e].other = r;
+        self.touch(right);
+        self.push(l);
+        self.push(r);
+        self.num_splits += 1;
+    }
+
+    /// Joins the edges in the result into boundaries and sorts them into
+    /// polygons with holes. Going into a vertex, a boundary leaves along the
+    /// first edge clockwise, which keeps apart parts of the result touching
+    /// there, and where it still comes back to a vertex it is split in two.
+    fn connect(&self, sorted: &[usize]) -> Vec<PolygonWithHoles> {
+        let mut nodes: HashMap<(u64, u64), usize> = HashMap::new();
+        let mut points = Vec::new();
+        let mut node = |p: Point| {
+            *nodes
+                .entry((p.0.to_bits(), p.1.to_bits()))
+                .or_insert_with(|| {
+                    points.push(p);
+                    points.len() - 1
+                })
+        };
+        // Edges go with the result on their left, and are kept with the
+        // left event they came from
+        let mut edges = Vec::new();
+        for &e in sorted {
+            let event = &self.events[e];
+            if !event.left || !event.in_result {
+                continue;
+            }
+            let (p, q) = (node(event.point), node(self.other_point(e)));
+            edges.push(if event.result_above {
+                (p, q, e)
+            } else {
+                (q, p, e)
+            });
+        }
+        let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, (from, _, _)) in edges.iter().enumerate() {
+            outgoing.entry(*from).or_default().push(i);
+        }
+        trace!("Joining {} edges in the result", edges.len());
+
+        let angle = |i: usize| {
+            let (p, q) = (points[edges[i].0], points[edges[i].1]);
+            (q.1 - p.1).atan2(q.0 - p.0)
+        };
+        let mut used = vec![false; edges.len()];
+        let mut boundaries: Vec<Vec<usize>> = Vec::new();
+        for start in 0..edges.len() {
+            if used[start] {
+                continue;
+            }
+            let mut path = Vec::new();
+            let mut i = start;
+            loop {
+                used[i] = true;
+                path.push(i);
+                let back = angle(i) + PI;
+                let next = outgoing[&edges[i].1]
+                    .iter()
+                    .filter(|j| !used[**j] || **j == start)
+                    .min_by_key(|j| {
+                        let clockwise = (back - angle(**j)).rem_euclid(2.0 * PI);
+                        OF(if clockwise <= 0.0 {
+                            2.0 * PI
+                        } else {
+                            clockwise
+                        })
+                    });
+                match next {
+                    Some(&j) if j != start => i = j,
+                    _ => break,
+                }
+            }
+            let mut k = 0;
+            while k < path.len() {
+                let from = edges[path[k]].0;
+                if let Some(j) = path[..k].iter().position(|i| edges[*i].0 == from) {
+                    boundaries.push(path.drain(j..k).collect_vec());
+                    k = j;
+                }
+                k += 1;
+            }
+            boundaries.push(path);
+        }
+
+        // Holes go CW, and each is in the polygon of the nearest boundary
+        // below the first edge of it the sweep reached, or in the polygon
+        // that boundary is a hole in
+        let position: HashMap<usize, usize> =
+            sorted.iter().enumerate().map(|(i, e)| (*e, i)).collect();
+        let mut boundary_of = HashMap::new();
+        for (b, boundary) in boundaries.iter().enumerate() {
+            for i in boundary {
+                boundary_of.insert(edges[*i].2, b);
+            }
+        }
+        let firsts = boundaries
+            .iter()
+            .map(|boundary| {
+                boundary
+                    .iter()
+                    .map(|i| edges[*i].2)
+                    .min_by_key(|e| position[e])
+                    .unwrap()
+            })
+            .collect_vec();
+        let loops = boundaries
+            .iter()
+            .map(|boundary| {
+                let points = boundary.iter().map(|i| points[edges[*i].0]).collect_vec();
+                clean_loop(points, self.tolerance)
+            })
+            .collect_vec();
+        let is_hole = loops
+            .iter()
+            .map(|points| {
+                points
+                    .as_ref()
+                    .is_some_and(|points| loop_area(points) < 0.0)
+            })
+            .collect_vec();
+        let mut parent: Vec<Option<usize>> = vec![None; boundaries.len()];
+        for b in (0..boundaries.len()).sorted_by_key(|b| position[&firsts[*b]]) {
+            if !is_hole[b] {
+                continue;
+            }
+            let mut below = self.events[firsts[b]].prev_in_result;
+            while let Some(e) = below {
+                if boundary_of.contains_key(&e) {
+                    break;
+                }
+                below = self.events[e].prev_in_result;
+            }
+            if let Some(below) = below {
+                let lower = boundary_of[&below];
+                parent[b] = if is_hole[lower] {
+                    parent[lower]
+                } else {
+                    Some(lower)
+                };
+            }
+        }
+
+        let mut holes_in = vec![Vec::new(); boundaries.len()];
+        for b in 0..boundaries.len() {
+            let Some(points) = &loops[b] else {
+                continue;
+            };
+            if !is_hole[b] {
+                continue;
+            }
+            match parent[b] {
+                Some(p) if loops[p].is_some() => {
+                    let hole = points.iter().rev().copied().collect_vec();
+                    holes_in[p].push(Polygon::from_coords(hole));
+                }
+                _ => debug!("Dropping hole outside every outer boundary: {points:?}"),
+            }
+        }
+        loops
+            .into_iter()
+            .zip(holes_in)
+            .enumerate()
+            .filter(|(b, _)| !is_hole[*b])
+            .filter_map(|(_, (points, holes))| {
+                Some(PolygonWithHoles::new(Polygon::from_coords(points?), holes))
+            })
+            .sorted_by_key(|piece| Reverse(OF(piece.area())))
+            .collect_vec()
+    }
+}
+
+/// How the edges of two left events meet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Crossing {
+    None,
+    /// At a single point, where they have been split.
+    Point,
+    /// Along a stretch starting at the left end of both.
+    Overlap,
+    /// Along a stretch, where they have been split so the overlapping
+    /// parts start together.
+    Partial,
+}
+
+/// The points of the boundary, leaving out those in a line with their
+/// neighbors as the ends of an edge split in two are, or `None` if it has
+/// no area left.
+fn clean_loop(mut points: Vec<Point>, tolerance: f64) -> Option<Vec<Point>> {
+    let mut changed = true;
+    while changed && points.len() >= 3 {
+        changed = false;
+        let n = points.len();
+        if let Some(i) = (0..n).find(|i| {
+            let (p, v, q) = (points[(i + n - 1) % n], points[*i], points[(i + 1) % n]);
+            let length = (q.0 - p.0).hypot(q.1 - p.1);
+            let cross = (v.0 - p.0) * (q.1 - p.1) - (v.1 - p.1) * (q.0 - p.0);
+            length == 0.0 || cross.abs() <= tolerance * length
+        }) {
+            points.remove(i);
+            changed = true;
+        }
+    }
+    (points.len() >= 3).then_some(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::overlay::Overlay;
+    use crate::test_util::*;
+    use crate::triangulation::{EarClipping, TriangulationComputer};
+    use crate::F64_ASSERT_PRECISION;
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    const OPS: [BooleanOp; 4] = [
+        BooleanOp::Union,
+        BooleanOp::Intersection,
+        BooleanOp::Difference,
+        BooleanOp::Xor,
+    ];
+
+    fn square(x: f64, y: f64, size: f64) -> Polygon {
+        Polygon::from_coords(square_coords(x, y, size))
+    }
+
+    fn total_area(pieces: &[PolygonWithHoles]) -> f64 {
+        pieces.iter().map(|p| p.area()).sum()
+    }
+
+    fn size(polygon: &Polygon) -> f64 {
+        let bbox = polygon.bounding_box();
+        (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y)
+    }
+
+    #[apply(all_polygons)]
+    fn test_boolean(case: PolygonTestCase) {
+        // The polygon against a copy of itself turned a little about its
+        // center and shifted along
+        let a = case.polygon;
+        let bbox = a.bounding_box();
+        let size = (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+        let (cx, cy) = bbox.center();
+        let mut b = a.clone();
+        b.translate(-cx, -cy);
+        b.rotate_about_origin(0.3);
+        b.translate(cx + 0.1 * size, cy + 0.05 * size);
+        let (a, b) = (PolygonWithHoles::from(a), PolygonWithHoles::from(b));
+
+        let areas = OPS.map(|op| total_area(&boolean(&a, &b, op)));
+        let [union, intersection, difference, xor] = areas;
+        let tolerance = 1e-9 * size * size;
+        assert_approx_eq!(union + intersection, a.area() + b.area(), tolerance);
+        assert_approx_eq!(difference + intersection, a.area(), tolerance);
+        assert_approx_eq!(xor, union - intersection, tolerance);
+
+        // Points of triangles inside either polygon are in the result when
+        // the operation keeps them
+        let mut points = Vec::new();
+        for polygon in [&a.outer, &b.outer] {
+            let triangulation = EarClipping.triangulation(polygon).unwrap();
+            for ids in triangulation.iter() {
+                points.push(triangle_centroid(polygon, ids));
+            }
+        }
+        for op in OPS {
+            let pieces = boolean(&a, &b, op);
+            for p in points.iter() {
+                let expected = op.keeps(a.contains(p), b.contains(p));
+                assert_eq!(pieces.iter().any(|piece| piece.contains(p)), expected);
+            }
+        }
+
+        // With itself, the union and intersection are the polygon and
+        // nothing is left over
+        for op in OPS {
+            let pieces = boolean(&a, &a, op);
+            if op == BooleanOp::Union || op == BooleanOp::Intersection {
+                assert_eq!(pieces.len(), 1);
+                assert_approx_eq!(pieces[0].area(), a.area(), tolerance);
+            } else {
+                assert!(pieces.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_boolean_shared_edge() {
+        let a = PolygonWithHoles::from(square(0.0, 0.0, 2.0));
+        let b = PolygonWithHoles::from(square(2.0, 0.0, 2.0));
+        let union = boolean(&a, &b, BooleanOp::Union);
+        assert_eq!(union.len(), 1);
+        assert_eq!(union[0].outer.num_vertices(), 4);
+        assert_approx_eq!(union[0].area(), 8.0, F64_ASSERT_PRECISION);
+        assert!(boolean(&a, &b, BooleanOp::Intersection).is_empty());
+        let difference = boolean(&a, &b, BooleanOp::Difference);
+        assert_eq!(difference.len(), 1);
+        assert_eq!(difference[0].outer.num_vertices(), 4);
+        assert_approx_eq!(difference[0].area(), 4.0, F64_ASSERT_PRECISION);
+
+        // Overlapping along part of an edge
+        let b = PolygonWithHoles::from(square(2.0, 1.0, 2.0));
+        let union = boolean(&a, &b, BooleanOp::Union);
+        assert_eq!(union.len(), 1);
+        assert_eq!(union[0].outer.num_vertices(), 8);
+        assert_approx_eq!(union[0].area(), 8.0, F64_ASSERT_PRECISION);
+    }
+
+    #[test]
+    fn test_boolean_touching_vertex() {
+        let a = PolygonWithHoles::from(square(0.0, 0.0, 2.0));
+        let b = PolygonWithHoles::from(square(2.0, 2.0, 2.0));
+        let union = boolean(&a, &b, BooleanOp::Union);
+        assert_eq!(union.len(), 2);
+        assert!(union.iter().all(|p| p.outer.num_vertices() == 4));
+        assert!(boolean(&a, &b, BooleanOp::Intersection).is_empty());
+    }
+
+    #[test]
+    fn test_boolean_holes() {
+        // Cutting a square out of the middle of another leaves a hole
+        let a = PolygonWithHoles::from(square(0.0, 0.0, 6.0));
+        let b = PolygonWithHoles::from(square(2.0, 2.0, 2.0));
+        let difference = boolean(&a, &b, BooleanOp::Difference);
+        assert_eq!(difference.len(), 1);
+        assert_eq!(difference[0].holes.len(), 1);
+        let hole = &difference[0].holes[0];
+        assert_eq!(hole.num_vertices(), 4);
+        assert_approx_eq!(hole.area(), b.area(), F64_ASSERT_PRECISION);
+        assert_approx_eq!(difference[0].area(), 32.0, F64_ASSERT_PRECISION);
+
+        // Which a square covering part of the hole fills in again
+        let c = PolygonWithHoles::from(square(1.0, 1.0, 2.0));
+        let union = boolean(&difference[0], &c, BooleanOp::Union);
+        assert_eq!(union.len(), 1);
+        assert_eq!(union[0].holes.len(), 1);
+        assert_eq!(union[0].holes[0].num_vertices(), 6);
+        assert_approx_eq!(union[0].area(), 33.0, F64_ASSERT_PRECISION);
+        let intersection = boolean(&difference[0], &c, BooleanOp::Intersection);
+        assert_eq!(intersection.len(), 1);
+        assert_eq!(intersection[0].outer.num_vertices(), 6);
+        assert_approx_eq!(intersection[0].area(), 3.0, F64_ASSERT_PRECISION);
+
+        // A square cut from a corner is a notch rather than a hole, and a
+        // triangle touching the boundary at one corner is a hole
+        let d = PolygonWithHoles::from(square(4.0, 4.0, 2.0));
+        let difference = boolean(&a, &d, BooleanOp::Difference);
+        assert_eq!(difference.len(), 1);
+        assert_eq!(difference[0].outer.num_vertices(), 6);
+        assert!(difference[0].holes.is_empty());
+        let e = Polygon::from_coords(vec![(3.0, 3.0), (6.0, 4.0), (5.0, 5.0)]);
+        let difference = boolean(
+            &a,
+            &PolygonWithHoles::from(e.clone()),
+            BooleanOp::Difference,
+        );
+        assert_eq!(difference.len(), 1);
+        assert_eq!(difference[0].outer.num_vertices(), 4);
+        assert_eq!(difference[0].holes.len(), 1);
+        assert_eq!(difference[0].holes[0].num_vertices(), 3);
+        assert_approx_eq!(difference[0].area(), 36.0 - e.area(), F64_ASSERT_PRECISION);
+    }
+
+    /// The polygon scaled to a fraction of the size of `target`, turned,
+    /// and moved onto its center and then along by the fractions of its
+    /// size in `shift`.
+    fn fit(
+        polygon: &Polygon,
+        target: &Polygon,
+        fraction: f64,
+        angle: f64,
+        shift: (f64, f64),
+    ) -> Polygon {
+        let (fx, fy) = polygon.bounding_box().center();
+        let (tx, ty) = target.bounding_box().center();
+        let scale = fraction * size(target) / size(polygon);
+        let (dx, dy) = (shift.0 * size(target), shift.1 * size(target));
+        let (sin, cos) = angle.sin_cos();
+        let coords = polygon
+            .vertices()
+            .iter()
+            .map(|v| {
+                let (x, y) = ((v.x - fx) * scale, (v.y - fy) * scale);
+                (tx + x * cos - y * sin + dx, ty + x * sin + y * cos + dy)
+            })
+            .collect_vec();
+        Polygon::from_coords(coords)
+    }
+
+    /// A hole in the largest triangle of the polygon, shrunk about its
+    /// centroid.
+    fn with_hole(polygon: Polygon) -> PolygonWithHoles {
+        let triangulation = EarClipping.triangulation(&polygon).unwrap();
+        let t = triangulation
+            .iter()
+            .map(|ids| polygon.get_triangle(&ids.0, &ids.1, &ids.2).unwrap())
+            .max_by_key(|t| OF(t.area()))
+            .unwrap();
+        let c = (
+            (t.v1.x + t.v2.x + t.v3.x) / 3.0,
+            (t.v1.y + t.v2.y + t.v3.y) / 3.0,
+        );
+        let hole = [t.v1, t.v2, t.v3]
+            .map(|v| (c.0 + 0.5 * (v.x - c.0), c.1 + 0.5 * (v.y - c.1)))
+            .to_vec();
+        PolygonWithHoles::new(polygon, vec![Polygon::from_coords(hole)])
+    }
+
+    /// Area of the result found by winding numbers over all crossings.
+    fn overlay_area(a: &PolygonWithHoles, b: &PolygonWithHoles, op: BooleanOp) -> f64 {
+        let mut overlay = Overlay::new(2);
+        for (operand, polygon) in [a, b].into_iter().enumerate() {
+            let coords = |p: &Polygon| p.vertices().iter().map(|v| v.coords()).collect_vec();
+            overlay.add_loop(operand, coords(&polygon.outer));
+            for hole in polygon.holes.iter() {
+                overlay.add_loop(operand, coords(hole).into_iter().rev().collect());
+            }
+        }
+        overlay
+            .boundary(|w| op.keeps(w[0] > 0, w[1] > 0))
+            .iter()
+            .map(|l| loop_area(l))
+            .sum()
+    }
+
+    #[apply(all_polygons)]
+    fn test_boolean_pairs(
+        case: PolygonTestCase,
+        #[values(eberly_14(), held_7a(), mapbox_dude(), matisse_blue(), mei_4())]
+        other: PolygonTestCase,
+    ) {
+        // The polygon against another one from the archive moved onto it,
+        // on their own and with a hole in each
+        let a = case.polygon;
+        let b = fit(&other.polygon, &a, 0.8, 0.3, (0.1, 0.05));
+        let tolerance = 1e-9 * size(&a) * size(&a);
+        let pairs = [
+            (
+                PolygonWithHoles::from(a.clone()),
+                PolygonWithHoles::from(b.clone()),
+            ),
+            (with_hole(a), with_hole(b)),
+        ];
+        for (a, b) in pairs.iter() {
+            let areas = OPS.map(|op| total_area(&boolean(a, b, op)));
+            let [union, intersection, difference, xor] = areas;
+            assert_approx_eq!(union + intersection, a.area() + b.area(), tolerance);
+            assert_approx_eq!(difference + intersection, a.area(), tolerance);
+            assert_approx_eq!(xor, union - intersection, tolerance);
+            for (op, area) in OPS.into_iter().zip(areas) {
+                assert_approx_eq!(area, overlay_area(a, b, op), tolerance);
+            }
+
+            let mut points = Vec::new();
+            for polygon in [a, b] {
+                for boundary in [&polygon.outer].into_iter().chain(polygon.holes.iter()) {
+                    let triangulation = EarClipping.triangulation(boundary).unwrap();
+                    for ids in triangulation.iter() {
+                        points.push(triangle_centroid(boundary, ids));
+                    }
+                }
+            }
+            for op in OPS {
+                let pieces = boolean(a, b, op);
+                for p in points.iter() {
+                    let expected = op.keeps(a.contains(p), b.contains(p));
+                    assert_eq!(pieces.iter().any(|piece| piece.contains(p)), expected);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use itertools::Itertools;
+use log::{debug, info};
+use ordered_float::OrderedFloat as OF;
+use std::collections::HashMap;
+
+use crate::{
+    bounding_box::BoundingBox,
+    error::ClipError,
+    geometry::Geometry,
+    overlay::{loop_area, Overlay},
+    polygon::Polygon,
+    predicates::orientation,
+    vertex::{Vertex, VertexId},
+};
+
+/// Where a vertex of a [`ClippedPolygon`] came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClipVertex {
+    /// A vertex of the subject polygon, which keeps its ID.
+    Original,
+    /// A new point where the subject edge between the two vertices crosses
+    /// the boundary of the window.
+    Intersection(VertexId, VertexId),
+    /// The window vertex with the ID, where it is inside the subject.
+    WindowCorner(VertexId),
+}
+
+/// A piece of a polygon inside a clip window.
+pub struct ClippedPolygon {
+    polygon: Polygon,
+    kinds: HashMap<VertexId, ClipVertex>,
+}
+
+impl ClippedPolygon {
+    pub fn polygon(&self) -> &Polygon {
+        &self.polygon
+    }
+
+    pub fn into_polygon(self) -> Polygon {
+        self.polygon
+    }
+
+    /// Where the vertex came from, with new vertices numbered after the
+    /// largest vertex ID of the subject polygon.
+    pub fn kind(&self, id: &VertexId) -> Option<ClipVertex> {
+        self.kinds.get(id).copied()
+    }
+}
+
+/// What the clipped boundary runs along after a point, either a piece of
+/// the subject edge between two vertices or the window edge starting at a
+/// window vertex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Along {
+    Subject(VertexId, VertexId),
+    Window(usize),
+}
+
+#[derive(Clone, Debug)]
+struct ClipPoint {
+    v: Vertex,
+    kind: ClipVertex,
+    along: Along,
+}
+
+/// Clipping to a convex window by Sutherland-Hodgman in $O(nm)$ for a
+/// subject with $n$ vertices and a window with $m$ vertices, cutting away
+/// the part outside each window edge in turn.
+///
+/// Where a concave subject leaves the window and comes back, what is left
+/// of it is joined along the window edge by a bridge going out and back
+/// with no width in between. When the boundary runs along some window
+/// edge more than once it may have bridges, and it is split apart where
+/// it overlaps itself so that each piece is a valid polygon.
+#[derive(Default)]
+pub struct SutherlandHodgman;
+
+impl SutherlandHodgman {
+    pub fn clip(
+        &self,
+        subject: &Polygon,
+        window: &Polygon,
+    ) -> Result<Vec<ClippedPolygon>, ClipError> {
+        if !window.is_convex() {
+            return Err(ClipError::NonConvexWindow);
+        }
+        info!(
+            "Clipping polygon with {} vertices to window with {} vertices",
+            subject.num_vertices(),
+            window.num_vertices()
+        );
+        let window = window.vertices();
+        let mut ring = subject
+            .vertices()
+            .into_iter()
+            .circular_tuple_windows()
+            .map(|(v, next)| ClipPoint {
+                v: v.clone(),
+                kind: ClipVertex::Original,
+                along: Along::Subject(v.id, next.id),
+            })
+            .collect_vec();
+
+        for (k, (a, b)) in window.iter().circular_tuple_windows().enumerate() {
+            let mut clipped: Vec<ClipPoint> = Vec::new();
+            for (p, q) in ring.iter().circular_tuple_windows() {
+                let (op, oq) = (orientation(a, b, &p.v), orientation(a, b, &q.v));
+                if op >= 0.0 {
+                    clipped.push(p.clone());
+                }
+                if op == 0.0 && oq < 0.0 {
+                    // Already on the window edge, so it runs along the
+                    // edge from here
+                    clipped.last_mut().unwrap().along = Along::Window(k);
+                } else if op > 0.0 && oq < 0.0 {
+                    clipped.push(crossing(&window, p, q, (op, oq), k, Along::Window(k)));
+                } else if op < 0.0 && oq > 0.0 {
+                    clipped.push(crossing(&window, p, q, (op, oq), k, p.along));
+                }
+            }
+            ring = clipped;
+            if ring.len() < 3 {
+                debug!("Nothing left after clipping to window edge {k}");
+                return Ok(Vec::new());
+            }
+        }
+        // Points can coincide where the subject touches the window, and the
+        // boundary goes on from the later one
+        let merge = |q: &mut ClipPoint, p: &mut ClipPoint| {
+            if q.v.coords() != p.v.coords() {
+                return false;
+            }
+            if q.kind == ClipVertex::Original {
+                (p.v, p.kind) = (q.v.clone(), q.kind);
+            }
+            p.along = q.along;
+            true
+        };
+        ring.dedup_by(merge);
+        if ring.len() > 1 {
+            let mut last = ring.pop().unwrap();
+            if merge(&mut ring[0], &mut last) {
+                ring[0] = last;
+            } else {
+                ring.push(last);
+            }
+        }
+
+        let mut num_along = HashMap::new();
+        for point in ring.iter() {
+            if let Along::Window(k) = point.along {
+                *num_along.entry(k).or_insert(0) += 1;
+            }
+        }
+        let pieces = if num_along.values().any(|n| *n > 1) {
+            split_bridges(&ring)
+        } else {
+            vec![ring]
+        };
+
+        let mut next_id = subject
+            .vertex_ids()
+            .into_iter()
+            .max()
+            .map_or(0, |id| usize::from(id) + 1);
+        let mut clipped = Vec::new();
+        for piece in pieces {
+            let coords = piece.iter().map(|p| p.v.coords()).collect_vec();
+            if piece.len() < 3 || loop_area(&coords) <= 0.0 {
+                continue;
+            }
+            let mut kinds = HashMap::new();
+            let mut vertices = Vec::new();
+            for point in piece {
+                let mut v = point.v;
+                if point.kind != ClipVertex::Original {
+                    v.id = VertexId::from(next_id);
+                    next_id += 1;
+                }
+                kinds.insert(v.id, point.kind);
+                vertices.push(v);
+            }
+            clipped.push(ClippedPolygon {
+                polygon: Polygon::from_vertices(vertices),
+                kinds,
+            });
+        }
+        info!("Clipped polygon into {} pieces", clipped.len());
+        Ok(clipped)
+    }
+
+    /// Clips to the rectangle, where a box with no area leaves nothing.
+    pub fn clip_to_box(&self, subject: &Polygon, bbox: &BoundingBox) -> Vec<ClippedPolygon> {
+        if bbox.max_x <= bbox.min_x || bbox.max_y <= bbox.min_y {
+            return Vec::new();
+        }
+        let window = Polygon::from_coords(vec![
+            (bbox.min_x, bbox.min_y),
+            (bbox.max_x, bbox.min_y),
+            (bbox.max_x, bbox.max_y),
+            (bbox.min_x, bbox.max_y),
+        ]);
+        self.clip(subject, &window).unwrap()
+    }
+}
+
+/// The point where the boundary from `p` to `q` crosses window edge `k`,
+/// given their orientations relative to the edge, after which it runs
+/// along `along`. Where the boundary was already running along a window
+/// edge this is a corner of the window.
+fn crossing(
+    window: &[&Vertex],
+    p: &ClipPoint,
+    q: &ClipPoint,
+    (op, oq): (f64, f64),
+    k: usize,
+    along: Along,
+) -> ClipPoint {
+    let (v, kind) = match p.along {
+        Along::Subject(a, b) => {
+            let t = op / (op - oq);
+            let x = p.v.x + t * (q.v.x - p.v.x);
+            let y = p.v.y + t * (q.v.y - p.v.y);
+            let v = Vertex::new(VertexId::default(), x, y);
+            (v, ClipVertex::Intersection(a, b))
+        }
+        Along::Window(j) => {
+            // Window edges only meet at the corner between them
+            let corner = if (j + 1) % window.len() == k {
+                window[k]
+            } else {
+                window[j]
+            };
+            (corner.clone(), ClipVertex::WindowCorner(corner.id))
+        }
+    };
+    ClipPoint { v, kind, along }
+}
+
+/// Splits the boundary where it overlaps itself along the window edges,
+/// where the bridges going out and back cancel out. The pieces are made
+/// up of the same points, so they keep where each came from.
+fn split_bridges(ring: &[ClipPoint]) -> Vec<Vec<ClipPoint>> {
+    let mut overlay = Overlay::new(1);
+    overlay.add_loop(0, ring.iter().map(|p| p.v.coords()).collect());
+    let points: HashMap<_, _> = ring.iter().map(|p| ((OF(p.v.x), OF(p.v.y)), p)).collect();
+    let pieces = overlay
+        .boundary(|w| w[0] > 0)
+        .into_iter()
+        .map(|piece| {
+            piece
+                .into_iter()
+                .map(|(x, y)| {
+                    points.get(&(OF(x), OF(y))).map_or_else(
+                        || {
+                            // Points are only moved by merging ones that
+                            // are too close to tell apart
+                            ring.iter()
+                                .min_by_key(|p| OF((p.v.x - x).hypot(p.v.y - y)))
+                                .unwrap()
+                                .clone()
+                        },
+                        |p| (*p).clone(),
+                    )
+                })
+                .collect_vec()
+        })
+        .collect_vec();
+    debug!("Split bridges leaving {} pieces", pieces.len());
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean::{boolean, BooleanOp};
+    use crate::polygon::PolygonWithHoles;
+    use crate::test_util::*;
+    use crate::F64_ASSERT_PRECISION;
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+    use std::f64::consts::PI;
+
+    fn on_segment(v: &Vertex, a: &Vertex, b: &Vertex, tolerance: f64) -> bool {
+        let length = a.distance_to(b);
+        let off_line = ((b.x - a.x) * (v.y - a.y) - (b.y - a.y) * (v.x - a.x)) / length;
+        let along = ((b.x - a.x) * (v.x - a.x) + (b.y - a.y) * (v.y - a.y)) / length;
+        off_line.abs() <= tolerance && along >= -tolerance && along <= length + tolerance
+    }
+
+    fn check_clip(subject: &Polygon, window: &Polygon) {
+        let bbox = subject.bounding_box();
+        let tolerance = 1e-9 * (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+        let pieces = SutherlandHodgman.clip(subject, window).unwrap();
+        let window_vertices = window.vertices();
+        let on_window = |v: &Vertex| {
+            window_vertices
+                .iter()
+                .circular_tuple_windows()
+                .any(|(a, b)| on_segment(v, a, b, tolerance))
+        };
+
+        for piece in pieces.iter() {
+            for v in piece.polygon().vertices() {
+                match piece.kind(&v.id).unwrap() {
+                    ClipVertex::Original => {
+                        assert_eq!(subject.get_vertex(&v.id), Some(v));
+                        assert!(window.contains(v) || on_window(v));
+                    }
+                    ClipVertex::Intersection(a, b) => {
+                        let (a, b) = (subject.get_vertex(&a), subject.get_vertex(&b));
+                        assert!(on_segment(v, a.unwrap(), b.unwrap(), tolerance));
+                        assert!(on_window(v));
+                    }
+                    ClipVertex::WindowCorner(id) => {
+                        assert_eq!(window.get_vertex(&id).unwrap().coords(), v.coords());
+                    }
+                }
+            }
+        }
+
+        // The pieces cover the same area as the intersection
+        let expected = boolean(
+            &PolygonWithHoles::from(subject.clone()),
+            &PolygonWithHoles::from(window.clone()),
+            BooleanOp::Intersection,
+        );
+        let area: f64 = pieces.iter().map(|p| p.polygon().area()).sum();
+        let expected_area: f64 = expected.iter().map(|p| p.area()).sum();
+        assert_approx_eq!(area, expected_area, tolerance * tolerance.sqrt().max(1.0));
+        assert_eq!(pieces.len(), expected.len());
+    }
+
+    #[apply(all_polygons)]
+    fn test_clip(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        let bbox = polygon.bounding_box();
+        let (cx, cy) = bbox.center();
+        let (w, h) = (bbox.max_x - bbox.min_x, bbox.max_y - bbox.min_y);
+
+        // The middle of the bounding box, and a hexagon around the center
+        let middle = BoundingBox::new(cx - w / 4.0, cx + w / 4.0, cy - h / 4.0, cy + h / 4.0);
+        let pieces = SutherlandHodgman.clip_to_box(polygon, &middle);
+        let window = Polygon::from_coords(vec![
+            (middle.min_x, middle.min_y),
+            (middle.max_x, middle.min_y),
+            (middle.max_x, middle.max_y),
+            (middle.min_x, middle.max_y),
+        ]);
+        assert_eq!(
+            pieces.len(),
+            SutherlandHodgman.clip(polygon, &window).unwrap().len()
+        );
+        check_clip(polygon, &window);
+        let hexagon = (0..6)
+            .map(|i| {
+                let angle = PI / 3.0 * i as f64 + 0.1;
+                (cx + w / 3.0 * angle.cos(), cy + h / 3.0 * angle.sin())
+            })
+            .collect_vec();
+        check_clip(polygon, &Polygon::from_coords(hexagon));
+
+        // Clipping to a window around the whole polygon leaves it as it is
+        let around = BoundingBox::new(
+            bbox.min_x - 1.0,
+            bbox.max_x + 1.0,
+            bbox.min_y - 1.0,
+            bbox.max_y + 1.0,
+        );
+        let pieces = SutherlandHodgman.clip_to_box(polygon, &around);
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pieces[0].polygon(), polygon);
+    }
+
+    #[test]
+    fn test_clip_splits_concave_subject() {
+        // Cutting across both arms of a U leaves two pieces, rather than
+        // one joined by a bridge along the bottom of the window
+        let coords = vec![
+            (0.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 3.0),
+            (2.0, 3.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 3.0),
+            (0.0, 3.0),
+        ];
+        let u = Polygon::from_coords(coords);
+        let window = BoundingBox::new(-1.0, 4.0, 2.0, 4.0);
+        let pieces = SutherlandHodgman.clip_to_box(&u, &window);
+        assert_eq!(pieces.len(), 2);
+        for piece in pieces.iter() {
+            let polygon = piece.polygon();
+            assert_eq!(polygon.num_vertices(), 4);
+            assert_approx_eq!(polygon.area(), 1.0, F64_ASSERT_PRECISION);
+            let kinds = polygon
+                .vertex_ids()
+                .iter()
+                .map(|id| piece.kind(id).unwrap())
+                .collect_vec();
+            let num_original = kinds.iter().filter(|k| **k == ClipVertex::Original).count();
+            assert_eq!(num_original, 2);
+        }
+    }
+
+    #[test]
+    fn test_clip_window_corners() {
+        // A window inside the subject is all that is left of it
+        let square = Polygon::from_coords(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]);
+        let window = Polygon::from_coords(vec![(1.0, 1.0), (3.0, 2.0), (2.0, 3.0)]);
+        let pieces = SutherlandHodgman.clip(&square, &window).unwrap();
+        assert_eq!(pieces.len(), 1);
+        let polygon = pieces[0].polygon();
+        assert_eq!(polygon.num_vertices(), 3);
+        for v in polygon.vertices() {
+            let ClipVertex::WindowCorner(id) = pieces[0].kind(&v.id).unwrap() else {
+                panic!("{v:?} is not a window corner");
+            };
+            assert_eq!(window.get_vertex(&id).unwrap().coords(), v.coords());
+        }
+
+        // A window off to the side leaves nothing
+        let away = BoundingBox::new(5.0, 6.0, 0.0, 1.0);
+        assert!(SutherlandHodgman.clip_to_box(&square, &away).is_empty());
+
+        let concave = Polygon::from_coords(vec![(0.0, 0.0), (2.0, 1.0), (4.0, 0.0), (2.0, 3.0)]);
+        assert!(matches!(
+            SutherlandHodgman.clip(&square, &concave),
+            Err(ClipError::NonConvexWindow)
+        ));
+    }
+}
@@ -0,0 +1,380 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use std::collections::HashMap;
+
+use crate::{
+    error::TriangulationError,
+    geometry::Geometry,
+    line_segment::LineSegment,
+    polygon::Polygon,
+    triangulation::{check_triangulation_input, EarClipping, TriangulationComputer},
+    vertex::{Vertex, VertexId},
+};
+
+pub trait ConvexDecompositionComputer {
+    fn convex_decomposition(&self, polygon: &Polygon) -> Result<Vec<Polygon>, TriangulationError>;
+}
+
+/// Hertel-Mehlhorn decomposition, which starts from a triangulation and
+/// removes every diagonal that can be removed without creating a reflex
+/// vertex. It is linear in the size of the triangulation and produces at
+/// most 4 times the minimum number of convex pieces.
+#[derive(Default)]
+pub struct HertelMehlhorn;
+
+impl HertelMehlhorn {
+    fn is_convex_at(
+        &self,
+        polygon: &Polygon,
+        prev: &VertexId,
+        id: &VertexId,
+        next: &VertexId,
+    ) -> bool {
+        polygon.get_triangle(prev, id, next).unwrap().area() >= 0.0
+    }
+}
+
+impl ConvexDecompositionComputer for HertelMehlhorn {
+    fn convex_decomposition(&self, polygon: &Polygon) -> Result<Vec<Polygon>, TriangulationError> {
+        info!("Computing convex decomposition with the HertelMehlhorn algorithm");
+
+        // Pieces are CCW vertex chains, and each directed edge maps to the
+        // piece it bounds so the pieces either side of a diagonal can be
+        // found. Merged pieces are left empty.
+        let mut pieces = Vec::new();
+        let mut owner = HashMap::new();
+        for t in EarClipping.triangulation(polygon)?.iter() {
+            for edge in [(t.0, t.1), (t.1, t.2), (t.2, t.0)] {
+                owner.insert(edge, pieces.len());
+            }
+            pieces.push(vec![t.0, t.1, t.2]);
+        }
+
+        let diagonals = owner
+            .keys()
+            .filter(|(a, b)| a < b && owner.contains_key(&(*b, *a)))
+            .copied()
+            .sorted()
+            .collect_vec();
+        debug!("Triangulation has {} diagonals", diagonals.len());
+
+        for (a, b) in diagonals {
+            let p = owner[&(a, b)];
+            let q = owner[&(b, a)];
+
+            // Rotate so one piece runs b -> a and the other a -> b, then
+            // joining them drops the diagonal leaving a and b as the only
+            // vertices where the merged piece could become reflex
+            let mut p_ids = pieces[p].clone();
+            let mut q_ids = pieces[q].clone();
+            let p_start = p_ids.iter().position(|id| *id == b).unwrap();
+            let q_start = q_ids.iter().position(|id| *id == a).unwrap();
+            p_ids.rotate_left(p_start);
+            q_ids.rotate_left(q_start);
+            let convex_a = self.is_convex_at(polygon, &p_ids[p_ids.len() - 2], &a, &q_ids[1]);
+            let convex_b = self.is_convex_at(polygon, &q_ids[q_ids.len() - 2], &b, &p_ids[1]);
+            if !convex_a || !convex_b {
+                trace!("Diagonal ({a}, {b}) is essential");
+                continue;
+            }
+
+            trace!("Removing diagonal ({a}, {b})");
+            owner.remove(&(a, b));
+            owner.remove(&(b, a));
+            for (id1, id2) in q_ids.iter().circular_tuple_windows() {
+                if (*id1, *id2) != (a, b) {
+                    owner.insert((*id1, *id2), p);
+                }
+            }
+            p_ids.extend_from_slice(&q_ids[1..q_ids.len() - 1]);
+            pieces[p] = p_ids;
+            pieces[q] = Vec::new();
+        }
+
+        let pieces = pieces
+            .into_iter()
+            .filter(|ids| !ids.is_empty())
+            .map(|ids| polygon.get_polygon(ids, false, false))
+            .collect_vec();
+        info!("Computed convex decomposition with {} pieces", pieces.len());
+        Ok(pieces)
+    }
+}
+
+/// Keil's dynamic programming algorithm for the minimum number of convex
+/// pieces using only diagonals of the polygon, $O(n^3)$ in the worst case.
+///
+/// For each diagonal $(i, j)$ the sub-polygon on the chain $i..j$ is
+/// solved using the other vertex $k$ of the piece at edge $(i, k)$, where
+/// the rest of the piece must come from the solution for $(k, j)$. Only
+/// minimum weight solutions are kept since a heavier one can never beat
+/// closing off the piece as the triangle $(i, k, j)$, and of those only
+/// the narrowest pieces at $i$ and $j$ since they are the easiest to merge.
+#[derive(Default)]
+pub struct Keil;
+
+/// A candidate piece bounding the sub-polygon of a diagonal $(i, j)$, with
+/// the vertices after $i$ and before $j$ on the piece, and how it was built.
+#[derive(Clone, Debug)]
+struct KeilPiece {
+    s: usize,
+    t: usize,
+    k: usize,
+    merged: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct KeilState {
+    weight: usize,
+    pieces: Vec<KeilPiece>,
+}
+
+type KeilStates = Vec<Vec<Option<KeilState>>>;
+
+impl Keil {
+    fn valid(&self, polygon: &Polygon, vertices: &[&Vertex], i: usize, j: usize) -> bool {
+        if j == i + 1 || (i == 0 && j == vertices.len() - 1) {
+            return true;
+        }
+        polygon.diagonal(vertices[i], vertices[j])
+    }
+
+    fn left_on(&self, vertices: &[&Vertex], a: usize, b: usize, c: usize) -> bool {
+        vertices[c].left_on(&LineSegment::from_vertices(vertices[a], vertices[b]))
+    }
+
+    fn solve(
+        &self,
+        vertices: &[&Vertex],
+        valid: &[Vec<bool>],
+        states: &KeilStates,
+        i: usize,
+        j: usize,
+    ) -> KeilState {
+        let mut weight = usize::MAX;
+        let mut candidates = Vec::new();
+        for k in (i + 1)..j {
+            if !valid[i][k] || !valid[k][j] {
+                continue;
+            }
+            let w_ik = states[i][k].as_ref().map_or(0, |s| s.weight);
+            let mut w = w_ik + 1;
+            let mut ks = vec![KeilPiece {
+                s: k,
+                t: k,
+                k,
+                merged: None,
+            }];
+
+            if let Some(state) = &states[k][j] {
+                w += state.weight;
+                // Merging with a piece from (k, j) saves a piece as long as
+                // the result stays convex at k and j
+                let merges = state
+                    .pieces
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, p)| {
+                        self.left_on(vertices, k, p.s, i) && self.left_on(vertices, p.t, j, i)
+                    })
+                    .map(|(idx, p)| KeilPiece {
+                        s: k,
+                        t: p.t,
+                        k,
+                        merged: Some(idx),
+                    })
+                    .collect_vec();
+                if !merges.is_empty() {
+                    w -= 1;
+                    ks = merges;
+                }
+            }
+
+            if w < weight {
+                weight = w;
+                candidates.clear();
+            }
+            if w == weight {
+                candidates.extend(ks);
+            }
+        }
+
+        let pieces = self.narrowest(vertices, i, j, candidates);
+        trace!(i, j, weight, pieces:?; "Solved sub-polygon");
+        KeilState { weight, pieces }
+    }
+
+    fn narrowest(
+        &self,
+        vertices: &[&Vertex],
+        i: usize,
+        j: usize,
+        pieces: Vec<KeilPiece>,
+    ) -> Vec<KeilPiece> {
+        // One piece is at least as narrow as another at i if its vertex s
+        // is swept towards j, and likewise at j for vertex t
+        let dominates = |p1: &KeilPiece, p2: &KeilPiece| {
+            self.left_on(vertices, i, p2.s, p1.s) && self.left_on(vertices, j, p1.t, p2.t)
+        };
+        let mut narrowest: Vec<KeilPiece> = Vec::new();
+        for piece in pieces {
+            if narrowest.iter().any(|p| dominates(p, &piece)) {
+                continue;
+            }
+            narrowest.retain(|p| !dominates(&piece, p));
+            narrowest.push(piece);
+        }
+        narrowest
+    }
+
+    fn collect_pieces(
+        &self,
+        states: &KeilStates,
+        i: usize,
+        j: usize,
+        piece: &KeilPiece,
+        chain: &mut Vec<usize>,
+        pieces: &mut Vec<Vec<usize>>,
+    ) {
+        // The piece at (i, j) continues through k and then along its merged
+        // piece from (k, j) if any, every other diagonal closes off its own
+        // sub-polygon which is collected recursively
+        let k = piece.k;
+        chain.push(i);
+        self.collect_sub_polygon(states, i, k, pieces);
+        match piece.merged {
+            Some(idx) => {
+                let merged = &states[k][j].as_ref().unwrap().pieces[idx];
+                self.collect_pieces(states, k, j, merged, chain, pieces);
+            }
+            None => {
+                chain.push(k);
+                self.collect_sub_polygon(states, k, j, pieces);
+                chain.push(j);
+            }
+        }
+    }
+
+    fn collect_sub_polygon(
+        &self,
+        states: &KeilStates,
+        i: usize,
+        j: usize,
+        pieces: &mut Vec<Vec<usize>>,
+    ) {
+        if let Some(state) = &states[i][j] {
+            let mut chain = Vec::new();
+            self.collect_pieces(states, i, j, &state.pieces[0], &mut chain, pieces);
+            pieces.push(chain);
+        }
+    }
+}
+
+impl ConvexDecompositionComputer for Keil {
+    fn convex_decomposition(&self, polygon: &Polygon) -> Result<Vec<Polygon>, TriangulationError> {
+        info!("Computing convex decomposition with the Keil algorithm");
+        check_triangulation_input(polygon)?;
+
+        let vertices = polygon.vertices();
+        let n = vertices.len();
+        let valid = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| i < j && self.valid(polygon, &vertices, i, j))
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        // Solve sub-polygons in order of increasing size, where the whole
+        // polygon is the sub-polygon closed off by the edge (n - 1, 0)
+        let mut states: KeilStates = (0..n).map(|_| (0..n).map(|_| None).collect()).collect();
+        for gap in 2..n {
+            for i in 0..(n - gap) {
+                let j = i + gap;
+                if valid[i][j] {
+                    states[i][j] = Some(self.solve(&vertices, &valid, &states, i, j));
+                }
+            }
+        }
+
+        let mut pieces = Vec::new();
+        self.collect_sub_polygon(&states, 0, n - 1, &mut pieces);
+        let pieces = pieces
+            .into_iter()
+            .map(|chain| {
+                polygon.get_polygon(chain.into_iter().map(|i| vertices[i].id), false, false)
+            })
+            .collect_vec();
+        info!("Computed convex decomposition with {} pieces", pieces.len());
+        Ok(pieces)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    #[apply(all_polygons)]
+    fn test_convex_decomposition(
+        #[case] case: PolygonTestCase,
+        #[values(HertelMehlhorn, Keil)] computer: impl ConvexDecompositionComputer,
+    ) {
+        let pieces = computer.convex_decomposition(&case.polygon).unwrap();
+        let num_reflex = case.polygon.reflex_vertices().len();
+        // Each diagonal can resolve at most two reflex vertices, and each
+        // reflex vertex has at most two essential diagonals
+        assert!(pieces.len() <= 2 * num_reflex + 1);
+        assert!(pieces.len() > num_reflex / 2);
+
+        let mut area = 0.0;
+        for piece in pieces.iter() {
+            assert!(piece.is_convex());
+            area += piece.area();
+        }
+        assert_eq!(area, case.metadata.area);
+    }
+
+    #[apply(all_polygons)]
+    fn test_keil_is_minimal(case: PolygonTestCase) {
+        let optimal = Keil.convex_decomposition(&case.polygon).unwrap();
+        let hertel_mehlhorn = HertelMehlhorn.convex_decomposition(&case.polygon).unwrap();
+        assert!(optimal.len() <= hertel_mehlhorn.len());
+        assert!(hertel_mehlhorn.len() <= 4 * optimal.len());
+    }
+
+    #[test]
+    fn test_convex_polygon_is_one_piece() {
+        let case = square_4x4();
+        assert_eq!(Keil.convex_decomposition(&case.polygon).unwrap().len(), 1);
+        assert_eq!(
+            HertelMehlhorn
+                .convex_decomposition(&case.polygon)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_keil_comb() {
+        // The middle tooth can only be its own piece, and what is left has
+        // two reflex vertices that no single diagonal resolves
+        let coords = vec![
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 6.0),
+            (8.0, 6.0),
+            (7.0, 2.0),
+            (6.0, 6.0),
+            (4.0, 6.0),
+            (3.0, 2.0),
+            (2.0, 6.0),
+            (0.0, 6.0),
+        ];
+        let polygon = Polygon::from_coords(coords);
+        assert_eq!(Keil.convex_decomposition(&polygon).unwrap().len(), 4);
+    }
+}
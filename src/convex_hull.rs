@@ -5,9 +5,12 @@ use std::fmt;
 
 use crate::{
     data_structure::{HullSet, Stack},
+    error::ConvexHullError,
     geometry::Geometry,
+    line_segment::LineSegment,
     polygon::Polygon,
-    vertex::VertexId,
+    triangle::Triangle,
+    vertex::{Vertex, VertexId},
 };
 
 #[derive(Default)]
@@ -58,6 +61,34 @@ pub trait ConvexHullComputer {
     fn convex_hull(&self, polygon: &Polygon, tracer: &mut Option<ConvexHullTracer>) -> Polygon;
 }
 
+/// Andrew's monotone chain scan over vertices already sorted by increasing
+/// x then y, returning the hull vertex IDs in CCW order. Vertices lying on
+/// a hull edge are only included if `keep_collinear` is set.
+pub(crate) fn monotone_chain(sorted: &[&Vertex], keep_collinear: bool) -> Vec<VertexId> {
+    if sorted.len() < 3 {
+        return sorted.iter().map(|v| v.id).collect_vec();
+    }
+
+    // Build the lower hull left to right and the upper hull right to left,
+    // the last vertex of each pass being the first vertex of the other
+    let mut chain: Vec<&Vertex> = Vec::new();
+    for pass in [sorted.to_vec(), sorted.iter().rev().copied().collect_vec()] {
+        let start = chain.len();
+        for v in pass {
+            while chain.len() >= start + 2 {
+                let t = Triangle::from_vertices(chain[chain.len() - 2], chain[chain.len() - 1], v);
+                if t.area() > 0.0 || (keep_collinear && t.area() == 0.0) {
+                    break;
+                }
+                chain.pop();
+            }
+            chain.push(v);
+        }
+        chain.pop();
+    }
+    chain.into_iter().map(|v| v.id).collect_vec()
+}
+
 #[derive(Default)]
 pub struct GiftWrapping;
 
@@ -213,32 +244,195 @@ impl ConvexHullComputer for GrahamScan {
     }
 }
 
+/// Common tangent lines between two convex polygons `a` and `b`, each
+/// given as a pair of vertex IDs `(id_a, id_b)` of the tangent points.
+#[derive(Debug, Default, PartialEq)]
+pub struct Bitangents {
+    /// Tangents that have both polygons on the same side. These are the
+    /// bridges along the hull of the union, so there are two when the
+    /// polygons are disjoint, none when one contains the other, and
+    /// possibly more when their boundaries cross.
+    pub outer: Vec<(VertexId, VertexId)>,
+    /// Tangents that separate the two polygons, only present when the
+    /// polygons do not overlap.
+    pub inner: Vec<(VertexId, VertexId)>,
+}
+
 #[derive(Default)]
 pub struct DivideConquer;
 
 impl DivideConquer {
-    fn lower_tangent_vertices<'a>(
-        &'a self,
+    /// Computes the convex hull of the union of two convex polygons. The
+    /// polygons may be disjoint or overlapping but must not share vertex
+    /// IDs, as the merged hull refers back to the vertices of both, and
+    /// an error is returned if they do.
+    ///
+    /// If the polygons are separated in x this is the same linear time
+    /// tangent merge used by the algorithm itself, otherwise the union is
+    /// re-hulled with a monotone chain scan in $O((n + m) \log (n + m))$.
+    pub fn merge_hulls(&self, a: &Polygon, b: &Polygon) -> Result<Polygon, ConvexHullError> {
+        self.validate_disjoint_ids(a, b)?;
+
+        let merged_ids = if a.max_x() < b.min_x() {
+            self.merge_from_tangents(a, b)
+        } else if b.max_x() < a.min_x() {
+            self.merge_from_tangents(b, a)
+        } else {
+            debug!("Hulls overlap in x, merging with monotone chain");
+            let vertices = a
+                .vertices()
+                .into_iter()
+                .chain(b.vertices())
+                .sorted_by_key(|v| (OF(v.x), OF(v.y)))
+                .dedup_by(|v1, v2| v1.coords() == v2.coords())
+                .collect_vec();
+            monotone_chain(&vertices, false)
+        };
+
+        let vertices = merged_ids
+            .into_iter()
+            .map(|id| a.get_vertex(&id).or(b.get_vertex(&id)).unwrap())
+            .cloned()
+            .collect_vec();
+        let mut hull = Polygon::from_vertices(vertices);
+        hull.clean_collinear();
+        Ok(hull)
+    }
+
+    /// Computes all common tangents between two convex polygons, see
+    /// [`Bitangents`]. The polygons must not share vertex IDs.
+    ///
+    /// The outer tangents come from the merged hull, and the inner ones
+    /// from tangent walks along the chains between them, so past the merge
+    /// this takes linear time.
+    pub fn bitangents(&self, a: &Polygon, b: &Polygon) -> Result<Bitangents, ConvexHullError> {
+        let hull = self.merge_hulls(a, b)?;
+
+        // Outer tangents are exactly the hull edges bridging a and b
+        let mut outer = Vec::new();
+        let (mut to_b, mut to_a) = (None, None);
+        let in_a = |id: &VertexId| a.get_vertex(id).is_some();
+        for id in hull.vertex_ids() {
+            let next = hull.next_vertex_id(&id).unwrap();
+            if in_a(&id) && !in_a(&next) {
+                outer.push((id, next));
+                to_b = Some((id, next));
+            } else if !in_a(&id) && in_a(&next) {
+                outer.push((next, id));
+                to_a = Some((next, id));
+            }
+        }
+
+        // Inner tangents can only exist when the hull bridges the polygons
+        // once each way, and then touch the chains between the bridges that
+        // face each other, running from the end of one bridge on a to the
+        // end of the other on b
+        let inner = match (outer.len(), to_b, to_a) {
+            (2, Some((a_1, b_1)), Some((a_2, b_2))) => [
+                self.inner_tangent_vertices(a, b, (a_1, a_2), (b_2, b_1), true),
+                self.inner_tangent_vertices(a, b, (a_2, a_1), (b_1, b_2), false),
+            ]
+            .into_iter()
+            .flatten()
+            .collect_vec(),
+            _ => Vec::new(),
+        };
+
+        trace!(outer:?, inner:?; "Computed bitangents");
+        Ok(Bitangents { outer, inner })
+    }
+
+    /// Walks the facing chains of the polygons from their first ends to
+    /// their last, going CCW with `a` on the left of the tangent and CW
+    /// with it on the right, until the line through both vertices is
+    /// tangent to each. If either walk runs off the end of its chain the
+    /// polygons overlap and there is no such tangent.
+    fn inner_tangent_vertices(
+        &self,
+        a: &Polygon,
+        b: &Polygon,
+        (mut v_a, a_end): (VertexId, VertexId),
+        (mut v_b, b_end): (VertexId, VertexId),
+        a_left: bool,
+    ) -> Option<(VertexId, VertexId)> {
+        let step = |polygon: &Polygon, v: &VertexId| match a_left {
+            true => polygon.next_vertex_id(v).unwrap(),
+            false => polygon.prev_vertex_id(v).unwrap(),
+        };
+        // Both bridges can end at the same vertex, and then the chain goes
+        // all the way around, so the end only counts once walked to
+        let (mut steps_a, mut steps_b) = (0, 0);
+        let mut it = self.tangent_segment(a, &v_a, b, &v_b);
+        trace!(a_0:?=v_a, b_0:?=v_b, it_0:?=it; "Searching for inner tangent vertices");
+        while !self.tangent_side(&it, &v_a, a, a_left) || !self.tangent_side(&it, &v_b, b, !a_left)
+        {
+            while !self.tangent_side(&it, &v_a, a, a_left) {
+                if steps_a > 0 && v_a == a_end {
+                    trace!("Walk on A passed its chain, no inner tangent");
+                    return None;
+                }
+                v_a = step(a, &v_a);
+                steps_a += 1;
+                it = self.tangent_segment(a, &v_a, b, &v_b);
+                trace!(a:?=v_a, it:?; "Step");
+            }
+            while !self.tangent_side(&it, &v_b, b, !a_left) {
+                if steps_b > 0 && v_b == b_end {
+                    trace!("Walk on B passed its chain, no inner tangent");
+                    return None;
+                }
+                v_b = step(b, &v_b);
+                steps_b += 1;
+                it = self.tangent_segment(a, &v_a, b, &v_b);
+                trace!(b:?=v_b, it:?; "Step");
+            }
+        }
+        trace!("Inner tangent vertices: {v_a}, {v_b}");
+        Some((v_a, v_b))
+    }
+
+    fn tangent_side(&self, ls: &LineSegment, id: &VertexId, polygon: &Polygon, left: bool) -> bool {
+        let prev = polygon.get_prev_vertex(id).unwrap();
+        let next = polygon.get_next_vertex(id).unwrap();
+        match left {
+            true => prev.left_on(ls) && next.left_on(ls),
+            false => prev.right_on(ls) && next.right_on(ls),
+        }
+    }
+
+    fn validate_disjoint_ids(&self, a: &Polygon, b: &Polygon) -> Result<(), ConvexHullError> {
+        let shared = a
+            .vertex_ids()
+            .into_iter()
+            .filter(|id| b.get_vertex(id).is_some())
+            .collect_vec();
+        if !shared.is_empty() {
+            return Err(ConvexHullError::SharedVertexIds(shared));
+        }
+        Ok(())
+    }
+
+    fn lower_tangent_vertices(
+        &self,
         left: impl Geometry,
         right: impl Geometry,
-        polygon: &'a Polygon,
     ) -> (VertexId, VertexId) {
         let mut a = left.lowest_rightmost_vertex().id;
         let mut b = right.lowest_leftmost_vertex().id;
-        let mut lt = polygon.get_line_segment(&a, &b).unwrap();
+        let mut lt = self.tangent_segment(&left, &a, &right, &b);
         trace!(a_0:?=a, b_0:?=b, lt_0:?=lt; "Searching for lower tangent vertices");
         while !lt.is_lower_tangent(&a, &left) || !lt.is_lower_tangent(&b, &right) {
             trace!("Moving left vertex down CW until lower tangent");
             while !lt.is_lower_tangent(&a, &left) {
                 a = left.prev_vertex_id(&a).unwrap(); // Move down cw
-                lt = polygon.get_line_segment(&a, &b).unwrap();
+                lt = self.tangent_segment(&left, &a, &right, &b);
                 trace!(a:?, lt:?; "Step");
             }
             trace!("Left lower tangent satisfied");
             trace!("Moving right vertex up CCW until lower tangent");
             while !lt.is_lower_tangent(&b, &right) {
                 b = right.next_vertex_id(&b).unwrap(); // Move down ccw
-                lt = polygon.get_line_segment(&a, &b).unwrap();
+                lt = self.tangent_segment(&left, &a, &right, &b);
                 trace!(b:?, lt:?; "Step");
             }
             trace!("Right lower tangent satisfied");
@@ -247,28 +441,27 @@ impl DivideConquer {
         (a, b)
     }
 
-    fn upper_tangent_vertices<'a>(
-        &'a self,
+    fn upper_tangent_vertices(
+        &self,
         left: impl Geometry,
         right: impl Geometry,
-        polygon: &'a Polygon,
     ) -> (VertexId, VertexId) {
         let mut a = left.highest_rightmost_vertex().id;
         let mut b = right.highest_leftmost_vertex().id;
-        let mut ut = polygon.get_line_segment(&a, &b).unwrap();
+        let mut ut = self.tangent_segment(&left, &a, &right, &b);
         trace!(a_0:?=a, b_0:?=b, ut_0:?=ut; "Searching for upper tangent vertices");
         while !ut.is_upper_tangent(&a, &left) || !ut.is_upper_tangent(&b, &right) {
             trace!("Moving left vertex up CCW until upper tangent");
             while !ut.is_upper_tangent(&a, &left) {
                 a = left.next_vertex_id(&a).unwrap(); // Move up ccw
-                ut = polygon.get_line_segment(&a, &b).unwrap();
+                ut = self.tangent_segment(&left, &a, &right, &b);
                 trace!(a:?, ut:?; "Step");
             }
             trace!("Left upper tangent satisfied");
             trace!("Moving right vertex down CW until upper tangent");
             while !ut.is_upper_tangent(&b, &right) {
                 b = right.prev_vertex_id(&b).unwrap(); // Move down cw
-                ut = polygon.get_line_segment(&a, &b).unwrap();
+                ut = self.tangent_segment(&left, &a, &right, &b);
                 trace!(b:?, ut:?; "Step");
             }
             trace!("Right upper tangent satisfied");
@@ -277,6 +470,21 @@ impl DivideConquer {
         (a, b)
     }
 
+    fn tangent_segment<'a>(
+        &self,
+        left: &'a impl Geometry,
+        a: &VertexId,
+        right: &'a impl Geometry,
+        b: &VertexId,
+    ) -> LineSegment<'a> {
+        // The tangent search only ever steps between vertices of the same
+        // polygon, starting from one of its extreme vertices, so the IDs
+        // are always there
+        let v_a = left.get_vertex(a).unwrap();
+        let v_b = right.get_vertex(b).unwrap();
+        LineSegment::from_vertices(v_a, v_b)
+    }
+
     fn extract_boundary(
         &self,
         a: impl Geometry,
@@ -316,14 +524,9 @@ impl DivideConquer {
         boundary
     }
 
-    fn merge_from_tangents<'a>(
-        &'a self,
-        left: impl Geometry,
-        right: impl Geometry,
-        polygon: &'a Polygon,
-    ) -> Vec<VertexId> {
-        let (lt_a, lt_b) = self.lower_tangent_vertices(&left, &right, polygon);
-        let (ut_a, ut_b) = self.upper_tangent_vertices(&left, &right, polygon);
+    fn merge_from_tangents(&self, left: impl Geometry, right: impl Geometry) -> Vec<VertexId> {
+        let (lt_a, lt_b) = self.lower_tangent_vertices(&left, &right);
+        let (ut_a, ut_b) = self.upper_tangent_vertices(&left, &right);
         self.extract_boundary(&left, &right, lt_a, lt_b, ut_a, ut_b)
     }
 
@@ -360,21 +563,21 @@ impl DivideConquer {
         if right_ids.len() >= 3 && left_ids.len() >= 3 {
             let right = polygon.get_polygon(right_ids, false, false);
             let left = polygon.get_polygon(left_ids, false, false);
-            merged_ids = self.merge_from_tangents(left, right, polygon);
+            merged_ids = self.merge_from_tangents(left, right);
         } else if left_ids.len() >= 3 {
             assert!(right_ids.len() == 2);
             let left = polygon.get_polygon(left_ids, false, false);
             let right = polygon
                 .get_line_segment(&right_ids[0], &right_ids[1])
                 .unwrap();
-            merged_ids = self.merge_from_tangents(left, right, polygon);
+            merged_ids = self.merge_from_tangents(left, right);
         } else if right_ids.len() >= 3 {
             assert!(left_ids.len() == 2);
             let right = polygon.get_polygon(right_ids, false, false);
             let left = polygon
                 .get_line_segment(&left_ids[0], &left_ids[1])
                 .unwrap();
-            merged_ids = self.merge_from_tangents(left, right, polygon);
+            merged_ids = self.merge_from_tangents(left, right);
         } else {
             assert!(left_ids.len() == 2);
             assert!(right_ids.len() == 2);
@@ -391,7 +594,7 @@ impl DivideConquer {
                     right.highest_rightmost_vertex().id,
                 ];
             } else {
-                merged_ids = self.merge_from_tangents(left, right, polygon);
+                merged_ids = self.merge_from_tangents(left, right);
             }
         }
         // Could be 2 if we tried to merge 2 collinear linear segments
@@ -589,4 +792,119 @@ mod tests {
         let hull_ids = hull.vertex_ids().into_iter().sorted().collect_vec();
         assert_eq!(hull_ids, case.metadata.extreme_points);
     }
+
+    fn polygon_with_offset_ids(coords: Vec<(f64, f64)>, offset: usize) -> Polygon {
+        let vertices = coords
+            .into_iter()
+            .enumerate()
+            .map(|(i, (x, y))| Vertex::new(VertexId::from(i + offset), x, y))
+            .collect_vec();
+        Polygon::from_vertices(vertices)
+    }
+
+    fn sorted_coords(polygon: &Polygon) -> Vec<(OF<f64>, OF<f64>)> {
+        polygon
+            .vertices()
+            .into_iter()
+            .map(|v| (OF(v.x), OF(v.y)))
+            .sorted()
+            .collect_vec()
+    }
+
+    #[apply(convex_hull_cases)]
+    fn test_merge_hull_chains(#[case] case: PolygonTestCase) {
+        // Any split of a convex polygon's boundary into two chains gives
+        // two convex polygons whose merged hull is the original hull, and
+        // hulls too small to split are merged with a copy shrunk inside them
+        let hull = GrahamScan.convex_hull(&case.polygon, &mut None);
+        let ids = hull.vertex_ids();
+        let (a, b) = if ids.len() >= 6 {
+            let (ids_a, ids_b) = ids.split_at(ids.len() / 2);
+            let a = hull.get_polygon(ids_a.to_vec(), false, false);
+            let b = hull.get_polygon(ids_b.to_vec(), false, false);
+            (a, b)
+        } else {
+            let vertices = hull.vertices();
+            let n = vertices.len() as f64;
+            let cx = vertices.iter().map(|v| v.x).sum::<f64>() / n;
+            let cy = vertices.iter().map(|v| v.y).sum::<f64>() / n;
+            let offset = usize::from(case.polygon.vertex_ids().into_iter().max().unwrap()) + 1;
+            let coords = vertices
+                .iter()
+                .map(|v| ((v.x + cx) / 2.0, (v.y + cy) / 2.0))
+                .collect_vec();
+            (hull.clone(), polygon_with_offset_ids(coords, offset))
+        };
+        let merged = DivideConquer.merge_hulls(&a, &b).unwrap();
+        let merged_ids = merged.vertex_ids().into_iter().sorted().collect_vec();
+        assert_eq!(merged_ids, case.metadata.extreme_points);
+    }
+
+    #[test]
+    fn test_merge_hulls_separated() {
+        let a = polygon_with_offset_ids(vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)], 0);
+        let b = polygon_with_offset_ids(vec![(4.0, 1.0), (6.0, 0.0), (6.0, 4.0)], 4);
+        let merged = DivideConquer.merge_hulls(&a, &b).unwrap();
+        let expected =
+            polygon_with_offset_ids(vec![(0.0, 0.0), (6.0, 0.0), (6.0, 4.0), (0.0, 2.0)], 0);
+        assert_eq!(sorted_coords(&merged), sorted_coords(&expected));
+        assert_eq!(merged.area(), expected.area());
+    }
+
+    #[test]
+    fn test_merge_hulls_overlapping() {
+        // Two crossing rectangles, the hull alternates between them
+        let a = polygon_with_offset_ids(vec![(0.0, 1.0), (4.0, 1.0), (4.0, 3.0), (0.0, 3.0)], 0);
+        let b = polygon_with_offset_ids(vec![(1.0, 0.0), (3.0, 0.0), (3.0, 4.0), (1.0, 4.0)], 4);
+        let merged = DivideConquer.merge_hulls(&a, &b).unwrap();
+        assert_eq!(merged.num_vertices(), 8);
+        assert_eq!(merged.area(), 14.0);
+
+        let bitangents = DivideConquer.bitangents(&a, &b).unwrap();
+        assert_eq!(bitangents.outer.len(), 4);
+        assert!(bitangents.inner.is_empty());
+    }
+
+    #[test]
+    fn test_merge_hulls_contained() {
+        let a = polygon_with_offset_ids(vec![(0.0, 0.0), (8.0, 0.0), (8.0, 8.0), (0.0, 8.0)], 0);
+        let b = polygon_with_offset_ids(vec![(2.0, 2.0), (4.0, 2.0), (3.0, 5.0)], 4);
+        let merged = DivideConquer.merge_hulls(&a, &b).unwrap();
+        assert_eq!(sorted_coords(&merged), sorted_coords(&a));
+
+        let bitangents = DivideConquer.bitangents(&a, &b).unwrap();
+        assert_eq!(bitangents, Bitangents::default());
+    }
+
+    #[test]
+    fn test_bitangents_disjoint() {
+        let a = polygon_with_offset_ids(vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)], 0);
+        let b = polygon_with_offset_ids(vec![(4.0, 0.0), (6.0, 0.0), (6.0, 2.0), (4.0, 2.0)], 4);
+        let bitangents = DivideConquer.bitangents(&a, &b).unwrap();
+
+        let outer = bitangents.outer.into_iter().sorted().collect_vec();
+        let expected_outer = vec![
+            (VertexId::from(0usize), VertexId::from(5usize)),
+            (VertexId::from(3usize), VertexId::from(6usize)),
+        ];
+        assert_eq!(outer, expected_outer);
+
+        let inner = bitangents.inner.into_iter().sorted().collect_vec();
+        let expected_inner = vec![
+            (VertexId::from(1usize), VertexId::from(7usize)),
+            (VertexId::from(2usize), VertexId::from(4usize)),
+        ];
+        assert_eq!(inner, expected_inner);
+    }
+
+    #[test]
+    fn test_merge_hulls_shared_ids() {
+        let a = polygon_with_offset_ids(vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0)], 0);
+        let b = polygon_with_offset_ids(vec![(4.0, 0.0), (6.0, 0.0), (6.0, 2.0)], 0);
+        assert!(matches!(
+            DivideConquer.merge_hulls(&a, &b),
+            Err(ConvexHullError::SharedVertexIds(ids)) if ids.len() == 3
+        ));
+        assert!(DivideConquer.bitangents(&a, &b).is_err());
+    }
 }
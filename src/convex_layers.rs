@@ -0,0 +1,514 @@
+use itertools::Itertools;
+use log::{debug, info};
+use ordered_float::OrderedFloat as OF;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{
+    predicates::orientation,
+    vertex::{Vertex, VertexId},
+};
+
+/// Nested convex hulls obtained by repeatedly peeling the hull off of a
+/// point set, also known as onion peeling. Layer 0 is the convex hull of
+/// all the points, layer 1 the hull of what remains, and so on. Points
+/// lying on a hull edge belong to that hull's layer.
+#[derive(Debug, Default)]
+pub struct ConvexLayers {
+    layers: Vec<Vec<VertexId>>,
+    depths: HashMap<VertexId, usize>,
+}
+
+impl ConvexLayers {
+    /// Computes the convex layers of the vertices with Chazelle's hull
+    /// tree, a balanced tree over the points sorted by x where each node
+    /// keeps the bridge joining the hulls of its two children, so the hull
+    /// of the node is the hull of its left child up to the bridge followed
+    /// by the hull of its right child after it. The lower and upper hulls
+    /// each have their own tree.
+    ///
+    /// Each layer is read off the root and its points deleted, after which
+    /// only the bridges above deleted points are found again. Points only
+    /// ever come out from under a bridge as others are deleted, so a new
+    /// bridge is found by walking along the hulls from where the old one
+    /// was, over points that were hidden before. Chazelle keeps the hulls
+    /// in linked lists to make each step of the walk $O(1)$ for a total of
+    /// $O(n \log n)$, here each step goes through the tree in $O(\log n)$
+    /// for a total of $O(n \log^2 n)$.
+    pub fn from_vertices<'a>(vertices: impl IntoIterator<Item = &'a Vertex>) -> Self {
+        info!("Computing convex layers");
+
+        let sorted = vertices
+            .into_iter()
+            .sorted_by_key(|v| (OF(v.x), OF(v.y)))
+            .collect_vec();
+        let mut lower = HullTree::new(&sorted, 1.0);
+        let mut upper = HullTree::new(&sorted, -1.0);
+        let mut layers = Vec::new();
+        let mut depths = HashMap::new();
+
+        while let Some(root) = lower.root() {
+            // The lower hull goes left to right and the upper hull right to
+            // left, with points on the edges of either added along them. If
+            // the remaining points are all collinear the two are the same
+            // points, so only keep the first visit of each
+            let mut visited = HashSet::new();
+            let mut layer = Vec::new();
+            let mut upper_chain = upper.boundary(upper.root().unwrap());
+            upper_chain.reverse();
+            for i in lower.boundary(root).into_iter().chain(upper_chain) {
+                if visited.insert(i) {
+                    layer.push(i);
+                }
+            }
+            debug!("Layer {}: {layer:?}", layers.len());
+
+            lower.delete(&layer);
+            upper.delete(&layer);
+            for i in layer.iter() {
+                depths.insert(sorted[*i].id, layers.len());
+            }
+            layers.push(layer.into_iter().map(|i| sorted[i].id).collect_vec());
+        }
+
+        info!("Computed {} convex layers", layers.len());
+        ConvexLayers { layers, depths }
+    }
+
+    /// Vertex IDs of each layer in CCW order, outermost layer first.
+    pub fn layers(&self) -> &[Vec<VertexId>] {
+        &self.layers
+    }
+
+    pub fn num_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// The index of the layer the vertex belongs to, where vertices on the
+    /// convex hull of the whole set have depth 0.
+    pub fn depth(&self, id: &VertexId) -> Option<usize> {
+        self.depths.get(id).copied()
+    }
+
+    /// Vertices with depth less than `min_depth`, these are the points
+    /// that are peeled off first and so can be treated as outliers.
+    pub fn outliers(&self, min_depth: usize) -> Vec<VertexId> {
+        self.layers
+            .iter()
+            .take(min_depth)
+            .flatten()
+            .copied()
+            .collect_vec()
+    }
+}
+
+struct HullNode {
+    // Range of sorted points under the node, end exclusive
+    lo: usize,
+    hi: usize,
+    children: Option<(usize, usize)>,
+    parent: Option<usize>,
+    count: usize,
+    bridge: Option<(usize, usize)>,
+}
+
+/// One side of the hull of the points that are left, as a tree of bridges
+/// over the points sorted by x then y. Points are referred to by their
+/// index in the sorted order. For the lower hull going left to right every
+/// turn is to the left, and for the upper hull to the right, so turns are
+/// multiplied by `sign` to make them positive either way.
+struct HullTree<'a> {
+    points: &'a [&'a Vertex],
+    sign: f64,
+    alive: Vec<bool>,
+    nodes: Vec<HullNode>,
+    leaves: Vec<usize>,
+}
+
+impl<'a> HullTree<'a> {
+    fn new(points: &'a [&'a Vertex], sign: f64) -> Self {
+        let mut tree = HullTree {
+            points,
+            sign,
+            alive: vec![true; points.len()],
+            nodes: Vec::new(),
+            leaves: vec![0; points.len()],
+        };
+        if !points.is_empty() {
+            tree.build(0, points.len(), None);
+        }
+        // Parents come before their children, so going backwards finds the
+        // bridges of the children first
+        for v in (0..tree.nodes.len()).rev() {
+            tree.update_bridge(v);
+        }
+        tree
+    }
+
+    fn build(&mut self, lo: usize, hi: usize, parent: Option<usize>) -> usize {
+        let v = self.nodes.len();
+        self.nodes.push(HullNode {
+            lo,
+            hi,
+            children: None,
+            parent,
+            count: hi - lo,
+            bridge: None,
+        });
+        if hi - lo == 1 {
+            self.leaves[lo] = v;
+        } else {
+            let mid = (lo + hi) / 2;
+            let left = self.build(lo, mid, Some(v));
+            let right = self.build(mid, hi, Some(v));
+            self.nodes[v].children = Some((left, right));
+        }
+        v
+    }
+
+    fn root(&self) -> Option<usize> {
+        (!self.nodes.is_empty() && self.nodes[0].count > 0).then_some(0)
+    }
+
+    /// Positive when `a`, `b`, `c` turn the way the hull does, negative
+    /// when `c` is outside the line from `a` to `b`.
+    fn turn(&self, a: usize, b: usize, c: usize) -> f64 {
+        self.sign * orientation(self.points[a], self.points[b], self.points[c])
+    }
+
+    /// The children of the node that still have points, both only when
+    /// the node has a bridge between them.
+    fn parts(&self, v: usize) -> (Option<usize>, Option<usize>) {
+        match self.nodes[v].children {
+            Some((left, right)) => (
+                (self.nodes[left].count > 0).then_some(left),
+                (self.nodes[right].count > 0).then_some(right),
+            ),
+            None => (None, None),
+        }
+    }
+
+    fn first(&self, v: usize) -> usize {
+        match self.parts(v) {
+            (Some(left), _) => self.first(left),
+            (None, Some(right)) => self.first(right),
+            (None, None) => self.nodes[v].lo,
+        }
+    }
+
+    fn last(&self, v: usize) -> usize {
+        match self.parts(v) {
+            (_, Some(right)) => self.last(right),
+            (Some(left), None) => self.last(left),
+            (None, None) => self.nodes[v].lo,
+        }
+    }
+
+    /// The point after `i` along the hull of the node, which `i` has to be
+    /// on.
+    fn next(&self, v: usize, i: usize) -> Option<usize> {
+        match (self.parts(v), self.nodes[v].bridge) {
+            ((Some(left), Some(right)), Some((l, r))) => match i < self.nodes[left].hi {
+                true if i == l => Some(r),
+                true => self.next(left, i),
+                false => self.next(right, i),
+            },
+            ((Some(child), None), _) | ((None, Some(child)), _) => self.next(child, i),
+            _ => None,
+        }
+    }
+
+    fn prev(&self, v: usize, i: usize) -> Option<usize> {
+        match (self.parts(v), self.nodes[v].bridge) {
+            ((Some(left), Some(right)), Some((l, r))) => match i < self.nodes[left].hi {
+                true => self.prev(left, i),
+                false if i == r => Some(l),
+                false => self.prev(right, i),
+            },
+            ((Some(child), None), _) | ((None, Some(child)), _) => self.prev(child, i),
+            _ => None,
+        }
+    }
+
+    /// The last point on the hull of the node at or before `k`.
+    fn pred(&self, v: usize, k: usize) -> Option<usize> {
+        match (self.parts(v), self.nodes[v].bridge) {
+            ((Some(left), Some(right)), Some((l, r))) => match k >= r {
+                true => self.pred(right, k),
+                false => self.pred(left, k.min(l)),
+            },
+            ((Some(child), None), _) | ((None, Some(child)), _) => self.pred(child, k),
+            _ => (self.nodes[v].count > 0 && self.nodes[v].lo <= k).then_some(self.nodes[v].lo),
+        }
+    }
+
+    /// The first point on the hull of the node at or after `k`.
+    fn succ(&self, v: usize, k: usize) -> Option<usize> {
+        match (self.parts(v), self.nodes[v].bridge) {
+            ((Some(left), Some(right)), Some((l, r))) => match k <= l {
+                true => self.succ(left, k),
+                false => self.succ(right, k.max(r)),
+            },
+            ((Some(child), None), _) | ((None, Some(child)), _) => self.succ(child, k),
+            _ => (self.nodes[v].count > 0 && self.nodes[v].lo >= k).then_some(self.nodes[v].lo),
+        }
+    }
+
+    /// Finds the bridge between the hulls of the children again, walking
+    /// from where it was before. Each end moves to a neighbor outside the
+    /// line through both ends until neither can, which is when the line
+    /// supports both hulls.
+    fn update_bridge(&mut self, v: usize) {
+        let (Some(left), Some(right)) = self.parts(v) else {
+            self.nodes[v].bridge = None;
+            return;
+        };
+        let (mut p, mut q) = match self.nodes[v].bridge {
+            Some((l, r)) => (
+                self.pred(left, l).or_else(|| self.succ(left, l)).unwrap(),
+                self.succ(right, r).or_else(|| self.pred(right, r)).unwrap(),
+            ),
+            None => (self.last(left), self.first(right)),
+        };
+        loop {
+            let outside = |a: usize, b: usize, c: Option<usize>| {
+                c.filter(|_| self.points[a].coords() != self.points[b].coords())
+                    .filter(|c| self.turn(a, b, *c) < 0.0)
+            };
+            if let Some(n) = outside(p, q, self.next(left, p)).or(outside(p, q, self.prev(left, p)))
+            {
+                p = n;
+            } else if let Some(n) =
+                outside(p, q, self.next(right, q)).or(outside(p, q, self.prev(right, q)))
+            {
+                q = n;
+            } else {
+                break;
+            }
+        }
+        self.nodes[v].bridge = Some((p, q));
+    }
+
+    /// Points on the hull of the node in order, including points on its
+    /// edges that are not corners.
+    fn boundary(&self, v: usize) -> Vec<usize> {
+        let mut corners = Vec::new();
+        self.corners(v, 0, self.points.len(), &mut corners);
+        let mut boundary = Vec::new();
+        for (a, b) in corners.iter().copied().tuple_windows() {
+            boundary.push(a);
+            self.on_edge(v, a, b, &mut boundary);
+        }
+        boundary.extend(corners.last());
+        boundary
+    }
+
+    /// Points on the hull of the node from `from` to `to`, inclusive.
+    fn corners(&self, v: usize, from: usize, to: usize, out: &mut Vec<usize>) {
+        let node = &self.nodes[v];
+        if node.count == 0 || node.hi <= from || node.lo > to {
+            return;
+        }
+        match (self.parts(v), node.bridge) {
+            ((Some(left), Some(right)), Some((l, r))) => {
+                if from <= l {
+                    self.corners(left, from, to.min(l), out);
+                }
+                if r <= to {
+                    self.corners(right, from.max(r), to, out);
+                }
+            }
+            ((Some(child), None), _) | ((None, Some(child)), _) => {
+                self.corners(child, from, to, out)
+            }
+            _ => out.push(node.lo),
+        }
+    }
+
+    /// Adds the points strictly between `a` and `b` on the line through
+    /// them, for consecutive points `a` and `b` on the hull of the node.
+    /// Every point between them is on or inside the line, so only nodes
+    /// whose hull reaches the line are searched.
+    fn on_edge(&self, v: usize, a: usize, b: usize, out: &mut Vec<usize>) {
+        let node = &self.nodes[v];
+        if node.count == 0 || node.hi <= a + 1 || node.lo >= b {
+            return;
+        }
+        if self.points[a].coords() == self.points[b].coords() {
+            return;
+        }
+        if a < node.lo && node.hi <= b && self.lowest_turn(v, a, b) > 0.0 {
+            return;
+        }
+        match node.children {
+            Some((left, right)) => {
+                self.on_edge(left, a, b, out);
+                self.on_edge(right, a, b, out);
+            }
+            None => {
+                if self.turn(a, b, node.lo) == 0.0 {
+                    out.push(node.lo);
+                }
+            }
+        }
+    }
+
+    /// The least turn from `a` to `b` to any point under the node, found by
+    /// following the hull towards the line.
+    fn lowest_turn(&self, v: usize, a: usize, b: usize) -> f64 {
+        match (self.parts(v), self.nodes[v].bridge) {
+            ((Some(left), Some(right)), Some((l, r))) => {
+                if self.turn(a, b, r) < self.turn(a, b, l) {
+                    self.lowest_turn(right, a, b)
+                } else {
+                    self.lowest_turn(left, a, b)
+                }
+            }
+            ((Some(child), None), _) | ((None, Some(child)), _) => self.lowest_turn(child, a, b),
+            _ => self.turn(a, b, self.nodes[v].lo),
+        }
+    }
+
+    /// Deletes the points and finds the bridges above them again.
+    fn delete(&mut self, points: &[usize]) {
+        let mut affected = BTreeSet::new();
+        for i in points.iter() {
+            self.alive[*i] = false;
+            let mut v = Some(self.leaves[*i]);
+            while let Some(u) = v {
+                self.nodes[u].count -= 1;
+                if self.nodes[u].children.is_some() {
+                    affected.insert(u);
+                }
+                v = self.nodes[u].parent;
+            }
+        }
+        for v in affected.into_iter().rev() {
+            self.update_bridge(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convex_hull::monotone_chain;
+    use crate::geometry::Geometry;
+    use crate::line_segment::LineSegment;
+    use crate::test_util::*;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    // Peels each layer by running monotone chain on what is left
+    fn peel(vertices: &[Vertex]) -> Vec<Vec<VertexId>> {
+        let mut remaining = vertices
+            .iter()
+            .sorted_by_key(|v| (OF(v.x), OF(v.y)))
+            .collect_vec();
+        let mut layers = Vec::new();
+        while !remaining.is_empty() {
+            let layer = monotone_chain(&remaining, true)
+                .into_iter()
+                .unique()
+                .collect_vec();
+            remaining.retain(|v| !layer.contains(&v.id));
+            layers.push(layer);
+        }
+        layers
+    }
+
+    #[test]
+    fn test_nested_squares() {
+        let coords = vec![
+            (0.0, 0.0),
+            (6.0, 0.0),
+            (6.0, 6.0),
+            (0.0, 6.0),
+            (1.0, 1.0),
+            (5.0, 1.0),
+            (5.0, 5.0),
+            (1.0, 5.0),
+            (3.0, 3.0),
+        ];
+        let vertices = vertices_from_coords(&coords);
+        let layers = ConvexLayers::from_vertices(&vertices);
+
+        assert_eq!(layers.num_layers(), 3);
+        for (i, v) in vertices.iter().enumerate() {
+            assert_eq!(layers.depth(&v.id), Some(i / 4));
+        }
+        assert_eq!(layers.outliers(1).len(), 4);
+    }
+
+    #[test]
+    fn test_collinear_points() {
+        let coords = vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 2.0), (1.0, 1.0)];
+        let vertices = vertices_from_coords(&coords);
+        let layers = ConvexLayers::from_vertices(&vertices);
+
+        // Points on hull edges are part of the hull layer, so everything
+        // is on the outermost layer
+        assert_eq!(layers.num_layers(), 1);
+        assert_eq!(layers.layers()[0].len(), 5);
+    }
+
+    #[apply(all_polygons)]
+    fn test_convex_layers(case: PolygonTestCase) {
+        let layers = ConvexLayers::from_vertices(case.polygon.vertices());
+
+        let num_layered: usize = layers.layers().iter().map(|l| l.len()).sum();
+        assert_eq!(num_layered, case.metadata.num_vertices);
+        for id in case.metadata.extreme_points.iter() {
+            assert_eq!(layers.depth(id), Some(0));
+        }
+
+        // Every point of a layer is strictly inside the hull of the layer
+        // that encloses it
+        for (outer, inner) in layers.layers().iter().tuple_windows() {
+            if outer.len() < 3 {
+                continue;
+            }
+            let hull = case.polygon.get_vertices(outer.clone());
+            for id in inner.iter() {
+                let v = case.polygon.get_vertex(id).unwrap();
+                for (v1, v2) in hull.iter().circular_tuple_windows() {
+                    assert!(v.left(&LineSegment::from_vertices(v1, v2)));
+                }
+            }
+        }
+    }
+
+    #[apply(all_polygons)]
+    fn test_convex_layers_match_peel(case: PolygonTestCase) {
+        let vertices = case.polygon.vertices().into_iter().cloned().collect_vec();
+        let layers = ConvexLayers::from_vertices(&vertices);
+        assert_eq!(layers.layers(), peel(&vertices));
+    }
+
+    #[rstest]
+    fn test_convex_layers_grid(
+        #[values(1, 2, 5, 8)] width: usize,
+        #[values(1, 3, 8)] height: usize,
+    ) {
+        // Grids have many points on each hull edge and layers that end up
+        // collinear, which makes the bridges and edge points degenerate
+        let coords = (0..width)
+            .cartesian_product(0..height)
+            .map(|(x, y)| (x as f64, y as f64))
+            .collect_vec();
+        let vertices = vertices_from_coords(&coords);
+        let layers = ConvexLayers::from_vertices(&vertices);
+        assert_eq!(layers.layers(), peel(&vertices));
+    }
+
+    #[rstest]
+    fn test_convex_layers_scattered(#[values(1, 2, 3, 10, 100, 1000)] num_points: usize) {
+        // Deterministic points on a coarse lattice so some of them coincide
+        // or line up
+        let mut random = seeded_random(num_points as u64);
+        let mut next = || random(64) as f64;
+        let coords = (0..num_points).map(|_| (next(), next())).collect_vec();
+        let vertices = vertices_from_coords(&coords);
+        let layers = ConvexLayers::from_vertices(&vertices);
+        assert_eq!(layers.layers(), peel(&vertices));
+    }
+}
@@ -109,3 +109,357 @@ impl HullSet {
         self.hull.insert(id);
     }
 }
+
+/// Disjoint sets over elements `0..n`, where elements are added one at a
+/// time and each starts out in its own set.
+#[derive(Default)]
+pub struct UnionFind {
+    parent: Vec<usize>,
+    num_sets: usize,
+}
+
+impl UnionFind {
+    pub fn new(capacity: usize) -> Self {
+        UnionFind {
+            parent: Vec::with_capacity(capacity),
+            num_sets: 0,
+        }
+    }
+
+    pub fn num_sets(&self) -> usize {
+        self.num_sets
+    }
+
+    pub fn add(&mut self) {
+        self.parent.push(self.parent.len());
+        self.num_sets += 1;
+    }
+
+    pub fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        self.parent[i] = root;
+        root
+    }
+
+    pub fn union(&mut self, i: usize, j: usize) {
+        let (root_i, root_j) = (self.find(i), self.find(j));
+        if root_i != root_j {
+            self.parent[root_i] = root_j;
+            self.num_sets -= 1;
+        }
+    }
+}
+
+/// Handle to an element of an [`OrderedList`], which stays valid until that
+/// element is removed. Handles of removed elements are reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ListHandle(usize);
+
+struct ListNode<T> {
+    value: Option<T>,
+    priority: u64,
+    parent: Option<usize>,
+    left: Option<usize>,
+    right: Option<usize>,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// A sequence kept as a treap, a binary search tree balanced by random
+/// priorities, for sweep line status structures. The order is whatever
+/// the caller inserts, so elements are found with [`Self::partition_point`]
+/// over a predicate that holds for a prefix of the list, and after that
+/// through their handles. Inserting, removing and searching take expected
+/// $O(\log n)$ time, and stepping to a neighbor $O(1)$.
+pub struct OrderedList<T> {
+    nodes: Vec<ListNode<T>>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    first: Option<usize>,
+    last: Option<usize>,
+    len: usize,
+    seed: u64,
+}
+
+impl<T> Default for OrderedList<T> {
+    fn default() -> Self {
+        OrderedList {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            first: None,
+            last: None,
+            len: 0,
+            seed: 0x9E3779B97F4A7C15,
+        }
+    }
+}
+
+impl<T> std::ops::Index<ListHandle> for OrderedList<T> {
+    type Output = T;
+
+    fn index(&self, handle: ListHandle) -> &Self::Output {
+        self.get(handle).unwrap()
+    }
+}
+
+impl<T> std::ops::IndexMut<ListHandle> for OrderedList<T> {
+    fn index_mut(&mut self, handle: ListHandle) -> &mut Self::Output {
+        self.get_mut(handle).unwrap()
+    }
+}
+
+impl<T> OrderedList<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The element for the handle, or `None` if it has been removed.
+    pub fn get(&self, handle: ListHandle) -> Option<&T> {
+        self.nodes.get(handle.0).and_then(|n| n.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: ListHandle) -> Option<&mut T> {
+        self.nodes.get_mut(handle.0).and_then(|n| n.value.as_mut())
+    }
+
+    pub fn first(&self) -> Option<ListHandle> {
+        self.first.map(ListHandle)
+    }
+
+    pub fn last(&self) -> Option<ListHandle> {
+        self.last.map(ListHandle)
+    }
+
+    pub fn next(&self, handle: ListHandle) -> Option<ListHandle> {
+        self.nodes[handle.0].next.map(ListHandle)
+    }
+
+    pub fn prev(&self, handle: ListHandle) -> Option<ListHandle> {
+        self.nodes[handle.0].prev.map(ListHandle)
+    }
+
+    /// Handles of the elements in order.
+    pub fn handles(&self) -> impl Iterator<Item = ListHandle> + '_ {
+        std::iter::successors(self.first(), |h| self.next(*h))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.handles().map(|h| &self[h])
+    }
+
+    /// The first element for which the predicate is false, where it has to
+    /// be true for every element before that one and false after, or `None`
+    /// if it is true for all of them. Same as [`slice::partition_point`]
+    /// but giving back a handle.
+    pub fn partition_point(&self, mut pred: impl FnMut(ListHandle) -> bool) -> Option<ListHandle> {
+        let mut found = None;
+        let mut current = self.root;
+        while let Some(i) = current {
+            if pred(ListHandle(i)) {
+                current = self.nodes[i].right;
+            } else {
+                found = Some(ListHandle(i));
+                current = self.nodes[i].left;
+            }
+        }
+        found
+    }
+
+    /// Inserts the value right before the element, or at the end if there
+    /// is none.
+    pub fn insert_before(&mut self, at: Option<ListHandle>, value: T) -> ListHandle {
+        let i = self.allocate(value);
+        match at {
+            Some(ListHandle(at)) => {
+                // Below the element if it has space on its left, otherwise
+                // below the element right before it, which has to have
+                // space on its right
+                let prev = self.nodes[at].prev;
+                match (self.nodes[at].left, prev) {
+                    (Some(_), Some(prev)) => self.attach(i, prev, false),
+                    _ => self.attach(i, at, true),
+                }
+                self.link(prev, Some(i));
+                self.link(Some(i), Some(at));
+            }
+            None => {
+                match self.last {
+                    Some(last) => self.attach(i, last, false),
+                    None => self.root = Some(i),
+                }
+                self.link(self.last, Some(i));
+                self.link(Some(i), None);
+            }
+        }
+        self.len += 1;
+        self.rotate_up(i);
+        ListHandle(i)
+    }
+
+    /// Inserts the value right after the element, or at the start if there
+    /// is none.
+    pub fn insert_after(&mut self, at: Option<ListHandle>, value: T) -> ListHandle {
+        let at = match at {
+            Some(at) => self.next(at),
+            None => self.first(),
+        };
+        self.insert_before(at, value)
+    }
+
+    /// Removes the element, after which its handle is no longer valid.
+    pub fn remove(&mut self, handle: ListHandle) -> T {
+        let i = handle.0;
+        // Rotate the node down until it is a leaf, then cut it off
+        loop {
+            let child = match (self.nodes[i].left, self.nodes[i].right) {
+                (Some(l), Some(r)) if self.nodes[l].priority > self.nodes[r].priority => l,
+                (Some(_), Some(r)) => r,
+                (Some(c), None) | (None, Some(c)) => c,
+                (None, None) => break,
+            };
+            self.rotate(child);
+        }
+        match self.nodes[i].parent {
+            Some(p) if self.nodes[p].left == Some(i) => self.nodes[p].left = None,
+            Some(p) => self.nodes[p].right = None,
+            None => self.root = None,
+        }
+        let (prev, next) = (self.nodes[i].prev, self.nodes[i].next);
+        self.link(prev, next);
+        self.len -= 1;
+        self.free.push(i);
+        self.nodes[i].parent = None;
+        self.nodes[i].value.take().unwrap()
+    }
+
+    fn allocate(&mut self, value: T) -> usize {
+        // Xorshift, which is plenty random for balancing
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        let node = ListNode {
+            value: Some(value),
+            priority: self.seed,
+            parent: None,
+            left: None,
+            right: None,
+            prev: None,
+            next: None,
+        };
+        match self.free.pop() {
+            Some(i) => {
+                self.nodes[i] = node;
+                i
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn attach(&mut self, child: usize, parent: usize, left: bool) {
+        if left {
+            self.nodes[parent].left = Some(child);
+        } else {
+            self.nodes[parent].right = Some(child);
+        }
+        self.nodes[child].parent = Some(parent);
+    }
+
+    fn link(&mut self, prev: Option<usize>, next: Option<usize>) {
+        match prev {
+            Some(p) => self.nodes[p].next = next,
+            None => self.first = next,
+        }
+        match next {
+            Some(n) => self.nodes[n].prev = prev,
+            None => self.last = prev,
+        }
+    }
+
+    fn rotate_up(&mut self, i: usize) {
+        while let Some(p) = self.nodes[i].parent {
+            if self.nodes[p].priority >= self.nodes[i].priority {
+                break;
+            }
+            self.rotate(i);
+        }
+    }
+
+    /// Rotates the node above its parent, keeping the order.
+    fn rotate(&mut self, i: usize) {
+        let p = self.nodes[i].parent.unwrap();
+        let grandparent = self.nodes[p].parent;
+        if self.nodes[p].left == Some(i) {
+            let middle = self.nodes[i].right;
+            self.nodes[p].left = middle;
+            self.nodes[i].right = Some(p);
+            if let Some(m) = middle {
+                self.nodes[m].parent = Some(p);
+            }
+        } else {
+            let middle = self.nodes[i].left;
+            self.nodes[p].right = middle;
+            self.nodes[i].left = Some(p);
+            if let Some(m) = middle {
+                self.nodes[m].parent = Some(p);
+            }
+        }
+        self.nodes[p].parent = Some(i);
+        self.nodes[i].parent = grandparent;
+        match grandparent {
+            Some(g) if self.nodes[g].left == Some(p) => self.nodes[g].left = Some(i),
+            Some(g) => self.nodes[g].right = Some(i),
+            None => self.root = Some(i),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::seeded_random;
+
+    #[test]
+    fn test_ordered_list_matches_vec() {
+        // Sorted insertions and removals at pseudo-random positions, checked
+        // against a vec doing the same
+        let mut list = OrderedList::new();
+        let mut expected: Vec<(u64, ListHandle)> = Vec::new();
+        let mut random = seeded_random(1);
+        for _ in 0..5000 {
+            let value = random(500);
+            if random(3) == 0 && !expected.is_empty() {
+                let i = (value as usize) % expected.len();
+                let (v, h) = expected.remove(i);
+                assert_eq!(list.remove(h), v);
+            } else {
+                let at = list.partition_point(|h| list[h] < value);
+                let h = match random(2) {
+                    0 => list.insert_before(at, value),
+                    _ => list.insert_after(at.map_or(list.last(), |at| list.prev(at)), value),
+                };
+                let i = expected.partition_point(|(v, _)| *v < value);
+                expected.insert(i, (value, h));
+            }
+            assert_eq!(list.len(), expected.len());
+        }
+        assert!(list.iter().copied().eq(expected.iter().map(|(v, _)| *v)));
+        assert!(list.handles().eq(expected.iter().map(|(_, h)| *h)));
+        let reversed = std::iter::successors(list.last(), |h| list.prev(*h));
+        assert!(reversed.eq(expected.iter().rev().map(|(_, h)| *h)));
+    }
+}
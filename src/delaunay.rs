@@ -0,0 +1,546 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use ordered_float::OrderedFloat as OF;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    error::TriangulationError,
+    geometry::Geometry,
+    polygon::Polygon,
+    predicates::{incircle, orientation},
+    triangulation::{EarClipping, TriangleVertexIds, Triangulation, TriangulationComputer},
+    vertex::{Vertex, VertexId},
+};
+
+// Stands in for a vertex at infinity that every hull edge is connected to
+pub(crate) const GHOST: usize = usize::MAX;
+
+/// Delaunay triangulation of a point set, computed incrementally with the
+/// Bowyer-Watson algorithm. Every orientation and incircle test uses the
+/// robust [`crate::predicates`], so nearly degenerate inputs are handled
+/// exactly. Duplicate points are skipped, and if every point is collinear
+/// there is nothing to triangulate so the triangulation is empty.
+pub struct Delaunay {
+    vertex_map: HashMap<VertexId, Vertex>,
+    triangulation: Triangulation,
+    duplicates: Vec<VertexId>,
+}
+
+impl Delaunay {
+    pub fn from_vertices<'a>(vertices: impl IntoIterator<Item = &'a Vertex>) -> Self {
+        info!("Computing Delaunay triangulation");
+
+        let mut coords = HashSet::new();
+        let mut points = Vec::new();
+        let mut duplicates = Vec::new();
+        for v in vertices {
+            if coords.insert((OF(v.x), OF(v.y))) {
+                points.push(v.clone());
+            } else {
+                debug!("Skipping duplicate point: {v:?}");
+                duplicates.push(v.id);
+            }
+        }
+
+        let vertex_map = points.iter().map(|v| (v.id, v.clone())).collect();
+        let triangulation = match Mesh::new(points) {
+            Some(mut mesh) => {
+                mesh.insert_all();
+                mesh.triangulation()
+            }
+            None => {
+                debug!("Points are all collinear, nothing to triangulate");
+                Triangulation::default()
+            }
+        };
+
+        info!(
+            "Computed Delaunay triangulation with {} triangles",
+            triangulation.len()
+        );
+        Delaunay {
+            vertex_map,
+            triangulation,
+            duplicates,
+        }
+    }
+
+    pub fn triangulation(&self) -> &Triangulation {
+        &self.triangulation
+    }
+
+    pub fn into_triangulation(self) -> Triangulation {
+        self.triangulation
+    }
+
+    /// IDs of points that were skipped because an earlier point had the
+    /// same coordinates.
+    pub fn duplicates(&self) -> &[VertexId] {
+        &self.duplicates
+    }
+
+    pub fn is_delaunay(&self) -> bool {
+        is_delaunay(self.vertex_map.values(), &self.triangulation)
+    }
+}
+
+/// Checks the empty circumcircle property, that no vertex is strictly
+/// inside the circumcircle of any triangle. This checks every vertex
+/// against every triangle so is only intended for verification.
+pub fn is_delaunay<'a>(
+    vertices: impl IntoIterator<Item = &'a Vertex>,
+    triangulation: &Triangulation,
+) -> bool {
+    let vertex_map: HashMap<VertexId, &Vertex> = vertices.into_iter().map(|v| (v.id, v)).collect();
+    for t in triangulation.iter() {
+        let (a, b, c) = (vertex_map[&t.0], vertex_map[&t.1], vertex_map[&t.2]);
+        let sign = orientation(a, b, c).signum();
+        for v in vertex_map.values() {
+            if incircle(a, b, c, v) * sign > 0.0 {
+                debug!("Vertex {v:?} is inside the circumcircle of {a:?}, {b:?}, {c:?}");
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Constrained Delaunay triangulation of a polygon, where the polygon edges
+/// are constraints that must be in the triangulation. Among all
+/// triangulations of the polygon this maximizes the minimum angle, so it
+/// avoids the slivers that ear clipping tends to produce.
+#[derive(Default)]
+pub struct ConstrainedDelaunay;
+
+impl ConstrainedDelaunay {
+    /// Lawson's flip algorithm, which repeatedly flips any diagonal that is
+    /// not locally Delaunay, meaning the opposite vertex of one of its two
+    /// triangles is inside the circumcircle of the other. Polygon edges are
+    /// never flipped, and once every diagonal is locally Delaunay the
+    /// triangulation is the constrained Delaunay triangulation.
+    pub fn flip_diagonals(
+        &self,
+        polygon: &Polygon,
+        triangulation: &Triangulation,
+    ) -> Triangulation {
+        info!("Flipping diagonals to constrained Delaunay");
+        let mut triangles = triangulation.iter().map(|t| [t.0, t.1, t.2]).collect_vec();
+        let mut owner: HashMap<(VertexId, VertexId), usize> = HashMap::new();
+        for (i, t) in triangles.iter().enumerate() {
+            for e in edges(t) {
+                owner.insert(e, i);
+            }
+        }
+
+        // Only diagonals have a triangle on both sides, polygon edges are
+        // left in the stack but skipped since they have no twin
+        let mut stack = owner
+            .keys()
+            .filter(|(a, b)| a < b && owner.contains_key(&(*b, *a)))
+            .copied()
+            .sorted()
+            .collect_vec();
+        let mut num_flips = 0;
+        while let Some((a, b)) = stack.pop() {
+            let (Some(&t1), Some(&t2)) = (owner.get(&(a, b)), owner.get(&(b, a))) else {
+                continue;
+            };
+            let c = opposite(&triangles[t1], a);
+            let d = opposite(&triangles[t2], b);
+            let [va, vb, vc, vd] = [a, b, c, d].map(|id| polygon.get_vertex(&id).unwrap());
+            if incircle(va, vb, vc, vd) <= 0.0 {
+                continue;
+            }
+
+            trace!("Flipping diagonal ({a}, {b}) to ({c}, {d})");
+            for e in edges(&triangles[t1])
+                .into_iter()
+                .chain(edges(&triangles[t2]))
+            {
+                owner.remove(&e);
+            }
+            triangles[t1] = [a, d, c];
+            triangles[t2] = [d, b, c];
+            for i in [t1, t2] {
+                for e in edges(&triangles[i]) {
+                    owner.insert(e, i);
+                }
+            }
+            stack.extend([(a, d), (d, b), (b, c), (c, a)]);
+            num_flips += 1;
+        }
+
+        let mut flipped = Triangulation::default();
+        for [a, b, c] in triangles {
+            flipped.push(TriangleVertexIds(a, b, c));
+        }
+        info!("Flipped {num_flips} diagonals");
+        flipped
+    }
+}
+
+impl TriangulationComputer for ConstrainedDelaunay {
+    fn triangulation(&self, polygon: &Polygon) -> Result<Triangulation, TriangulationError> {
+        let triangulation = EarClipping.triangulation(polygon)?;
+        Ok(self.flip_diagonals(polygon, &triangulation))
+    }
+}
+
+/// The vertex of the triangle opposite the edge that starts at `a`.
+fn opposite(t: &[VertexId; 3], a: VertexId) -> VertexId {
+    let i = t.iter().position(|id| *id == a).unwrap();
+    t[(i + 2) % 3]
+}
+
+/// Triangles over local point indices, where every hull edge also has a
+/// ghost triangle on its outside connecting it to the [`GHOST`] vertex.
+/// With ghost triangles there is no need for a large enclosing triangle,
+/// whose corners can end up inside circumcircles of hull triangles and
+/// break the Delaunay property on the hull.
+pub(crate) struct Mesh {
+    points: Vec<Vertex>,
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    // Directed edge to the triangle that has it in CCW order
+    edges: HashMap<(usize, usize), usize>,
+    // Recently created triangle to start point location walks from
+    last: usize,
+}
+
+impl Mesh {
+    /// Starts the mesh from the first three points that are not collinear,
+    /// or returns `None` if there are none. The rest of the points are not
+    /// in the mesh until they are inserted.
+    pub(crate) fn new(points: Vec<Vertex>) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+        let k =
+            (2..points.len()).find(|k| orientation(&points[0], &points[1], &points[*k]) != 0.0)?;
+        let (a, b, c) = if orientation(&points[0], &points[1], &points[k]) > 0.0 {
+            (0, 1, k)
+        } else {
+            (0, k, 1)
+        };
+
+        let mut mesh = Mesh {
+            points,
+            triangles: Vec::new(),
+            alive: Vec::new(),
+            edges: HashMap::new(),
+            last: 0,
+        };
+        for t in [[a, b, c], [b, a, GHOST], [c, b, GHOST], [a, c, GHOST]] {
+            mesh.add_triangle(t);
+        }
+        mesh.last = 0;
+        Some(mesh)
+    }
+
+    pub(crate) fn insert_all(&mut self) {
+        let initial = self.triangles[0];
+        for p in (0..self.points.len()).filter(|p| !initial.contains(p)) {
+            self.insert(p);
+        }
+    }
+
+    /// Adds a new point and inserts it, returning its index.
+    pub(crate) fn add_point(&mut self, v: Vertex) -> usize {
+        self.points.push(v);
+        let p = self.points.len() - 1;
+        self.insert(p);
+        p
+    }
+
+    pub(crate) fn points(&self) -> &[Vertex] {
+        &self.points
+    }
+
+    pub(crate) fn triangle(&self, id: usize) -> [usize; 3] {
+        self.triangles[id]
+    }
+
+    pub(crate) fn is_alive(&self, id: usize) -> bool {
+        self.alive[id]
+    }
+
+    /// The triangle that has the directed edge from `a` to `b` in CCW
+    /// order, which may be a ghost triangle.
+    pub(crate) fn triangle_with_edge(&self, a: usize, b: usize) -> Option<usize> {
+        self.edges.get(&(a, b)).copied()
+    }
+
+    fn add_triangle(&mut self, t: [usize; 3]) -> usize {
+        let id = self.triangles.len();
+        for (a, b) in edges(&t) {
+            self.edges.insert((a, b), id);
+        }
+        self.triangles.push(t);
+        self.alive.push(true);
+        id
+    }
+
+    fn remove_triangle(&mut self, id: usize) {
+        self.alive[id] = false;
+        for e in edges(&self.triangles[id]) {
+            self.edges.remove(&e);
+        }
+    }
+
+    /// Whether the point is strictly inside the circumcircle of the
+    /// triangle. For a ghost triangle the circumcircle degenerates to the
+    /// open half-plane outside its hull edge, plus the open hull edge.
+    fn in_conflict(&self, id: usize, p: usize) -> bool {
+        let [a, b, c] = self.triangles[id];
+        let (pa, pb, v) = (&self.points[a], &self.points[b], &self.points[p]);
+        if c != GHOST {
+            return incircle(pa, pb, &self.points[c], v) > 0.0;
+        }
+        let o = orientation(pa, pb, v);
+        o > 0.0
+            || (o == 0.0
+                && (v.x - pa.x) * (pb.x - pa.x) + (v.y - pa.y) * (pb.y - pa.y) > 0.0
+                && (v.x - pb.x) * (pa.x - pb.x) + (v.y - pb.y) * (pa.y - pb.y) > 0.0)
+    }
+
+    /// Walks from the last created triangle towards the point, stepping
+    /// over any edge the point is strictly on the other side of. This ends
+    /// at the triangle containing the point, or at a ghost triangle if the
+    /// point is outside the hull, and either one is in conflict with it.
+    fn locate(&self, p: usize) -> usize {
+        let mut current = self.last;
+        'walk: loop {
+            let t = self.triangles[current];
+            if t[2] == GHOST {
+                return current;
+            }
+            for (a, b) in edges(&t) {
+                if orientation(&self.points[a], &self.points[b], &self.points[p]) < 0.0 {
+                    current = self.edges[&(b, a)];
+                    continue 'walk;
+                }
+            }
+            return current;
+        }
+    }
+
+    fn insert(&mut self, p: usize) {
+        trace!(p:?=self.points[p].id; "Inserting point");
+
+        // The triangles in conflict with the point form a connected cavity
+        // that is star-shaped from the point, so flood fill from the one
+        // containing it and then fan out from the point to the boundary
+        let start = self.locate(p);
+        let mut cavity = HashSet::from([start]);
+        let mut stack = vec![start];
+        let mut boundary = Vec::new();
+        while let Some(id) = stack.pop() {
+            for (a, b) in edges(&self.triangles[id]) {
+                let neighbor = self.edges[&(b, a)];
+                if cavity.contains(&neighbor) {
+                    continue;
+                }
+                if self.in_conflict(neighbor, p) {
+                    cavity.insert(neighbor);
+                    stack.push(neighbor);
+                } else {
+                    boundary.push((a, b));
+                }
+            }
+        }
+
+        for id in cavity {
+            self.remove_triangle(id);
+        }
+        for (a, b) in boundary {
+            let id = match (a, b) {
+                (GHOST, _) => self.add_triangle([b, p, GHOST]),
+                (_, GHOST) => self.add_triangle([p, a, GHOST]),
+                _ => self.add_triangle([a, b, p]),
+            };
+            if self.triangles[id][2] != GHOST {
+                self.last = id;
+            }
+        }
+    }
+
+    pub(crate) fn triangulation(&self) -> Triangulation {
+        let mut triangulation = Triangulation::default();
+        for (t, _) in self
+            .triangles
+            .iter()
+            .zip(self.alive.iter())
+            .filter(|(t, alive)| **alive && t[2] != GHOST)
+        {
+            let [a, b, c] = t.map(|i| self.points[i].id);
+            triangulation.push(TriangleVertexIds(a, b, c));
+        }
+        triangulation
+    }
+}
+
+pub(crate) fn edges<T: Copy>(t: &[T; 3]) -> [(T, T); 3] {
+    [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convex_hull::{monotone_chain, ConvexHullComputer, GrahamScan};
+    use crate::geometry::Geometry;
+    use crate::test_util::*;
+    use crate::triangle::Triangle;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    fn area(vertices: &[Vertex], triangulation: &Triangulation) -> f64 {
+        let vertex_map: HashMap<_, _> = vertices.iter().map(|v| (v.id, v)).collect();
+        triangulation
+            .iter()
+            .map(|t| {
+                Triangle::from_vertices(vertex_map[&t.0], vertex_map[&t.1], vertex_map[&t.2]).area()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_square_with_center() {
+        let vertices =
+            vertices_from_coords(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0), (2.0, 1.0)]);
+        let delaunay = Delaunay::from_vertices(&vertices);
+        assert_eq!(delaunay.triangulation().len(), 4);
+        assert!(delaunay.is_delaunay());
+        assert_eq!(area(&vertices, delaunay.triangulation()), 16.0);
+    }
+
+    #[test]
+    fn test_cocircular_grid() {
+        // Every square of the grid has four cocircular corners, so either
+        // diagonal is fine but there must be exactly two triangles each
+        let coords = (0..5)
+            .cartesian_product(0..5)
+            .map(|(i, j)| (i as f64, j as f64))
+            .collect_vec();
+        let vertices = vertices_from_coords(&coords);
+        let delaunay = Delaunay::from_vertices(&vertices);
+        assert_eq!(delaunay.triangulation().len(), 32);
+        assert!(delaunay.is_delaunay());
+        assert_eq!(area(&vertices, delaunay.triangulation()), 16.0);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let vertices =
+            vertices_from_coords(&[(0.0, 0.0), (4.0, 0.0), (0.0, 0.0), (0.0, 4.0), (4.0, 0.0)]);
+        let delaunay = Delaunay::from_vertices(&vertices);
+        assert_eq!(
+            delaunay.duplicates(),
+            &[VertexId::from(2usize), VertexId::from(4usize)]
+        );
+        assert_eq!(delaunay.triangulation().len(), 1);
+    }
+
+    #[test]
+    fn test_collinear() {
+        let mut coords = (0..10).map(|i| (i as f64, 2.0 * i as f64)).collect_vec();
+        let delaunay = Delaunay::from_vertices(&vertices_from_coords(&coords));
+        assert!(delaunay.triangulation().is_empty());
+
+        // A single point off the line fans out to every segment on it
+        coords.push((0.0, 5.0));
+        let vertices = vertices_from_coords(&coords);
+        let delaunay = Delaunay::from_vertices(&vertices);
+        assert_eq!(delaunay.triangulation().len(), 9);
+        assert!(delaunay.is_delaunay());
+        assert_eq!(area(&vertices, delaunay.triangulation()), 22.5);
+    }
+
+    #[test]
+    fn test_nearly_collinear() {
+        // These are collinear over the reals but not after rounding, which
+        // makes plain floating point predicates inconsistent
+        let mut coords = (0..50)
+            .map(|i| (0.1 * i as f64, 0.3 * i as f64))
+            .collect_vec();
+        coords.push((3.0, 0.0));
+        coords.push((0.0, 10.0));
+        let vertices = vertices_from_coords(&coords);
+        let delaunay = Delaunay::from_vertices(&vertices);
+        assert!(delaunay.is_delaunay());
+        for t in delaunay.triangulation().iter() {
+            let vertex_map: HashMap<_, _> = vertices.iter().map(|v| (v.id, v)).collect();
+            assert!(orientation(vertex_map[&t.0], vertex_map[&t.1], vertex_map[&t.2]) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_is_delaunay() {
+        let vertices = vertices_from_coords(&[(0.0, 0.0), (2.0, -1.0), (4.0, 0.0), (2.0, 1.0)]);
+        let ids = vertices.iter().map(|v| v.id).collect_vec();
+        let mut triangulation = Triangulation::default();
+        triangulation.push(TriangleVertexIds(ids[0], ids[1], ids[2]));
+        triangulation.push(TriangleVertexIds(ids[0], ids[2], ids[3]));
+        assert!(!is_delaunay(&vertices, &triangulation));
+
+        let mut triangulation = Triangulation::default();
+        triangulation.push(TriangleVertexIds(ids[0], ids[1], ids[3]));
+        triangulation.push(TriangleVertexIds(ids[1], ids[2], ids[3]));
+        assert!(is_delaunay(&vertices, &triangulation));
+    }
+
+    #[apply(all_polygons)]
+    fn test_constrained_delaunay(case: PolygonTestCase) {
+        let ear_clipping = EarClipping.triangulation(&case.polygon).unwrap();
+        let cdt = ConstrainedDelaunay.flip_diagonals(&case.polygon, &ear_clipping);
+        assert_eq!(cdt.len(), case.metadata.num_triangles);
+
+        // Every polygon edge is kept and every diagonal is locally Delaunay
+        let owner: HashMap<_, _> = cdt
+            .iter()
+            .flat_map(|t| edges(&[t.0, t.1, t.2]).map(|e| (e, [t.0, t.1, t.2])))
+            .collect();
+        for e in case.polygon.edges() {
+            assert!(owner.contains_key(&e));
+        }
+        for ((a, b), t) in owner.iter() {
+            if let Some(u) = owner.get(&(*b, *a)) {
+                let ids = [*a, *b, opposite(t, *a), opposite(u, *b)];
+                let [va, vb, vc, vd] = ids.map(|id| case.polygon.get_vertex(&id).unwrap());
+                assert!(incircle(va, vb, vc, vd) <= 0.0);
+            }
+        }
+
+        // The minimum angle is the largest of any triangulation
+        let min_angle = |triangulation: &Triangulation| {
+            triangulation
+                .iter()
+                .map(|t| {
+                    case.polygon
+                        .get_triangle(&t.0, &t.1, &t.2)
+                        .unwrap()
+                        .min_angle()
+                })
+                .fold(f64::MAX, f64::min)
+        };
+        assert!(min_angle(&cdt) >= min_angle(&ear_clipping));
+    }
+
+    #[apply(all_polygons)]
+    fn test_delaunay(case: PolygonTestCase) {
+        let vertices = case.polygon.vertices().into_iter().cloned().collect_vec();
+        let delaunay = Delaunay::from_vertices(&vertices);
+        assert!(delaunay.is_delaunay());
+
+        // Triangulating n points with h of them on the hull boundary always
+        // gives 2n - h - 2 triangles that exactly cover the hull
+        let sorted = vertices
+            .iter()
+            .sorted_by_key(|v| (OF(v.x), OF(v.y)))
+            .collect_vec();
+        let h = monotone_chain(&sorted, true).into_iter().unique().count();
+        let n = vertices.len();
+        assert_eq!(delaunay.triangulation().len(), 2 * n - h - 2);
+        let hull = GrahamScan.convex_hull(&case.polygon, &mut None);
+        assert_eq!(area(&vertices, delaunay.triangulation()), hull.area());
+    }
+}
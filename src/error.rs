@@ -1,4 +1,6 @@
-use std::io;
+use std::{error, fmt, io};
+
+use crate::vertex::{Vertex, VertexId};
 
 #[derive(Debug)]
 pub enum FileError {
@@ -17,3 +19,188 @@ impl From<serde_json::Error> for FileError {
         FileError::Parse(value)
     }
 }
+
+#[derive(Clone, Debug)]
+pub enum TriangulationError {
+    /// No ear could be found, which can only happen if the polygon is not
+    /// simple. Holds the vertices of the sub-polygon that was left to
+    /// triangulate in CCW order, for debugging.
+    EarNotFound(Vec<Vertex>),
+    /// The polygon has fewer than 3 vertices, coordinates that are not
+    /// finite, or an area that is not positive, or refinement was asked
+    /// for quality bounds that can't be met.
+    DegenerateInput(String),
+    /// Floating point error left the algorithm in an inconsistent state.
+    NumericalFailure(String),
+    /// No vertex of the polygon is in its kernel, so there is nowhere to
+    /// fan a triangulation out from.
+    NoKernelVertex,
+    /// The polygon has no kernel, so there is no point to fan a
+    /// triangulation out from.
+    NotStarShaped,
+    /// Refinement used up the number of Steiner vertices it was allowed
+    /// before every triangle met the quality bounds.
+    SteinerLimitReached(usize),
+}
+
+impl fmt::Display for TriangulationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TriangulationError::EarNotFound(remaining) => write!(
+                f,
+                "no ear found with {} vertices left, polygon is likely not simple",
+                remaining.len()
+            ),
+            TriangulationError::DegenerateInput(reason) => write!(f, "degenerate input: {reason}"),
+            TriangulationError::NumericalFailure(reason) => {
+                write!(f, "numerical failure: {reason}")
+            }
+            TriangulationError::NoKernelVertex => {
+                write!(f, "no vertex of the polygon sees all of it")
+            }
+            TriangulationError::NotStarShaped => {
+                write!(f, "no point of the polygon sees all of it")
+            }
+            TriangulationError::SteinerLimitReached(limit) => write!(
+                f,
+                "quality bounds not met after inserting {limit} Steiner vertices"
+            ),
+        }
+    }
+}
+
+impl error::Error for TriangulationError {}
+
+#[derive(Clone, Debug)]
+pub enum TrapezoidalMapError {
+    /// Floating point error, or segments that cross, left the map without a
+    /// trapezoid where a segment being inserted should pass through.
+    NumericalFailure(String),
+}
+
+impl fmt::Display for TrapezoidalMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrapezoidalMapError::NumericalFailure(reason) => {
+                write!(f, "numerical failure: {reason}")
+            }
+        }
+    }
+}
+
+impl error::Error for TrapezoidalMapError {}
+
+impl From<TrapezoidalMapError> for TriangulationError {
+    fn from(value: TrapezoidalMapError) -> Self {
+        match value {
+            TrapezoidalMapError::NumericalFailure(reason) => {
+                TriangulationError::NumericalFailure(format!("trapezoidal map: {reason}"))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum QueryPointError {
+    /// The query point is outside of the polygon, neither inside it nor on
+    /// its boundary.
+    OutsidePolygon(Vertex),
+    /// The polygon could not be triangulated to answer the query.
+    Triangulation(TriangulationError),
+}
+
+impl From<TriangulationError> for QueryPointError {
+    fn from(value: TriangulationError) -> Self {
+        QueryPointError::Triangulation(value)
+    }
+}
+
+impl fmt::Display for QueryPointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryPointError::OutsidePolygon(v) => {
+                write!(f, "query point ({}, {}) is outside the polygon", v.x, v.y)
+            }
+            QueryPointError::Triangulation(e) => write!(f, "triangulation failed: {e}"),
+        }
+    }
+}
+
+impl error::Error for QueryPointError {}
+
+#[derive(Clone, Debug)]
+pub enum SkeletonError {
+    /// Floating point error left the wavefront in a state where no event
+    /// could be found to continue shrinking it.
+    NumericalFailure(String),
+    /// The boundary can only be sampled at a finite spacing greater than
+    /// zero.
+    InvalidSpacing(f64),
+}
+
+impl fmt::Display for SkeletonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkeletonError::NumericalFailure(reason) => write!(f, "numerical failure: {reason}"),
+            SkeletonError::InvalidSpacing(spacing) => {
+                write!(f, "sample spacing {spacing} is not finite and positive")
+            }
+        }
+    }
+}
+
+impl error::Error for SkeletonError {}
+
+#[derive(Clone, Debug)]
+pub enum ClipError {
+    /// The clip window has a reflex vertex, so it can't be clipped to one
+    /// edge at a time.
+    NonConvexWindow,
+}
+
+impl fmt::Display for ClipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipError::NonConvexWindow => write!(f, "clip window is not convex"),
+        }
+    }
+}
+
+impl error::Error for ClipError {}
+
+#[derive(Clone, Debug)]
+pub enum ConvexHullError {
+    /// The polygons to merge have vertices with the same IDs, so the merged
+    /// hull could not say which polygon each of its vertices came from.
+    SharedVertexIds(Vec<VertexId>),
+}
+
+impl fmt::Display for ConvexHullError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConvexHullError::SharedVertexIds(ids) => {
+                write!(f, "polygons to merge share vertex IDs: {ids:?}")
+            }
+        }
+    }
+}
+
+impl error::Error for ConvexHullError {}
+
+#[derive(Clone, Debug)]
+pub enum OffsetError {
+    /// The polygon can only be offset by a finite distance.
+    InvalidDistance(f64),
+}
+
+impl fmt::Display for OffsetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OffsetError::InvalidDistance(distance) => {
+                write!(f, "offset distance {distance} is not finite")
+            }
+        }
+    }
+}
+
+impl error::Error for OffsetError {}
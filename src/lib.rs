@@ -6,18 +6,34 @@
 // empirical precision limit on the entire test suite
 const F64_ASSERT_PRECISION: f64 = 1e-4f64;
 
+pub mod alpha_shape;
+pub mod art_gallery;
+pub mod boolean;
 pub mod bounding_box;
+pub mod clipping;
+pub mod convex_decomposition;
 pub mod convex_hull;
+pub mod convex_layers;
 pub mod data_structure;
+pub mod delaunay;
 pub mod error;
 pub mod geometry;
 pub mod line_segment;
+pub mod mesh;
+pub mod overlay;
 pub mod polygon;
+pub mod predicates;
+pub mod refinement;
+pub mod shortest_path;
+pub mod skeleton;
+pub mod trapezoidal_map;
 pub mod triangle;
 pub mod triangulation;
 pub mod util;
 pub mod vector;
 pub mod vertex;
+pub mod visibility;
+pub mod voronoi;
 
 #[cfg(test)]
 pub mod test_util;
@@ -0,0 +1,192 @@
+use itertools::Itertools;
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    triangulation::{TriangleVertexIds, Triangulation},
+    vertex::VertexId,
+};
+
+/// Index of a triangle in a [`TriangleMesh`], which is the same as its
+/// position in the [`Triangulation`] the mesh was built from.
+pub type TriangleId = usize;
+
+/// A triangulation with adjacency between its triangles, for walking
+/// across edges and around vertices. The dual graph has a node for each
+/// triangle and an edge for each pair of triangles sharing an edge, which
+/// for a triangulation of a simple polygon is a tree.
+pub struct TriangleMesh {
+    triangles: Vec<TriangleVertexIds>,
+    edges: HashMap<(VertexId, VertexId), TriangleId>,
+    incident: HashMap<VertexId, Vec<TriangleId>>,
+}
+
+impl TriangleMesh {
+    pub fn from_triangulation(triangulation: &Triangulation) -> Self {
+        let triangles = triangulation.iter().copied().collect_vec();
+        let mut edges = HashMap::new();
+        let mut incident: HashMap<VertexId, Vec<TriangleId>> = HashMap::new();
+        for (i, t) in triangles.iter().enumerate() {
+            for (a, b) in triangle_edges(t) {
+                edges.insert((a, b), i);
+                incident.entry(a).or_default().push(i);
+            }
+        }
+        TriangleMesh {
+            triangles,
+            edges,
+            incident,
+        }
+    }
+
+    pub fn num_triangles(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn triangle(&self, id: TriangleId) -> &TriangleVertexIds {
+        &self.triangles[id]
+    }
+
+    /// The triangle that has the directed edge from `a` to `b` in CCW
+    /// order, if there is one.
+    pub fn triangle_with_edge(&self, a: &VertexId, b: &VertexId) -> Option<TriangleId> {
+        self.edges.get(&(*a, *b)).copied()
+    }
+
+    /// Neighbors across the edges `(t.0, t.1)`, `(t.1, t.2)` and `(t.2, t.0)`
+    /// of the triangle, which are `None` on the boundary.
+    pub fn neighbors(&self, id: TriangleId) -> [Option<TriangleId>; 3] {
+        triangle_edges(&self.triangles[id]).map(|(a, b)| self.triangle_with_edge(&b, &a))
+    }
+
+    /// Triangles that have the vertex as one of their corners.
+    pub fn incident_triangles(&self, id: &VertexId) -> &[TriangleId] {
+        self.incident.get(id).map_or(&[], |ts| ts.as_slice())
+    }
+
+    /// Edges shared by two triangles, each given once with the smaller
+    /// vertex ID first.
+    pub fn diagonals(&self) -> Vec<(VertexId, VertexId)> {
+        self.edges
+            .keys()
+            .filter(|(a, b)| a < b && self.edges.contains_key(&(*b, *a)))
+            .copied()
+            .sorted()
+            .collect_vec()
+    }
+
+    /// Edges on the boundary of the mesh, each directed CCW around the mesh.
+    pub fn boundary_edges(&self) -> Vec<(VertexId, VertexId)> {
+        self.edges
+            .keys()
+            .filter(|(a, b)| !self.edges.contains_key(&(*b, *a)))
+            .copied()
+            .sorted()
+            .collect_vec()
+    }
+
+    /// Adjacency lists of the dual graph, indexed by triangle.
+    pub fn dual_graph(&self) -> Vec<Vec<TriangleId>> {
+        (0..self.triangles.len())
+            .map(|t| self.neighbors(t).into_iter().flatten().collect_vec())
+            .collect_vec()
+    }
+
+    /// Triangles in breadth-first order over the dual graph starting from
+    /// `root`, each paired with the triangle it was reached from. Every
+    /// triangle after the root shares an edge with its parent.
+    pub fn dual_traversal(&self, root: TriangleId) -> Vec<(TriangleId, Option<TriangleId>)> {
+        let mut parents = vec![None; self.triangles.len()];
+        let mut visited = vec![false; self.triangles.len()];
+        let mut order = Vec::new();
+        let mut queue = VecDeque::from([root]);
+        visited[root] = true;
+        while let Some(t) = queue.pop_front() {
+            order.push((t, parents[t]));
+            for n in self.neighbors(t).into_iter().flatten() {
+                if !visited[n] {
+                    visited[n] = true;
+                    parents[n] = Some(t);
+                    queue.push_back(n);
+                }
+            }
+        }
+        order
+    }
+
+    /// Triangles on the path between two triangles in the dual graph,
+    /// including both ends. For a triangulated polygon the dual graph is a
+    /// tree so this is the unique sleeve of triangles connecting them.
+    pub fn dual_path(&self, from: TriangleId, to: TriangleId) -> Option<Vec<TriangleId>> {
+        let parents: HashMap<_, _> = self.dual_traversal(to).into_iter().collect();
+        parents.get(&from)?;
+        let mut path = vec![from];
+        let mut current = from;
+        while let Some(parent) = parents[&current] {
+            path.push(parent);
+            current = parent;
+        }
+        Some(path)
+    }
+}
+
+fn triangle_edges(t: &TriangleVertexIds) -> [(VertexId, VertexId); 3] {
+    [(t.0, t.1), (t.1, t.2), (t.2, t.0)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Geometry;
+    use crate::test_util::*;
+    use crate::triangulation::{EarClipping, TriangulationComputer};
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    #[apply(all_polygons)]
+    fn test_triangle_mesh(case: PolygonTestCase) {
+        let triangulation = EarClipping.triangulation(&case.polygon).unwrap();
+        let mesh = TriangleMesh::from_triangulation(&triangulation);
+        let n = case.metadata.num_vertices;
+
+        assert_eq!(mesh.num_triangles(), n - 2);
+        assert_eq!(mesh.diagonals().len(), n - 3);
+        assert_eq!(
+            mesh.boundary_edges().into_iter().sorted().collect_vec(),
+            case.polygon.edges().into_iter().sorted().collect_vec()
+        );
+        let num_incident: usize = case
+            .polygon
+            .vertex_ids()
+            .iter()
+            .map(|id| mesh.incident_triangles(id).len())
+            .sum();
+        assert_eq!(num_incident, 3 * (n - 2));
+
+        // Neighbors are symmetric and share the edge between them
+        for t in 0..mesh.num_triangles() {
+            for (n, (a, b)) in mesh
+                .neighbors(t)
+                .iter()
+                .zip(triangle_edges(mesh.triangle(t)))
+            {
+                if let Some(n) = n {
+                    assert_eq!(mesh.triangle_with_edge(&b, &a), Some(*n));
+                    assert!(mesh.neighbors(*n).contains(&Some(t)));
+                }
+            }
+        }
+
+        // The dual graph is a tree, so it is connected with one less edge
+        // than it has nodes
+        let num_dual_edges: usize = mesh.dual_graph().iter().map(|ns| ns.len()).sum();
+        assert_eq!(num_dual_edges, 2 * (mesh.num_triangles() - 1));
+        assert_eq!(mesh.dual_traversal(0).len(), mesh.num_triangles());
+
+        let path = mesh.dual_path(0, mesh.num_triangles() - 1).unwrap();
+        assert_eq!(path[0], 0);
+        assert_eq!(*path.last().unwrap(), mesh.num_triangles() - 1);
+        for (t, u) in path.iter().tuple_windows() {
+            assert!(mesh.neighbors(*t).contains(&Some(*u)));
+        }
+    }
+}
@@ -0,0 +1,531 @@
+use itertools::Itertools;
+use log::{debug, trace};
+use ordered_float::OrderedFloat as OF;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// Relative tolerance for points to be merged into one node, scaled by the
+/// size of the loops.
+const NODE_TOLERANCE: f64 = 1e-9;
+
+/// Below this, segments are treated as parallel.
+const PARALLEL_TOLERANCE: f64 = 1e-12;
+
+type Point = (f64, f64);
+
+fn cross(a: Point, b: Point) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn dot(a: Point, b: Point) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+/// Closed loops of segments from one or more operands, which may cross
+/// each other and themselves. Every point of the plane off the loops has a
+/// winding number for each operand, counting how many times the loops of
+/// that operand go around it CCW, and a region is picked out by a rule on
+/// those winding numbers. Its boundary is found by splitting the loops
+/// where they cross and keeping the pieces with the region on one side
+/// only, which is $O(n^2)$ for $n$ segments.
+pub(crate) struct Overlay {
+    num_operands: usize,
+    loops: Vec<(usize, Vec<Point>)>,
+}
+
+/// A piece of the loops between two nodes, going from the lower to the
+/// higher node, with how many more times each operand goes that way than
+/// the other.
+struct Piece {
+    from: usize,
+    to: usize,
+    multiplicity: Vec<i32>,
+}
+
+impl Overlay {
+    pub(crate) fn new(num_operands: usize) -> Self {
+        Overlay {
+            num_operands,
+            loops: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add_loop(&mut self, operand: usize, points: Vec<Point>) {
+        self.loops.push((operand, points));
+    }
+
+    /// Boundary of the region whose winding numbers satisfy `inside`, as
+    /// simple loops with the region on their left. Outer boundaries are
+    /// CCW and boundaries of holes are CW. Where the region touches itself
+    /// at a point the loops are kept apart, so none of them pass through a
+    /// point twice.
+    pub(crate) fn boundary(&self, inside: impl Fn(&[i32]) -> bool) -> Vec<Vec<Point>> {
+        let all_points = self.loops.iter().flat_map(|(_, points)| points.iter());
+        let (min, max) = all_points.fold(
+            (
+                (f64::INFINITY, f64::INFINITY),
+                (f64::NEG_INFINITY, f64::NEG_INFINITY),
+            ),
+            |(min, max), p| {
+                (
+                    (min.0.min(p.0), min.1.min(p.1)),
+                    (max.0.max(p.0), max.1.max(p.1)),
+                )
+            },
+        );
+        let tolerance = NODE_TOLERANCE * (max.0 - min.0).max(max.1 - min.1);
+        if tolerance.is_nan() || tolerance <= 0.0 {
+            return Vec::new();
+        }
+
+        let segments = self
+            .loops
+            .iter()
+            .flat_map(|(operand, points)| {
+                points
+                    .iter()
+                    .circular_tuple_windows()
+                    .filter(|(a, b)| a != b)
+                    .map(|(a, b)| (*a, *b, *operand))
+            })
+            .collect_vec();
+        let splits = split_points(&segments, tolerance);
+        let mut nodes = Nodes::new(tolerance);
+        let mut pieces: HashMap<(usize, usize), Vec<i32>> = HashMap::new();
+        for ((a, b, operand), points) in segments.iter().zip(splits) {
+            let d = sub(*b, *a);
+            let path = points
+                .into_iter()
+                .chain([*a, *b])
+                .sorted_by_key(|p| OF(dot(sub(*p, *a), d)))
+                .map(|p| nodes.insert(p))
+                .dedup()
+                .collect_vec();
+            for (from, to) in path.into_iter().tuple_windows() {
+                let key = (from.min(to), from.max(to));
+                let multiplicity = pieces
+                    .entry(key)
+                    .or_insert_with(|| vec![0; self.num_operands]);
+                multiplicity[*operand] += if from < to { 1 } else { -1 };
+            }
+        }
+        // Pieces going both ways equally often have the same winding
+        // numbers on both sides, so they can be left out
+        let pieces = pieces
+            .into_iter()
+            .filter(|(_, multiplicity)| multiplicity.iter().any(|m| *m != 0))
+            .sorted_by_key(|(key, _)| *key)
+            .map(|((from, to), multiplicity)| Piece {
+                from,
+                to,
+                multiplicity,
+            })
+            .collect_vec();
+        debug!(
+            "Split {} segments into {} pieces between {} nodes",
+            segments.len(),
+            pieces.len(),
+            nodes.points.len()
+        );
+
+        let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut edges = Vec::new();
+        for (k, piece) in pieces.iter().enumerate() {
+            let (left, right) = side_winding_numbers(&nodes.points, &pieces, k);
+            let (in_left, in_right) = (inside(&left), inside(&right));
+            if in_left == in_right {
+                continue;
+            }
+            let edge = if in_left {
+                (piece.from, piece.to)
+            } else {
+                (piece.to, piece.from)
+            };
+            outgoing.entry(edge.0).or_default().push(edges.len());
+            edges.push(edge);
+        }
+        trace!("Kept {} of {} pieces", edges.len(), pieces.len());
+        link(&nodes.points, &edges, &outgoing, tolerance)
+    }
+}
+
+/// Points where each segment crosses or touches any other segment.
+fn split_points(segments: &[(Point, Point, usize)], tolerance: f64) -> Vec<Vec<Point>> {
+    let mut splits = vec![Vec::new(); segments.len()];
+    let order = (0..segments.len())
+        .sorted_by_key(|i| OF(segments[*i].0 .0.min(segments[*i].1 .0)))
+        .collect_vec();
+    for (k, &i) in order.iter().enumerate() {
+        let (p, p2, _) = segments[i];
+        let max_x = p.0.max(p2.0) + tolerance;
+        for &j in order[k + 1..].iter() {
+            let (q, q2, _) = segments[j];
+            if q.0.min(q2.0) > max_x {
+                break;
+            }
+            if p.1.max(p2.1) + tolerance < q.1.min(q2.1)
+                || q.1.max(q2.1) + tolerance < p.1.min(p2.1)
+            {
+                continue;
+            }
+            for (point, on_i, on_j) in intersections((p, p2), (q, q2), tolerance) {
+                if on_i {
+                    splits[i].push(point);
+                }
+                if on_j {
+                    splits[j].push(point);
+                }
+            }
+        }
+    }
+    splits
+}
+
+/// Where the segments meet, with whether each point is inside the first
+/// and second segment rather than at one of its ends. Points within the
+/// tolerance of an end are moved onto it.
+fn intersections(
+    (p, p2): (Point, Point),
+    (q, q2): (Point, Point),
+    tolerance: f64,
+) -> Vec<(Point, bool, bool)> {
+    let (r, s) = (sub(p2, p), sub(q2, q));
+    let (len_r, len_s) = (r.0.hypot(r.1), s.0.hypot(s.1));
+    let d = cross(r, s);
+    let qp = sub(q, p);
+    if d.abs() <= PARALLEL_TOLERANCE * len_r * len_s {
+        if (cross(r, qp) / len_r).abs() > tolerance {
+            return Vec::new();
+        }
+        // Collinear segments overlap where the ends of each are inside
+        // the other
+        let inside = |a: Point, b: Point, length: f64, v: Point| {
+            let t = dot(sub(v, a), sub(b, a)) / (length * length);
+            t * length > tolerance && (1.0 - t) * length > tolerance
+        };
+        let mut points = Vec::new();
+        for v in [q, q2] {
+            if inside(p, p2, len_r, v) {
+                points.push((v, true, false));
+            }
+        }
+        for v in [p, p2] {
+            if inside(q, q2, len_s, v) {
+                points.push((v, false, true));
+            }
+        }
+        return points;
+    }
+
+    let t = cross(qp, s) / d;
+    let u = cross(qp, r) / d;
+    let (et, eu) = (tolerance / len_r, tolerance / len_s);
+    if t < -et || t > 1.0 + et || u < -eu || u > 1.0 + eu {
+        return Vec::new();
+    }
+    let (at_p, at_p2) = (t.abs() <= et, (1.0 - t).abs() <= et);
+    let (at_q, at_q2) = (u.abs() <= eu, (1.0 - u).abs() <= eu);
+    let point = if at_p {
+        p
+    } else if at_p2 {
+        p2
+    } else if at_q {
+        q
+    } else if at_q2 {
+        q2
+    } else {
+        (p.0 + t * r.0, p.1 + t * r.1)
+    };
+    vec![(point, !(at_p || at_p2), !(at_q || at_q2))]
+}
+
+/// Points merged into nodes when they are within the tolerance, by hashing
+/// them into a grid.
+struct Nodes {
+    points: Vec<Point>,
+    grid: HashMap<(i64, i64), Vec<usize>>,
+    tolerance: f64,
+}
+
+impl Nodes {
+    fn new(tolerance: f64) -> Self {
+        Nodes {
+            points: Vec::new(),
+            grid: HashMap::new(),
+            tolerance,
+        }
+    }
+
+    fn insert(&mut self, p: Point) -> usize {
+        let cell = (
+            (p.0 / self.tolerance).floor() as i64,
+            (p.1 / self.tolerance).floor() as i64,
+        );
+        for (dx, dy) in (-1..=1).cartesian_product(-1..=1) {
+            for &n in self
+                .grid
+                .get(&(cell.0 + dx, cell.1 + dy))
+                .into_iter()
+                .flatten()
+            {
+                let q = self.points[n];
+                if (p.0 - q.0).hypot(p.1 - q.1) <= self.tolerance {
+                    return n;
+                }
+            }
+        }
+        self.points.push(p);
+        self.grid
+            .entry(cell)
+            .or_default()
+            .push(self.points.len() - 1);
+        self.points.len() - 1
+    }
+}
+
+/// Signed number of times the piece from `a` to `b` crosses the ray going
+/// right from `p`, counting upward crossings as positive.
+fn ray_crossing(a: Point, b: Point, p: Point) -> i32 {
+    let crosses_right = || a.0 + (p.1 - a.1) * (b.0 - a.0) / (b.1 - a.1) > p.0;
+    if a.1 <= p.1 && p.1 < b.1 && crosses_right() {
+        1
+    } else if b.1 <= p.1 && p.1 < a.1 && crosses_right() {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Winding numbers of each operand just to the left and right of piece
+/// `k`, going from its lower to its higher node. The other pieces are
+/// counted where they cross a ray from its midpoint, which runs across
+/// the piece so that only points on one side of it see it cross the ray.
+/// Going along the ray is horizontal, unless the piece is closer to
+/// horizontal, in which case the axes are swapped, mirroring the plane.
+fn side_winding_numbers(nodes: &[Point], pieces: &[Piece], k: usize) -> (Vec<i32>, Vec<i32>) {
+    let (a, b) = (nodes[pieces[k].from], nodes[pieces[k].to]);
+    let mirrored = (b.0 - a.0).abs() > (b.1 - a.1).abs();
+    let flip = |p: Point| if mirrored { (p.1, p.0) } else { p };
+    let (a, b) = (flip(a), flip(b));
+    let mid = ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+
+    let mut others = vec![0; pieces[k].multiplicity.len()];
+    for (l, piece) in pieces.iter().enumerate() {
+        if l == k {
+            continue;
+        }
+        let crossing = ray_crossing(flip(nodes[piece.from]), flip(nodes[piece.to]), mid);
+        if crossing != 0 {
+            for (w, m) in others.iter_mut().zip(piece.multiplicity.iter()) {
+                *w += crossing * m;
+            }
+        }
+    }
+
+    // Points just before the piece along the ray see it cross
+    let multiplicity = &pieces[k].multiplicity;
+    let before = others
+        .iter()
+        .zip(multiplicity.iter())
+        .map(|(w, m)| w + if b.1 > a.1 { *m } else { -*m })
+        .collect_vec();
+    // Going up, the left side is before the piece along the ray
+    let (left, right) = if b.1 > a.1 {
+        (before, others)
+    } else {
+        (others, before)
+    };
+    if mirrored {
+        let negate = |w: Vec<i32>| w.into_iter().map(|w| -w).collect_vec();
+        (negate(right), negate(left))
+    } else {
+        (left, right)
+    }
+}
+
+/// Joins the edges into loops. Arriving at a node, the loop leaves along
+/// the first edge clockwise from the one it came in on, which keeps apart
+/// parts of the region that only touch at the node. A loop can still come
+/// back to a node where a hole touches the outer boundary, so it is split
+/// there into the outer boundary and the hole.
+fn link(
+    nodes: &[Point],
+    edges: &[(usize, usize)],
+    outgoing: &HashMap<usize, Vec<usize>>,
+    tolerance: f64,
+) -> Vec<Vec<Point>> {
+    let angle = |e: usize| {
+        let d = sub(nodes[edges[e].1], nodes[edges[e].0]);
+        d.1.atan2(d.0)
+    };
+    let mut used = vec![false; edges.len()];
+    let mut loops = Vec::new();
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+        let mut path = Vec::new();
+        let mut e = start;
+        loop {
+            used[e] = true;
+            path.push(edges[e].0);
+            let back = angle(e) + PI;
+            let next = outgoing[&edges[e].1]
+                .iter()
+                .filter(|f| !used[**f] || **f == start)
+                .min_by_key(|f| {
+                    let clockwise = (back - angle(**f)).rem_euclid(2.0 * PI);
+                    OF(if clockwise <= 0.0 {
+                        2.0 * PI
+                    } else {
+                        clockwise
+                    })
+                });
+            match next {
+                Some(&f) if f != start => e = f,
+                _ => break,
+            }
+        }
+
+        let mut simple_paths = Vec::new();
+        let mut i = 0;
+        while i < path.len() {
+            if let Some(j) = path[..i].iter().position(|n| *n == path[i]) {
+                simple_paths.push(path.drain(j..i).collect_vec());
+                i = j;
+            }
+            i += 1;
+        }
+        simple_paths.push(path);
+        loops.extend(
+            simple_paths
+                .into_iter()
+                .filter_map(|path| clean_loop(nodes, path, tolerance)),
+        );
+    }
+    loops
+}
+
+/// The points of the loop, leaving out those in a line with their
+/// neighbors as pieces split from one segment are, or `None` if it has no
+/// area left.
+fn clean_loop(nodes: &[Point], path: Vec<usize>, tolerance: f64) -> Option<Vec<Point>> {
+    let mut points = path.into_iter().map(|n| nodes[n]).collect_vec();
+    let mut changed = true;
+    while changed && points.len() >= 3 {
+        changed = false;
+        let n = points.len();
+        if let Some(i) = (0..n).find(|i| {
+            let (p, v, q) = (points[(i + n - 1) % n], points[*i], points[(i + 1) % n]);
+            let length = (q.0 - p.0).hypot(q.1 - p.1);
+            length == 0.0 || cross(sub(v, p), sub(q, p)).abs() <= tolerance * length
+        }) {
+            points.remove(i);
+            changed = true;
+        }
+    }
+    (points.len() >= 3).then_some(points)
+}
+
+/// Signed area of a loop, positive when it is CCW.
+pub(crate) fn loop_area(points: &[Point]) -> f64 {
+    points
+        .iter()
+        .circular_tuple_windows()
+        .map(|(p, q)| cross(*p, *q))
+        .sum::<f64>()
+        / 2.0
+}
+
+/// Whether the point is inside the loop, by counting crossings of a ray.
+pub(crate) fn loop_contains(points: &[Point], p: Point) -> bool {
+    points
+        .iter()
+        .circular_tuple_windows()
+        .map(|(a, b)| ray_crossing(*a, *b, p))
+        .sum::<i32>()
+        != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::square_coords;
+    use crate::F64_ASSERT_PRECISION;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_figure_eight() {
+        // A bowtie crossing itself in the middle has one lobe going each
+        // way around
+        let mut overlay = Overlay::new(1);
+        overlay.add_loop(0, vec![(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)]);
+        let positive = overlay.boundary(|w| w[0] > 0);
+        assert_eq!(positive.len(), 1);
+        assert_approx_eq!(loop_area(&positive[0]), 1.0, F64_ASSERT_PRECISION);
+        assert!(loop_contains(&positive[0], (0.5, 1.0)));
+
+        let nonzero = overlay.boundary(|w| w[0] != 0);
+        assert_eq!(nonzero.len(), 2);
+        for points in nonzero.iter() {
+            assert_eq!(points.len(), 3);
+            assert_approx_eq!(loop_area(points), 1.0, F64_ASSERT_PRECISION);
+        }
+    }
+
+    #[test]
+    fn test_overlapping_squares() {
+        let mut overlay = Overlay::new(2);
+        overlay.add_loop(0, square_coords(0.0, 0.0, 2.0));
+        overlay.add_loop(1, square_coords(1.0, 1.0, 2.0));
+
+        let union = overlay.boundary(|w| w[0] > 0 || w[1] > 0);
+        assert_eq!(union.len(), 1);
+        assert_eq!(union[0].len(), 8);
+        assert_approx_eq!(loop_area(&union[0]), 7.0, F64_ASSERT_PRECISION);
+
+        let intersection = overlay.boundary(|w| w[0] > 0 && w[1] > 0);
+        assert_eq!(intersection.len(), 1);
+        assert_approx_eq!(loop_area(&intersection[0]), 1.0, F64_ASSERT_PRECISION);
+    }
+
+    #[test]
+    fn test_shared_edges_and_corners() {
+        // Squares side by side merge into a rectangle, and squares only
+        // meeting at a corner stay apart
+        let mut overlay = Overlay::new(1);
+        overlay.add_loop(0, square_coords(0.0, 0.0, 1.0));
+        overlay.add_loop(0, square_coords(1.0, 0.0, 1.0));
+        let merged = overlay.boundary(|w| w[0] > 0);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].len(), 4);
+        assert_approx_eq!(loop_area(&merged[0]), 2.0, F64_ASSERT_PRECISION);
+
+        let mut overlay = Overlay::new(1);
+        overlay.add_loop(0, square_coords(0.0, 0.0, 1.0));
+        overlay.add_loop(0, square_coords(1.0, 1.0, 1.0));
+        let apart = overlay.boundary(|w| w[0] > 0);
+        assert_eq!(apart.len(), 2);
+        assert!(apart.iter().all(|points| points.len() == 4));
+    }
+
+    #[test]
+    fn test_hole() {
+        let mut overlay = Overlay::new(2);
+        overlay.add_loop(0, square_coords(0.0, 0.0, 4.0));
+        overlay.add_loop(1, square_coords(1.0, 1.0, 2.0));
+        let difference = overlay.boundary(|w| w[0] > 0 && w[1] <= 0);
+        let areas = difference
+            .iter()
+            .map(|points| loop_area(points))
+            .sorted_by_key(|a| OF(*a))
+            .collect_vec();
+        assert_eq!(areas.len(), 2);
+        assert_approx_eq!(areas[0], -4.0, F64_ASSERT_PRECISION);
+        assert_approx_eq!(areas[1], 16.0, F64_ASSERT_PRECISION);
+    }
+}
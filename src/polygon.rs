@@ -4,18 +4,38 @@ use ordered_float::OrderedFloat as OF;
 use serde::Deserialize;
 use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
 use std::fs;
 use std::path::Path;
 
 use crate::{
     bounding_box::BoundingBox,
-    error::FileError,
+    error::{FileError, OffsetError},
     geometry::Geometry,
     line_segment::LineSegment,
+    overlay::{loop_area, loop_contains, Overlay},
+    predicates::orientation,
     triangle::Triangle,
+    vector::Vector,
     vertex::{Vertex, VertexId},
 };
 
+/// Largest angle in radians between consecutive points of a round join.
+const ROUND_JOIN_STEP: f64 = PI / 16.0;
+
+/// How the offset edges at a corner are joined where they move apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OffsetJoin {
+    /// Extends the edges until they meet, unless the point where they meet
+    /// is more than the limit times the offset distance from the corner,
+    /// in which case the corner is beveled.
+    Miter(f64),
+    /// An arc around the corner with the offset distance as its radius.
+    Round,
+    /// A straight cut across from one edge to the other.
+    Bevel,
+}
+
 #[derive(Deserialize)]
 pub struct PolygonMetadata {
     pub area: f64,
@@ -256,6 +276,221 @@ impl Polygon {
         }
     }
 
+    pub fn is_reflex(&self, id: &VertexId) -> bool {
+        let prev = self.prev_vertex_id(id).unwrap();
+        let next = self.next_vertex_id(id).unwrap();
+        self.get_triangle(&prev, id, &next).unwrap().area() < 0.0
+    }
+
+    pub fn reflex_vertices(&self) -> Vec<VertexId> {
+        self.vertex_ids()
+            .into_iter()
+            .filter(|id| self.is_reflex(id))
+            .collect_vec()
+    }
+
+    /// A polygon is convex if it has no reflex vertices, where collinear
+    /// vertices are not considered reflex.
+    pub fn is_convex(&self) -> bool {
+        self.reflex_vertices().is_empty()
+    }
+
+    /// Whether the polygon is monotone with respect to the direction, so
+    /// that every line perpendicular to it meets the polygon in a connected
+    /// set. Going around the boundary then only turns back once at each end
+    /// of the polygon along the direction, ignoring edges perpendicular to
+    /// it, which is checked in $O(n)$.
+    pub fn is_monotone(&self, direction: &Vector) -> bool {
+        let forwards = self
+            .vertices()
+            .into_iter()
+            .circular_tuple_windows()
+            .map(|(a, b)| Vector::from(&LineSegment::from_vertices(a, b)).dot(direction))
+            .filter(|d| *d != 0.0)
+            .map(|d| d > 0.0)
+            .collect_vec();
+        let num_turns = forwards
+            .iter()
+            .circular_tuple_windows()
+            .filter(|(a, b)| a != b)
+            .count();
+        num_turns <= 2
+    }
+
+    /// The kernel of the polygon, which is the region from which all of it
+    /// is visible, or `None` when it is empty or has no area. It is the
+    /// intersection of the half-planes to the left of every edge, found with
+    /// Lee and Preparata's algorithm in $O(n)$.
+    ///
+    /// The edges are taken in order around the boundary, cutting down a
+    /// convex region that starts out as the bounding box. The region is kept
+    /// with the two vertices where the lines from the current polygon vertex
+    /// touch it. Each edge line goes through that polygon vertex, so only
+    /// one of the two can be on the far side of it, and if neither is then
+    /// nothing is cut. Both touching vertices only move forwards around the
+    /// region as the polygon goes around it, which they do at most twice
+    /// before the boundary has turned too far for the polygon to be
+    /// star-shaped.
+    pub fn kernel(&self) -> Option<Polygon> {
+        let vertex = |x, y| Vertex::new(VertexId::default(), x, y);
+        let bbox = self.bounding_box();
+        let mut region = KernelRegion::new(vec![
+            vertex(bbox.min_x, bbox.min_y),
+            vertex(bbox.max_x, bbox.min_y),
+            vertex(bbox.max_x, bbox.max_y),
+            vertex(bbox.min_x, bbox.max_y),
+        ]);
+
+        // Cutting with the edge into the first vertex puts that vertex on
+        // the boundary of the region, outside of it or touching it
+        let vertices = self.vertices();
+        let n = vertices.len();
+        let (a, b) = (vertices[n - 1], vertices[0]);
+        if let Some(seed) = (0..4).find(|&i| orientation(a, b, &region.vertices[i]) < 0.0) {
+            region.cut(a, b, seed)?;
+        }
+        let start = region.any();
+        let mut first = region.first_tangent(b, start);
+        let mut last = region.last_tangent(b, start);
+
+        let mut turning = 0.0;
+        for i in 0..n - 1 {
+            let (u, v, w) = (vertices[(i + n - 1) % n], vertices[i], vertices[i + 1]);
+            let (dx1, dy1, dx2, dy2) = (v.x - u.x, v.y - u.y, w.x - v.x, w.y - v.y);
+            turning += (dx1 * dy2 - dy1 * dx2).atan2(dx1 * dx2 + dy1 * dy2);
+            if turning >= 3.0 * PI {
+                debug!(
+                    "Boundary turns too far at vertex {} to be star-shaped",
+                    v.id
+                );
+                return None;
+            }
+
+            let seed = [first, last]
+                .into_iter()
+                .find(|&j| orientation(v, w, &region.vertices[j]) < 0.0);
+            if let Some(seed) = seed {
+                let Some(cut) = region.cut(v, w, seed) else {
+                    debug!(
+                        "Kernel is empty after cutting with edge ({}, {})",
+                        v.id, w.id
+                    );
+                    return None;
+                };
+                if !region.alive[first] {
+                    first = cut;
+                }
+                if !region.alive[last] {
+                    last = cut;
+                }
+            }
+            first = region.first_tangent(w, first);
+            last = region.last_tangent(w, last);
+        }
+
+        // Rounding the points where edges are cut can leave collinear points
+        let kernel = region.vertices();
+        let corners = kernel
+            .iter()
+            .circular_tuple_windows()
+            .filter(|(p, v, q)| orientation(p, v, q) != 0.0)
+            .map(|(_, v, _)| v.coords())
+            .collect_vec();
+        if corners.len() < 3 {
+            return None;
+        }
+        Some(Polygon::from_coords(corners))
+    }
+
+    /// A polygon is star-shaped if there is some point inside it that sees
+    /// all of it, meaning the polygon has a kernel. A kernel that shrinks to
+    /// a segment or a point is not counted.
+    pub fn is_star_shaped(&self) -> bool {
+        self.kernel().is_some()
+    }
+
+    /// The polygons covering the points within the distance of this one
+    /// for a positive distance, or the points at least that deep inside it
+    /// for a negative distance, sorted by decreasing area. Eroding
+    /// the polygon can split it into several polygons or leave nothing.
+    ///
+    /// Each edge is moved out by the distance and the corners where the
+    /// edges move apart are filled in with the join. Where they move
+    /// together the ends are connected through the old corner, and the
+    /// loops this leaves along with any other overlaps of the offset curve
+    /// are cleaned up by keeping only the points it winds around. Growing
+    /// the polygon can enclose gaps, which are filled in since a polygon
+    /// has no holes, so the grown polygon then also covers points farther
+    /// than the distance from this one. The distance has to be finite.
+    pub fn offset(&self, distance: f64, join: OffsetJoin) -> Result<Vec<Polygon>, OffsetError> {
+        if !distance.is_finite() {
+            return Err(OffsetError::InvalidDistance(distance));
+        }
+        if distance == 0.0 {
+            return Ok(vec![self.clone()]);
+        }
+        let normal = |a: &Vertex, b: &Vertex| {
+            let length = a.distance_to(b);
+            ((b.y - a.y) / length, (a.x - b.x) / length)
+        };
+        let mut curve = Vec::new();
+        for (prev, v, next) in self.vertices().into_iter().circular_tuple_windows() {
+            let (n_in, n_out) = (normal(prev, v), normal(v, next));
+            let at = |n: (f64, f64)| (v.x + distance * n.0, v.y + distance * n.1);
+            let turn = n_in.0 * n_out.1 - n_in.1 * n_out.0;
+            let cos = n_in.0 * n_out.0 + n_in.1 * n_out.1;
+            if n_in == n_out {
+                curve.push(at(n_in));
+                continue;
+            }
+            if turn * distance <= 0.0 {
+                curve.extend([at(n_in), v.coords(), at(n_out)]);
+                continue;
+            }
+            match join {
+                OffsetJoin::Miter(limit) if (2.0 / (1.0 + cos)).sqrt() <= limit => {
+                    let scale = 1.0 / (1.0 + cos);
+                    curve.push(at(((n_in.0 + n_out.0) * scale, (n_in.1 + n_out.1) * scale)));
+                }
+                OffsetJoin::Round => {
+                    let angle = turn.atan2(cos);
+                    let num_steps = (angle.abs() / ROUND_JOIN_STEP).ceil() as usize;
+                    curve.extend((0..=num_steps).map(|i| {
+                        let (sin, cos) = (angle * i as f64 / num_steps as f64).sin_cos();
+                        at((n_in.0 * cos - n_in.1 * sin, n_in.0 * sin + n_in.1 * cos))
+                    }));
+                }
+                _ => curve.extend([at(n_in), at(n_out)]),
+            }
+        }
+
+        let mut overlay = Overlay::new(1);
+        overlay.add_loop(0, curve);
+        let loops = overlay
+            .boundary(|w| w[0] > 0)
+            .into_iter()
+            .filter(|points| loop_area(points) > 0.0)
+            .collect_vec();
+        // Loops inside others were in gaps that are now filled
+        let outer = loops
+            .iter()
+            .enumerate()
+            .filter(|(i, points)| {
+                !loops
+                    .iter()
+                    .enumerate()
+                    .any(|(j, other)| *i != j && loop_contains(other, points[0]))
+            })
+            .map(|(_, points)| points.clone())
+            .sorted_by_key(|points| Reverse(OF(loop_area(points))))
+            .collect_vec();
+        debug!(
+            "Offset by {distance} with {join:?} joins gives {} polygons",
+            outer.len()
+        );
+        Ok(outer.into_iter().map(Polygon::from_coords).collect_vec())
+    }
+
     pub fn get_vertex_mut(&mut self, id: &VertexId) -> Option<&mut Vertex> {
         self.vertex_map.get_mut(id)
     }
@@ -338,9 +573,11 @@ impl Polygon {
 
     fn diagonal_internal_external(&self, a: &Vertex, b: &Vertex) -> bool {
         let ab = &LineSegment::from_vertices(a, b);
-        for (id1, id2) in self.edges() {
-            // TODO instead of unwrap, return result with error
-            let e = self.get_line_segment(&id1, &id2).unwrap();
+        // Walking the boundary directly rather than going through the edge
+        // set matters here since this is called O(n^2) times by the
+        // dynamic programming algorithms
+        for (v1, v2) in self.vertices().into_iter().circular_tuple_windows() {
+            let e = LineSegment::from_vertices(v1, v2);
             if !e.connected_to(ab) && e.intersects(ab) {
                 return false;
             }
@@ -348,6 +585,51 @@ impl Polygon {
         true
     }
 
+    /// Whether every point on the segment between two points in the polygon
+    /// is also in the polygon, so that the points can see each other. The
+    /// segment may touch or run along the boundary but not leave it.
+    pub fn sees(&self, a: &Vertex, b: &Vertex) -> bool {
+        let ab = LineSegment::from_vertices(a, b);
+        let vertices = self.vertices();
+        let edges = vertices
+            .iter()
+            .copied()
+            .circular_tuple_windows()
+            .map(|(v1, v2)| LineSegment::from_vertices(v1, v2))
+            .collect_vec();
+        if edges.iter().any(|e| e.proper_intersects(&ab)) {
+            return false;
+        }
+
+        // Splitting the segment at the vertices it passes through leaves
+        // pieces that are each either along an edge, or entirely inside or
+        // outside of the polygon
+        let mut points = vec![a, b];
+        points.extend(vertices.into_iter().filter(|v| v.between(a, b)));
+        points.sort_by_key(|v| OF(v.distance_to(a)));
+        points.dedup_by(|p, q| p.coords() == q.coords());
+        let on_edge = |v: &Vertex, e: &LineSegment| e.incident_to(v) || v.between(e.v1, e.v2);
+        points.into_iter().tuple_windows().all(|(p, q)| {
+            let mid = Vertex::new(VertexId::default(), (p.x + q.x) / 2.0, (p.y + q.y) / 2.0);
+            edges.iter().any(|e| on_edge(p, e) && on_edge(q, e)) || self.contains(&mid)
+        })
+    }
+
+    /// Whether the point is inside the polygon, by counting how many edges
+    /// a ray cast from it in the +x direction crosses. Points exactly on
+    /// the boundary may be reported either way.
+    pub fn contains(&self, v: &Vertex) -> bool {
+        let mut inside = false;
+        for (id1, id2) in self.edges() {
+            let a = self.get_vertex(&id1).unwrap();
+            let b = self.get_vertex(&id2).unwrap();
+            if (a.y > v.y) != (b.y > v.y) && v.x < a.x + (v.y - a.y) * (b.x - a.x) / (b.y - a.y) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
     pub fn bounding_box(&self) -> BoundingBox {
         BoundingBox::new(self.min_x(), self.max_x(), self.min_y(), self.max_y())
     }
@@ -461,6 +743,213 @@ impl Polygon {
     }
 }
 
+/// A convex region kept as a circular linked list of its vertices in CCW
+/// order, which the kernel is cut down from one half-plane at a time. Each
+/// vertex also keeps the polygon edge that the region edge after it lies
+/// along, if any, so that cuts through the ends of that edge land on the
+/// polygon vertex itself rather than a rounded point next to it.
+struct KernelRegion {
+    vertices: Vec<Vertex>,
+    edges: Vec<Option<(VertexId, VertexId)>>,
+    next: Vec<usize>,
+    prev: Vec<usize>,
+    alive: Vec<bool>,
+    len: usize,
+}
+
+impl KernelRegion {
+    fn new(vertices: Vec<Vertex>) -> Self {
+        let n = vertices.len();
+        KernelRegion {
+            vertices,
+            edges: vec![None; n],
+            next: (0..n).map(|i| (i + 1) % n).collect(),
+            prev: (0..n).map(|i| (i + n - 1) % n).collect(),
+            alive: vec![true; n],
+            len: n,
+        }
+    }
+
+    fn any(&self) -> usize {
+        self.alive.iter().position(|&alive| alive).unwrap()
+    }
+
+    fn vertices(&self) -> Vec<Vertex> {
+        let start = self.any();
+        let mut vertices = vec![self.vertices[start].clone()];
+        let mut i = self.next[start];
+        while i != start {
+            vertices.push(self.vertices[i].clone());
+            i = self.next[i];
+        }
+        vertices
+    }
+
+    /// Cuts away the part of the region to the right of the polygon edge
+    /// from `a` to `b`, given a vertex `seed` in that part, in time linear
+    /// in the number of vertices cut away. Returns the first vertex of the
+    /// region on the edge line, or `None` when the region has no area left.
+    fn cut(&mut self, a: &Vertex, b: &Vertex, seed: usize) -> Option<usize> {
+        let side = |region: &Self, i: usize| orientation(a, b, &region.vertices[i]);
+        let mut first = seed;
+        while side(self, self.prev[first]) < 0.0 {
+            first = self.prev[first];
+            if first == seed {
+                return None;
+            }
+        }
+        let mut last = seed;
+        while side(self, self.next[last]) < 0.0 {
+            last = self.next[last];
+        }
+        let (p, q) = (self.prev[first], self.next[last]);
+
+        let mut i = first;
+        loop {
+            self.alive[i] = false;
+            self.len -= 1;
+            if i == last {
+                break;
+            }
+            i = self.next[i];
+        }
+        let (a, b) = (a.clone(), b.clone());
+        let enter = self.crossing(p, first, side(self, p), side(self, first), &a, &b);
+        let exit = self.crossing(q, last, side(self, q), side(self, last), &a, &b);
+        if exit != q {
+            self.edges[exit] = self.edges[last];
+        }
+        self.edges[enter] = Some((a.id, b.id));
+        self.link(p, enter);
+        self.link(exit, q);
+
+        // The next polygon vertex is put on the cut as it is when it falls
+        // on it, since the rounded crossings can leave it just inside
+        let (e, x) = (&self.vertices[enter], &self.vertices[exit]);
+        let (dx, dy) = (x.x - e.x, x.y - e.y);
+        if (b.x - e.x) * dx + (b.y - e.y) * dy > 0.0 && (x.x - b.x) * dx + (x.y - b.y) * dy > 0.0 {
+            let on_cut = self.push(b.clone());
+            self.edges[on_cut] = Some((a.id, b.id));
+            self.link(enter, on_cut);
+            self.link(on_cut, exit);
+        } else {
+            self.link(enter, exit);
+        }
+        if self.len < 3 {
+            return None;
+        }
+        Some(enter)
+    }
+
+    /// The point on the cut where the edge between a vertex `p` left of it
+    /// and a vertex `r` right of it crosses, which is `p` when it is on the
+    /// cut and a polygon vertex when the edge runs along the polygon edge
+    /// next to the cut.
+    fn crossing(&mut self, p: usize, r: usize, op: f64, or: f64, a: &Vertex, b: &Vertex) -> usize {
+        if op == 0.0 {
+            return p;
+        }
+        let edge = if self.next[p] == r {
+            self.edges[p]
+        } else {
+            self.edges[r]
+        };
+        let (p, r) = (&self.vertices[p], &self.vertices[r]);
+        let crossing = match edge {
+            Some((_, end)) if end == a.id => a.clone(),
+            Some((start, _)) if start == b.id => b.clone(),
+            _ if a.between(p, r) => a.clone(),
+            _ if b.between(p, r) => b.clone(),
+            _ => {
+                let t = op / (op - or);
+                Vertex::new(
+                    VertexId::default(),
+                    p.x + t * (r.x - p.x),
+                    p.y + t * (r.y - p.y),
+                )
+            }
+        };
+        self.push(crossing)
+    }
+
+    fn push(&mut self, v: Vertex) -> usize {
+        self.vertices.push(v);
+        self.edges.push(None);
+        self.next.push(0);
+        self.prev.push(0);
+        self.alive.push(true);
+        self.len += 1;
+        self.vertices.len() - 1
+    }
+
+    fn link(&mut self, i: usize, j: usize) {
+        if i != j {
+            self.next[i] = j;
+            self.prev[j] = i;
+        }
+    }
+
+    /// The vertex at which the line from `v` touches the region with all of
+    /// it to the left, searching forwards from `start`.
+    fn first_tangent(&self, v: &Vertex, start: usize) -> usize {
+        self.tangent(v, start, |o| o >= 0.0)
+    }
+
+    /// The vertex at which the line from `v` touches the region with all of
+    /// it to the right, searching forwards from `start`.
+    fn last_tangent(&self, v: &Vertex, start: usize) -> usize {
+        self.tangent(v, start, |o| o <= 0.0)
+    }
+
+    fn tangent(&self, v: &Vertex, start: usize, keeps: impl Fn(f64) -> bool) -> usize {
+        let mut i = start;
+        for _ in 0..self.len {
+            let u = &self.vertices[i];
+            if u.coords() != v.coords()
+                && keeps(orientation(v, u, &self.vertices[self.prev[i]]))
+                && keeps(orientation(v, u, &self.vertices[self.next[i]]))
+            {
+                return i;
+            }
+            i = self.next[i];
+        }
+        debug!("No tangent from {v:?} to the kernel region");
+        start
+    }
+}
+
+/// A polygon with holes in it. The holes are polygons like any other, so
+/// they go CCW, and they are inside the outer polygon and apart from each
+/// other other than touching at vertices.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolygonWithHoles {
+    pub outer: Polygon,
+    pub holes: Vec<Polygon>,
+}
+
+impl PolygonWithHoles {
+    pub fn new(outer: Polygon, holes: Vec<Polygon>) -> Self {
+        PolygonWithHoles { outer, holes }
+    }
+
+    /// Area of the outer polygon less the area of the holes.
+    pub fn area(&self) -> f64 {
+        self.outer.area() - self.holes.iter().map(|h| h.area()).sum::<f64>()
+    }
+
+    /// Whether the point is inside the outer polygon and not in a hole.
+    /// Points exactly on the boundary may be reported either way.
+    pub fn contains(&self, v: &Vertex) -> bool {
+        self.outer.contains(v) && !self.holes.iter().any(|h| h.contains(v))
+    }
+}
+
+impl From<Polygon> for PolygonWithHoles {
+    fn from(polygon: Polygon) -> Self {
+        PolygonWithHoles::new(polygon, Vec::new())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::test_util::*;
@@ -495,6 +984,317 @@ mod tests {
         assert_eq!(case.polygon, new_polygon);
     }
 
+    #[test]
+    fn test_contains() {
+        let coords = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (2.0, 1.0), (0.0, 4.0)];
+        let polygon = Polygon::from_coords(coords);
+        let vertex = |x, y| Vertex::new(VertexId::default(), x, y);
+        assert!(polygon.contains(&vertex(1.0, 0.5)));
+        assert!(polygon.contains(&vertex(3.5, 3.0)));
+        assert!(!polygon.contains(&vertex(2.0, 3.0)));
+        assert!(!polygon.contains(&vertex(5.0, 1.0)));
+        assert!(!polygon.contains(&vertex(-1.0, 0.5)));
+    }
+
+    #[test]
+    fn test_sees() {
+        let coords = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (2.0, 1.0), (0.0, 4.0)];
+        let polygon = Polygon::from_coords(coords);
+        let vertex = |x, y| Vertex::new(VertexId::default(), x, y);
+        let v = |id: usize| polygon.get_vertex(&VertexId::from(id)).unwrap();
+        // Along an edge, through the interior and grazing the reflex vertex
+        assert!(polygon.sees(v(0), v(1)));
+        assert!(polygon.sees(v(0), v(3)));
+        assert!(polygon.sees(&vertex(0.0, 2.0), &vertex(4.0, 0.0)));
+        assert!(polygon.sees(&vertex(1.0, 0.0), &vertex(3.0, 2.0)));
+        // Across the notch, either outside of it entirely or crossing edges
+        assert!(!polygon.sees(v(2), v(4)));
+        assert!(!polygon.sees(v(0), v(2)));
+        assert!(!polygon.sees(v(1), v(4)));
+        assert!(!polygon.sees(&vertex(0.5, 3.0), &vertex(3.5, 3.0)));
+    }
+
+    #[test]
+    fn test_is_monotone() {
+        let coords = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (2.0, 1.0), (0.0, 4.0)];
+        let polygon = Polygon::from_coords(coords);
+        assert!(polygon.is_monotone(&Vector::new(1.0, 0.0)));
+        assert!(polygon.is_monotone(&Vector::new(-1.0, 0.0)));
+        assert!(!polygon.is_monotone(&Vector::new(0.0, 1.0)));
+        assert!(!polygon.is_monotone(&Vector::new(1.0, 1.0)));
+
+        // Horizontal edges don't count as turning back along the y-axis
+        let coords = vec![
+            (0.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 4.0),
+            (1.0, 4.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+        ];
+        let polygon = Polygon::from_coords(coords);
+        assert!(polygon.is_monotone(&Vector::new(0.0, 1.0)));
+        assert!(polygon.is_monotone(&Vector::new(1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_kernel() {
+        // The notch cuts the kernel down to the triangle under it
+        let coords = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (2.0, 1.0), (0.0, 4.0)];
+        let polygon = Polygon::from_coords(coords);
+        let kernel = polygon.kernel().unwrap();
+        assert!(kernel.is_convex());
+        assert_approx_eq!(kernel.area(), 2.0 / 3.0, F64_ASSERT_PRECISION);
+        assert!(polygon.is_star_shaped());
+
+        // A comb has no point that sees into every tooth
+        let coords = vec![
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (9.0, 10.0),
+            (8.0, 2.0),
+            (6.0, 2.0),
+            (5.0, 10.0),
+            (4.0, 10.0),
+            (3.0, 2.0),
+            (1.0, 2.0),
+            (0.0, 10.0),
+        ];
+        let polygon = Polygon::from_coords(coords);
+        assert!(polygon.kernel().is_none());
+        assert!(!polygon.is_star_shaped());
+    }
+
+    #[apply(all_polygons)]
+    fn test_kernel_sees_polygon(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        let Some(kernel) = polygon.kernel() else {
+            assert!(!polygon.is_convex());
+            return;
+        };
+        assert!(kernel.is_convex());
+        assert!(kernel.area() <= polygon.area() + F64_ASSERT_PRECISION);
+        if polygon.is_convex() {
+            assert_approx_eq!(kernel.area(), polygon.area(), F64_ASSERT_PRECISION);
+        }
+
+        // A point inside the kernel sees every vertex
+        let n = kernel.num_vertices() as f64;
+        let (x, y) = kernel
+            .vertices()
+            .iter()
+            .fold((0.0, 0.0), |(x, y), v| (x + v.x / n, y + v.y / n));
+        let center = Vertex::new(VertexId::default(), x, y);
+        assert!(polygon.contains(&center));
+        for v in polygon.vertices() {
+            assert!(polygon.sees(&center, v));
+        }
+    }
+
+    /// Area of the kernel found by clipping the bounding box with every
+    /// edge in turn, or zero when nothing is left.
+    fn clipped_kernel_area(polygon: &Polygon) -> f64 {
+        let vertex = |x, y| Vertex::new(VertexId::default(), x, y);
+        let bbox = polygon.bounding_box();
+        let mut kernel = vec![
+            vertex(bbox.min_x, bbox.min_y),
+            vertex(bbox.max_x, bbox.min_y),
+            vertex(bbox.max_x, bbox.max_y),
+            vertex(bbox.min_x, bbox.max_y),
+        ];
+        for (a, b) in polygon.vertices().into_iter().circular_tuple_windows() {
+            let mut clipped = Vec::new();
+            for (p, q) in kernel.iter().circular_tuple_windows() {
+                let (op, oq) = (orientation(a, b, p), orientation(a, b, q));
+                if op >= 0.0 {
+                    clipped.push(p.clone());
+                }
+                if (op > 0.0 && oq < 0.0) || (op < 0.0 && oq > 0.0) {
+                    let t = op / (op - oq);
+                    clipped.push(vertex(p.x + t * (q.x - p.x), p.y + t * (q.y - p.y)));
+                }
+            }
+            kernel = clipped;
+        }
+        let coords = kernel.iter().map(|v| v.coords()).collect_vec();
+        loop_area(&coords)
+    }
+
+    #[apply(all_polygons)]
+    fn test_kernel_matches_clipping(case: PolygonTestCase) {
+        let area = case.polygon.kernel().map_or(0.0, |k| k.area());
+        assert_approx_eq!(
+            area,
+            clipped_kernel_area(&case.polygon),
+            F64_ASSERT_PRECISION
+        );
+    }
+
+    #[rstest]
+    fn test_kernel_star_polygons(#[values(3, 4, 5, 10, 100, 1000)] num_vertices: usize) {
+        // Vertices at evenly spaced angles around the origin with
+        // deterministic radii
+        let mut random = seeded_random(num_vertices as u64);
+        let coords = (0..num_vertices)
+            .map(|i| {
+                let angle = 2.0 * PI * i as f64 / num_vertices as f64;
+                let radius = 1.0 + random(16) as f64;
+                (radius * angle.cos(), radius * angle.sin())
+            })
+            .collect_vec();
+        let polygon = Polygon::from_coords(coords);
+        let kernel = polygon.kernel().unwrap();
+        assert!(kernel.is_convex());
+        assert!(kernel.contains(&Vertex::new(VertexId::default(), 0.0, 0.0)));
+        assert_approx_eq!(
+            kernel.area(),
+            clipped_kernel_area(&polygon),
+            F64_ASSERT_PRECISION
+        );
+    }
+
+    fn boundary_distance(polygon: &Polygon, p: &Vertex) -> f64 {
+        polygon
+            .vertices()
+            .into_iter()
+            .circular_tuple_windows()
+            .map(|(a, b)| {
+                let (dx, dy) = (b.x - a.x, b.y - a.y);
+                let t = ((p.x - a.x) * dx + (p.y - a.y) * dy) / (dx * dx + dy * dy);
+                let t = t.clamp(0.0, 1.0);
+                (p.x - a.x - t * dx).hypot(p.y - a.y - t * dy)
+            })
+            .min_by(f64::total_cmp)
+            .unwrap()
+    }
+
+    #[apply(all_polygons)]
+    fn test_offset(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        let bbox = polygon.bounding_box();
+        let size = (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+        let distance = 0.02 * size;
+        let tolerance = 1e-6 * size;
+
+        for join in [OffsetJoin::Miter(2.0), OffsetJoin::Round, OffsetJoin::Bevel] {
+            let grown = polygon.offset(distance, join).unwrap();
+            assert_eq!(grown.len(), 1);
+            assert!(grown[0].area() > polygon.area());
+            for v in polygon.vertices() {
+                assert!(grown[0].contains(v));
+            }
+
+            let eroded = polygon.offset(-distance, join).unwrap();
+            let area: f64 = eroded.iter().map(|p| p.area()).sum();
+            assert!(area < polygon.area());
+            for v in eroded.iter().flat_map(|p| p.vertices()) {
+                assert!(polygon.contains(v));
+            }
+
+            // Round joins keep the offset curve the same distance from the
+            // polygon, other than cutting across the arcs
+            if join == OffsetJoin::Round {
+                let min_distance = distance * (ROUND_JOIN_STEP / 2.0).cos() - tolerance;
+                for v in grown.iter().chain(eroded.iter()).flat_map(|p| p.vertices()) {
+                    let d = boundary_distance(polygon, v);
+                    assert!(d >= min_distance && d <= distance + tolerance, "{v:?} {d}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_offset_joins() {
+        let square = Polygon::from_coords(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]);
+        let grown = square.offset(1.0, OffsetJoin::Miter(2.0)).unwrap();
+        assert_eq!(grown[0].num_vertices(), 4);
+        assert_approx_eq!(grown[0].area(), 36.0, F64_ASSERT_PRECISION);
+        let grown = square.offset(1.0, OffsetJoin::Bevel).unwrap();
+        assert_eq!(grown[0].num_vertices(), 8);
+        assert_approx_eq!(grown[0].area(), 34.0, F64_ASSERT_PRECISION);
+        let grown = square.offset(1.0, OffsetJoin::Round).unwrap();
+        assert!((grown[0].area() - (32.0 + PI)).abs() < 0.05);
+
+        for join in [OffsetJoin::Miter(2.0), OffsetJoin::Round, OffsetJoin::Bevel] {
+            let shrunk = square.offset(-1.0, join).unwrap();
+            assert_eq!(shrunk.len(), 1);
+            assert_eq!(shrunk[0].num_vertices(), 4);
+            assert_approx_eq!(shrunk[0].area(), 4.0, F64_ASSERT_PRECISION);
+            assert!(square.offset(-2.5, join).unwrap().is_empty());
+        }
+
+        // The sharp corner goes past the miter limit unless it is large
+        let sliver = Polygon::from_coords(vec![(0.0, 0.0), (10.0, 0.0), (0.0, 1.0)]);
+        assert_eq!(
+            sliver.offset(1.0, OffsetJoin::Miter(4.0)).unwrap()[0].num_vertices(),
+            4
+        );
+        assert_eq!(
+            sliver.offset(1.0, OffsetJoin::Miter(100.0)).unwrap()[0].num_vertices(),
+            3
+        );
+    }
+
+    #[rstest]
+    fn test_offset_invalid_distance(
+        #[values(f64::NAN, f64::INFINITY, f64::NEG_INFINITY)] distance: f64,
+    ) {
+        let square = Polygon::from_coords(square_coords(0.0, 0.0, 4.0));
+        assert!(matches!(
+            square.offset(distance, OffsetJoin::Round),
+            Err(OffsetError::InvalidDistance(_))
+        ));
+    }
+
+    #[test]
+    fn test_offset_split_and_fill() {
+        // Eroding two squares joined by a thin corridor pulls them apart
+        let coords = vec![
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 1.5),
+            (6.0, 1.5),
+            (6.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 4.0),
+            (6.0, 4.0),
+            (6.0, 2.5),
+            (4.0, 2.5),
+            (4.0, 4.0),
+            (0.0, 4.0),
+        ];
+        let dumbbell = Polygon::from_coords(coords);
+        let pieces = dumbbell.offset(-1.0, OffsetJoin::Miter(2.0)).unwrap();
+        assert_eq!(pieces.len(), 2);
+        for piece in pieces {
+            assert_eq!(piece.num_vertices(), 4);
+            assert_approx_eq!(piece.area(), 4.0, F64_ASSERT_PRECISION);
+        }
+
+        // Growing a ring with a narrow slot closes the slot, and the room
+        // inside is filled in
+        let coords = vec![
+            (0.0, 0.0),
+            (6.0, 0.0),
+            (6.0, 6.0),
+            (3.25, 6.0),
+            (3.25, 5.0),
+            (5.0, 5.0),
+            (5.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 5.0),
+            (2.75, 5.0),
+            (2.75, 6.0),
+            (0.0, 6.0),
+        ];
+        let ring = Polygon::from_coords(coords);
+        let grown = ring.offset(0.5, OffsetJoin::Miter(2.0)).unwrap();
+        assert_eq!(grown.len(), 1);
+        assert_eq!(grown[0].num_vertices(), 4);
+        assert_approx_eq!(grown[0].area(), 49.0, F64_ASSERT_PRECISION);
+    }
+
     #[test]
     // TODO could expand this test to polygon cases
     fn test_min_max() {
@@ -528,6 +1328,37 @@ mod tests {
         assert_eq!(lowest.y, -3.0);
     }
 
+    #[test]
+    fn test_reflex_vertices() {
+        let coords = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (2.0, 2.0), (0.0, 4.0)];
+        let polygon = Polygon::from_coords(coords);
+        assert_eq!(polygon.reflex_vertices(), vec![VertexId::from(3usize)]);
+        assert!(!polygon.is_convex());
+    }
+
+    #[test]
+    fn test_is_convex_collinear() {
+        let coords = vec![(0.0, 0.0), (2.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)];
+        let polygon = Polygon::from_coords(coords);
+        assert!(polygon.reflex_vertices().is_empty());
+        assert!(polygon.is_convex());
+    }
+
+    #[apply(all_polygons)]
+    fn test_convex_hull_is_convex(case: PolygonTestCase) {
+        let hull = case
+            .polygon
+            .get_polygon(case.metadata.extreme_points.clone(), true, false);
+        assert!(hull.is_convex());
+        for (x, y) in [(1.0, 0.0), (0.0, 1.0), (0.3, -0.7)] {
+            assert!(hull.is_monotone(&Vector::new(x, y)));
+        }
+        assert_eq!(
+            case.polygon.is_convex(),
+            case.metadata.extreme_points.len() == case.metadata.num_vertices
+        );
+    }
+
     #[apply(all_polygons)]
     fn test_area(case: PolygonTestCase) {
         let area = case.polygon.area();
@@ -0,0 +1,170 @@
+//! Robust geometric predicates following Shewchuk's "Adaptive Precision
+//! Floating-Point Arithmetic and Fast Robust Geometric Predicates". Each
+//! predicate is first evaluated in plain floating point, and only when the
+//! result is too close to zero to trust its sign is it recomputed exactly
+//! with floating-point expansion arithmetic.
+
+use crate::vertex::Vertex;
+
+const EPSILON: f64 = f64::EPSILON / 2.0;
+const ORIENTATION_ERROR_BOUND: f64 = (3.0 + 16.0 * EPSILON) * EPSILON;
+const INCIRCLE_ERROR_BOUND: f64 = (10.0 + 96.0 * EPSILON) * EPSILON;
+
+/// Positive if `c` is to the left of the line from `a` to `b`, negative if
+/// it is to the right and zero if the points are collinear. The sign is
+/// always exact, the magnitude is approximately twice the signed area of
+/// the triangle.
+pub fn orientation(a: &Vertex, b: &Vertex, c: &Vertex) -> f64 {
+    let left = (a.x - c.x) * (b.y - c.y);
+    let right = (a.y - c.y) * (b.x - c.x);
+    let det = left - right;
+    let bound = ORIENTATION_ERROR_BOUND * (left.abs() + right.abs());
+    if det.abs() > bound {
+        return det;
+    }
+
+    let acx = diff(a.x, c.x);
+    let bcy = diff(b.y, c.y);
+    let acy = diff(a.y, c.y);
+    let bcx = diff(b.x, c.x);
+    estimate(&sum(&product(&acx, &bcy), &negate(&product(&acy, &bcx))))
+}
+
+/// Positive if `d` is strictly inside the circle through `a`, `b` and `c`,
+/// negative if it is strictly outside and zero if the four points are
+/// cocircular, where `a`, `b` and `c` must be in CCW order. The sign is
+/// reversed if they are in CW order. The sign is always exact.
+pub fn incircle(a: &Vertex, b: &Vertex, c: &Vertex, d: &Vertex) -> f64 {
+    let (adx, ady) = (a.x - d.x, a.y - d.y);
+    let (bdx, bdy) = (b.x - d.x, b.y - d.y);
+    let (cdx, cdy) = (c.x - d.x, c.y - d.y);
+
+    let alift = adx * adx + ady * ady;
+    let blift = bdx * bdx + bdy * bdy;
+    let clift = cdx * cdx + cdy * cdy;
+    let (bdxcdy, cdxbdy) = (bdx * cdy, cdx * bdy);
+    let (cdxady, adxcdy) = (cdx * ady, adx * cdy);
+    let (adxbdy, bdxady) = (adx * bdy, bdx * ady);
+
+    let det = alift * (bdxcdy - cdxbdy) + blift * (cdxady - adxcdy) + clift * (adxbdy - bdxady);
+    let permanent = (bdxcdy.abs() + cdxbdy.abs()) * alift
+        + (cdxady.abs() + adxcdy.abs()) * blift
+        + (adxbdy.abs() + bdxady.abs()) * clift;
+    if det.abs() > INCIRCLE_ERROR_BOUND * permanent {
+        return det;
+    }
+
+    let (adx, ady) = (diff(a.x, d.x), diff(a.y, d.y));
+    let (bdx, bdy) = (diff(b.x, d.x), diff(b.y, d.y));
+    let (cdx, cdy) = (diff(c.x, d.x), diff(c.y, d.y));
+    let lift = |x: &[f64], y: &[f64]| sum(&product(x, x), &product(y, y));
+    let cross = |x1: &[f64], y1: &[f64], x2: &[f64], y2: &[f64]| {
+        sum(&product(x1, y2), &negate(&product(x2, y1)))
+    };
+    let a_term = product(&lift(&adx, &ady), &cross(&bdx, &bdy, &cdx, &cdy));
+    let b_term = product(&lift(&bdx, &bdy), &cross(&cdx, &cdy, &adx, &ady));
+    let c_term = product(&lift(&cdx, &cdy), &cross(&adx, &ady, &bdx, &bdy));
+    estimate(&sum(&sum(&a_term, &b_term), &c_term))
+}
+
+// The exact fallbacks represent numbers as expansions, sums of doubles
+// ordered by increasing magnitude whose nonzero components don't overlap
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    let b_virtual = x - a;
+    let a_virtual = x - b_virtual;
+    let error = (a - a_virtual) + (b - b_virtual);
+    (x, error)
+}
+
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let x = a * b;
+    (x, a.mul_add(b, -x))
+}
+
+fn diff(a: f64, b: f64) -> Vec<f64> {
+    let (x, error) = two_sum(a, -b);
+    vec![error, x]
+}
+
+fn grow(e: &[f64], b: f64) -> Vec<f64> {
+    let mut q = b;
+    let mut h = Vec::with_capacity(e.len() + 1);
+    for component in e {
+        let (sum, error) = two_sum(q, *component);
+        if error != 0.0 {
+            h.push(error);
+        }
+        q = sum;
+    }
+    h.push(q);
+    h
+}
+
+fn sum(e: &[f64], f: &[f64]) -> Vec<f64> {
+    f.iter()
+        .fold(e.to_vec(), |acc, component| grow(&acc, *component))
+}
+
+fn negate(e: &[f64]) -> Vec<f64> {
+    e.iter().map(|component| -component).collect()
+}
+
+fn product(e: &[f64], f: &[f64]) -> Vec<f64> {
+    let mut h = Vec::new();
+    for a in e {
+        for b in f {
+            let (x, error) = two_product(*a, *b);
+            h = grow(&grow(&h, error), x);
+        }
+    }
+    h
+}
+
+/// The most significant component has the sign of the whole expansion.
+fn estimate(e: &[f64]) -> f64 {
+    e.iter().rev().find(|c| **c != 0.0).copied().unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vertex::VertexId;
+
+    fn vertex(x: f64, y: f64) -> Vertex {
+        Vertex::new(VertexId::default(), x, y)
+    }
+
+    #[test]
+    fn test_orientation_near_collinear() {
+        // Points near the line y = x, perturbed by a few ulps, which plain
+        // floating point gets wrong. Every coordinate is a multiple of
+        // 2^-53 so the exact answer can be computed with integers.
+        let q = vertex(12.0, 12.0);
+        let r = vertex(24.0, 24.0);
+        let ulp = 2f64.powi(-53);
+        let scale = |v: f64| (v / ulp) as i128;
+        for i in 0..64 {
+            for j in 0..64 {
+                let p = vertex(0.5 + i as f64 * ulp, 0.5 + j as f64 * ulp);
+                let exact = (scale(p.x) - scale(r.x)) * (scale(q.y) - scale(r.y))
+                    - (scale(p.y) - scale(r.y)) * (scale(q.x) - scale(r.x));
+                let result = orientation(&p, &q, &r);
+                assert_eq!(result.partial_cmp(&0.0), Some(exact.cmp(&0)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_incircle_cocircular() {
+        let offset = 2f64.powi(40);
+        let a = vertex(offset + 1.0, offset);
+        let b = vertex(offset, offset + 1.0);
+        let c = vertex(offset - 1.0, offset);
+        assert_eq!(incircle(&a, &b, &c, &vertex(offset, offset - 1.0)), 0.0);
+        assert!(incircle(&a, &b, &c, &vertex(offset, offset)) > 0.0);
+        assert!(incircle(&a, &b, &c, &vertex(offset, offset - 2.0)) < 0.0);
+        assert!(incircle(&a, &c, &b, &vertex(offset, offset)) < 0.0);
+    }
+}
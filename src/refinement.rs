@@ -0,0 +1,592 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use ordered_float::OrderedFloat as OF;
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::{FRAC_PI_3, PI};
+
+use crate::{
+    delaunay::{edges, ConstrainedDelaunay, Mesh, GHOST},
+    error::TriangulationError,
+    geometry::Geometry,
+    polygon::Polygon,
+    triangle::Triangle,
+    triangulation::{TriangleVertexIds, Triangulation, TriangulationComputer},
+    vertex::{Vertex, VertexId},
+};
+
+const NUM_ANGLE_BINS: usize = 18;
+
+/// Summary of the shapes of the triangles in a triangulation, with angles
+/// in radians.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QualityStats {
+    pub num_triangles: usize,
+    pub min_angle: f64,
+    pub max_angle: f64,
+    /// Number of triangle angles in each 10° bin, from [0°, 10°) up to
+    /// [170°, 180°].
+    pub angle_histogram: [usize; NUM_ANGLE_BINS],
+    /// Largest and mean [`Triangle::aspect_ratio`] over all triangles.
+    pub max_aspect_ratio: f64,
+    pub mean_aspect_ratio: f64,
+}
+
+impl QualityStats {
+    pub fn from_triangulation<'a>(
+        vertices: impl IntoIterator<Item = &'a Vertex>,
+        triangulation: &Triangulation,
+    ) -> Self {
+        let vertex_map: HashMap<VertexId, &Vertex> =
+            vertices.into_iter().map(|v| (v.id, v)).collect();
+        let mut stats = QualityStats {
+            num_triangles: triangulation.len(),
+            min_angle: PI,
+            max_angle: 0.0,
+            angle_histogram: [0; NUM_ANGLE_BINS],
+            max_aspect_ratio: 0.0,
+            mean_aspect_ratio: 0.0,
+        };
+        let mut total_aspect_ratio = 0.0;
+        for t in triangulation.iter() {
+            let triangle =
+                Triangle::from_vertices(vertex_map[&t.0], vertex_map[&t.1], vertex_map[&t.2]);
+            for angle in triangle.angles() {
+                stats.min_angle = stats.min_angle.min(angle);
+                stats.max_angle = stats.max_angle.max(angle);
+                let bin = (angle / PI * NUM_ANGLE_BINS as f64) as usize;
+                stats.angle_histogram[bin.min(NUM_ANGLE_BINS - 1)] += 1;
+            }
+            let aspect_ratio = triangle.aspect_ratio();
+            stats.max_aspect_ratio = stats.max_aspect_ratio.max(aspect_ratio);
+            total_aspect_ratio += aspect_ratio;
+        }
+        if !triangulation.is_empty() {
+            stats.mean_aspect_ratio = total_aspect_ratio / triangulation.len() as f64;
+        }
+        stats
+    }
+}
+
+/// Delaunay refinement of a polygon following Ruppert's algorithm, which
+/// inserts Steiner vertices until every triangle meets the quality bounds.
+/// A boundary segment is split whenever a vertex encroaches on it, meaning
+/// the vertex is inside the segment's diametral circle, and a poor
+/// triangle is split by inserting its circumcenter unless that would
+/// encroach on a segment, in which case the segment is split instead.
+///
+/// This is guaranteed to terminate for a `min_angle` up to about 20.7° if
+/// the polygon has no angles under 60°. Sharper corners would set off an
+/// endless cascade of splits, so as in Shewchuk's Triangle, segments next
+/// to a polygon vertex are split on circles of power of two radius around
+/// it, and triangles whose shortest edge cuts across a sharp corner are
+/// left alone.
+pub struct Ruppert {
+    /// Smallest angle in radians each triangle should have, which has to
+    /// be greater than zero and less than 60°, the angles of an
+    /// equilateral triangle.
+    pub min_angle: f64,
+    /// Largest area each triangle should have, if any, which has to be
+    /// finite and greater than zero.
+    pub max_area: Option<f64>,
+    /// Safeguard that stops refinement after this many Steiner vertices,
+    /// failing with [`TriangulationError::SteinerLimitReached`] if the
+    /// quality bounds are not met by then.
+    pub max_steiner_vertices: usize,
+}
+
+impl Default for Ruppert {
+    fn default() -> Self {
+        Ruppert {
+            min_angle: 20f64.to_radians(),
+            max_area: None,
+            max_steiner_vertices: 100_000,
+        }
+    }
+}
+
+impl Ruppert {
+    pub fn refine(&self, polygon: &Polygon) -> Result<RefinedMesh, TriangulationError> {
+        if !(self.min_angle > 0.0 && self.min_angle < FRAC_PI_3) {
+            return Err(TriangulationError::DegenerateInput(format!(
+                "minimum angle must be in (0, π/3), min_angle={}",
+                self.min_angle
+            )));
+        }
+        if let Some(max_area) = self.max_area.filter(|a| !(a.is_finite() && *a > 0.0)) {
+            return Err(TriangulationError::DegenerateInput(format!(
+                "maximum area must be finite and positive, max_area={max_area}"
+            )));
+        }
+        info!(
+            "Refining triangulation to a minimum angle of {:.2}°",
+            self.min_angle.to_degrees()
+        );
+        let initial = ConstrainedDelaunay.triangulation(polygon)?;
+        let before = QualityStats::from_triangulation(polygon.vertices(), &initial);
+
+        let mut refiner = Refiner::new(self, polygon);
+        refiner.refine()?;
+
+        let mut triangulation = Triangulation::default();
+        for t in refiner.inside_triangles()? {
+            let [a, b, c] = refiner.mesh.triangle(t).map(|i| refiner.point(i).id);
+            triangulation.push(TriangleVertexIds(a, b, c));
+        }
+        let points = refiner.mesh.points();
+        let steiner_vertices = points[refiner.num_input..]
+            .iter()
+            .map(|v| v.id)
+            .collect_vec();
+        let after = QualityStats::from_triangulation(points, &triangulation);
+
+        info!(
+            "Refined triangulation with {} Steiner vertices",
+            steiner_vertices.len()
+        );
+        Ok(RefinedMesh {
+            vertex_map: points.iter().map(|v| (v.id, v.clone())).collect(),
+            triangulation,
+            steiner_vertices,
+            before,
+            after,
+        })
+    }
+}
+
+/// Result of [`Ruppert::refine`]. The triangulation refers to both the
+/// polygon's vertices and the Steiner vertices, whose IDs follow on from
+/// the largest ID in the polygon.
+pub struct RefinedMesh {
+    vertex_map: HashMap<VertexId, Vertex>,
+    triangulation: Triangulation,
+    steiner_vertices: Vec<VertexId>,
+    before: QualityStats,
+    after: QualityStats,
+}
+
+impl RefinedMesh {
+    pub fn get_vertex(&self, id: &VertexId) -> Option<&Vertex> {
+        self.vertex_map.get(id)
+    }
+
+    pub fn vertices(&self) -> impl Iterator<Item = &Vertex> {
+        self.vertex_map.values()
+    }
+
+    pub fn triangulation(&self) -> &Triangulation {
+        &self.triangulation
+    }
+
+    pub fn steiner_vertices(&self) -> &[VertexId] {
+        &self.steiner_vertices
+    }
+
+    /// Quality of the constrained Delaunay triangulation of the polygon,
+    /// which is the best that can be done without Steiner vertices.
+    pub fn before(&self) -> &QualityStats {
+        &self.before
+    }
+
+    pub fn after(&self) -> &QualityStats {
+        &self.after
+    }
+}
+
+/// Refinement state over the Delaunay triangulation of every vertex so
+/// far. Once no segment is encroached every segment is an edge of the
+/// triangulation, so the triangles inside the polygon can be found by
+/// flood filling from the segments without crossing them.
+struct Refiner<'a> {
+    config: &'a Ruppert,
+    polygon: &'a Polygon,
+    mesh: Mesh,
+    num_input: usize,
+    first_steiner_id: usize,
+    // Pieces of the polygon edges directed CCW, to the index of the edge
+    segments: HashMap<(usize, usize), usize>,
+    // Index of the polygon edge each Steiner vertex on the boundary is on
+    on_edge: HashMap<usize, usize>,
+    // Whether the interior angle at each polygon vertex is under 60°
+    sharp: Vec<bool>,
+}
+
+impl<'a> Refiner<'a> {
+    fn new(config: &'a Ruppert, polygon: &'a Polygon) -> Self {
+        let vertices = polygon.vertices();
+        let n = vertices.len();
+        let sharp = (0..n)
+            .map(|i| {
+                let (u, v, w) = (
+                    vertices[(i + n - 1) % n],
+                    vertices[i],
+                    vertices[(i + 1) % n],
+                );
+                let (ux, uy) = (u.x - v.x, u.y - v.y);
+                let (wx, wy) = (w.x - v.x, w.y - v.y);
+                let angle = (ux * wy - uy * wx).abs().atan2(ux * wx + uy * wy);
+                !polygon.is_reflex(&v.id) && angle < FRAC_PI_3
+            })
+            .collect_vec();
+        let first_steiner_id = vertices.iter().map(|v| usize::from(v.id)).max().unwrap() + 1;
+
+        let points = vertices.into_iter().cloned().collect_vec();
+        // The polygon has positive area so its vertices can't all be collinear
+        let mut mesh = Mesh::new(points).unwrap();
+        mesh.insert_all();
+
+        Refiner {
+            config,
+            polygon,
+            mesh,
+            num_input: n,
+            first_steiner_id,
+            segments: (0..n).map(|i| ((i, (i + 1) % n), i)).collect(),
+            on_edge: HashMap::new(),
+            sharp,
+        }
+    }
+
+    fn point(&self, i: usize) -> &Vertex {
+        &self.mesh.points()[i]
+    }
+
+    fn num_steiner(&self) -> usize {
+        self.mesh.points().len() - self.num_input
+    }
+
+    fn next_vertex(&self, x: f64, y: f64) -> Vertex {
+        Vertex::new(
+            VertexId::from(self.first_steiner_id + self.num_steiner()),
+            x,
+            y,
+        )
+    }
+
+    fn refine(&mut self) -> Result<(), TriangulationError> {
+        loop {
+            self.split_encroached_segments()?;
+            let bad = self
+                .inside_triangles()?
+                .into_iter()
+                .filter_map(|t| self.badness(t).map(|angle| (OF(angle), t)))
+                .sorted()
+                .collect_vec();
+            debug!("Refining {} poor triangles", bad.len());
+            if bad.is_empty() {
+                return Ok(());
+            }
+
+            let num_bad = bad.len();
+            let mut progress = false;
+            for (_, t) in bad {
+                self.check_steiner_limit()?;
+                if !self.mesh.is_alive(t) {
+                    continue;
+                }
+                let [a, b, c] = self.mesh.triangle(t).map(|i| self.point(i));
+                let (x, y) = Triangle::from_vertices(a, b, c).circumcenter();
+                let center = self.next_vertex(x, y);
+                let encroached = self
+                    .segments
+                    .keys()
+                    .filter(|s| self.in_diametral_circle(**s, &center))
+                    .copied()
+                    .sorted()
+                    .collect_vec();
+                if !encroached.is_empty() {
+                    for s in encroached {
+                        self.split_segment(s);
+                    }
+                    self.split_encroached_segments()?;
+                    progress = true;
+                } else if self.polygon.contains(&center) {
+                    trace!(center:?; "Inserting circumcenter");
+                    self.mesh.add_point(center);
+                    progress = true;
+                } else {
+                    debug!("Skipping triangle with circumcenter outside polygon: {center:?}");
+                }
+            }
+            // With no segment encroached the circumcenter of a triangle
+            // inside the polygon is inside it too, so getting stuck here is
+            // only down to rounding
+            if !progress {
+                return Err(TriangulationError::NumericalFailure(format!(
+                    "no circumcenter of the {num_bad} poor triangles could be inserted"
+                )));
+            }
+        }
+    }
+
+    fn check_steiner_limit(&self) -> Result<(), TriangulationError> {
+        let limit = self.config.max_steiner_vertices;
+        if self.num_steiner() >= limit {
+            debug!("Reached the limit of Steiner vertices");
+            return Err(TriangulationError::SteinerLimitReached(limit));
+        }
+        Ok(())
+    }
+
+    /// The smallest angle of the triangle if it needs to be split, which
+    /// is used to split the worst triangles first.
+    fn badness(&self, t: usize) -> Option<f64> {
+        let [a, b, c] = self.mesh.triangle(t).map(|i| self.point(i));
+        let triangle = Triangle::from_vertices(a, b, c);
+        let min_angle = triangle.min_angle();
+        let too_large = self
+            .config
+            .max_area
+            .is_some_and(|max_area| triangle.area() > max_area);
+        let too_sharp = min_angle < self.config.min_angle && !self.cuts_sharp_corner(t);
+        (too_large || too_sharp).then_some(min_angle)
+    }
+
+    /// Whether the shortest edge of the triangle connects two Steiner
+    /// vertices on different polygon edges that meet at a sharp corner.
+    fn cuts_sharp_corner(&self, t: usize) -> bool {
+        let (u, v) = edges(&self.mesh.triangle(t))
+            .into_iter()
+            .min_by_key(|(u, v)| OF(self.point(*u).distance_to(self.point(*v))))
+            .unwrap();
+        let (Some(&i), Some(&j)) = (self.on_edge.get(&u), self.on_edge.get(&v)) else {
+            return false;
+        };
+        let n = self.num_input;
+        if (i + 1) % n == j {
+            self.sharp[j]
+        } else if (j + 1) % n == i {
+            self.sharp[i]
+        } else {
+            false
+        }
+    }
+
+    fn in_diametral_circle(&self, (a, b): (usize, usize), v: &Vertex) -> bool {
+        let (pa, pb) = (self.point(a), self.point(b));
+        (pa.x - v.x) * (pb.x - v.x) + (pa.y - v.y) * (pb.y - v.y) < 0.0
+    }
+
+    /// A segment is encroached if it is not an edge of the triangulation,
+    /// or if the vertex opposite it on either side is in its diametral
+    /// circle. Both triangles have empty circumcircles, so when neither
+    /// opposite vertex is in the diametral circle no other vertex can be.
+    fn is_encroached(&self, (a, b): (usize, usize)) -> bool {
+        let Some(inner) = self.mesh.triangle_with_edge(a, b) else {
+            return true;
+        };
+        std::iter::once(inner)
+            .chain(self.mesh.triangle_with_edge(b, a))
+            .filter_map(|t| {
+                let c = self
+                    .mesh
+                    .triangle(t)
+                    .into_iter()
+                    .find(|i| *i != a && *i != b);
+                c.filter(|c| *c != GHOST)
+            })
+            .any(|c| self.in_diametral_circle((a, b), self.point(c)))
+    }
+
+    /// Splits segments until none are encroached, after which every
+    /// segment is an edge of the triangulation.
+    fn split_encroached_segments(&mut self) -> Result<(), TriangulationError> {
+        let mut stack = self
+            .segments
+            .keys()
+            .filter(|s| self.is_encroached(**s))
+            .copied()
+            .sorted()
+            .collect_vec();
+        while let Some(s) = stack.pop() {
+            if !self.segments.contains_key(&s) || !self.is_encroached(s) {
+                continue;
+            }
+            self.check_steiner_limit()?;
+            let m = self.split_segment(s);
+            let v = self.point(m);
+            stack.extend(
+                self.segments
+                    .keys()
+                    .filter(|s| s.0 == m || s.1 == m || self.in_diametral_circle(**s, v))
+                    .copied()
+                    .sorted(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Splits the segment at its midpoint, except that a segment with one
+    /// end at a polygon vertex is split at a power of two distance from
+    /// it. Segments on the two sides of a sharp corner then get split at
+    /// matching distances, so the splits don't keep encroaching each other.
+    fn split_segment(&mut self, (a, b): (usize, usize)) -> usize {
+        let edge = self.segments.remove(&(a, b)).unwrap();
+        let (pa, pb) = (self.point(a), self.point(b));
+        let length = pa.distance_to(pb);
+        let shell = 2f64.powf((length / 2.0).log2().round()) / length;
+        let ratio = match (a < self.num_input, b < self.num_input) {
+            (true, false) => shell,
+            (false, true) => 1.0 - shell,
+            _ => 0.5,
+        };
+        let v = self.next_vertex(pa.x + ratio * (pb.x - pa.x), pa.y + ratio * (pb.y - pa.y));
+        trace!(a:?=pa.id, b:?=pb.id, v:?; "Splitting segment");
+
+        let m = self.mesh.add_point(v);
+        self.on_edge.insert(m, edge);
+        self.segments.insert((a, m), edge);
+        self.segments.insert((m, b), edge);
+        m
+    }
+
+    /// Triangles inside the polygon, found by flood filling from the
+    /// inner side of each segment. A segment missing from the triangulation
+    /// would let the flood out of the polygon, so this fails unless they
+    /// are all there.
+    fn inside_triangles(&self) -> Result<Vec<usize>, TriangulationError> {
+        if let Some((a, b)) = self
+            .segments
+            .keys()
+            .find(|(a, b)| self.mesh.triangle_with_edge(*a, *b).is_none())
+        {
+            return Err(TriangulationError::NumericalFailure(format!(
+                "segment from {:?} to {:?} is not an edge of the triangulation",
+                self.point(*a),
+                self.point(*b)
+            )));
+        }
+        let mut inside = HashSet::new();
+        let mut stack = self
+            .segments
+            .keys()
+            .filter_map(|(a, b)| self.mesh.triangle_with_edge(*a, *b))
+            .collect_vec();
+        while let Some(t) = stack.pop() {
+            if !inside.insert(t) {
+                continue;
+            }
+            for (a, b) in edges(&self.mesh.triangle(t)) {
+                if self.segments.contains_key(&(a, b)) {
+                    continue;
+                }
+                if let Some(neighbor) = self.mesh.triangle_with_edge(b, a) {
+                    if self.mesh.triangle(neighbor)[2] != GHOST {
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+        Ok(inside.into_iter().sorted().collect_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delaunay::is_delaunay;
+    use crate::test_util::*;
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    fn area(mesh: &RefinedMesh) -> f64 {
+        mesh.triangulation()
+            .iter()
+            .map(|t| {
+                let [a, b, c] = [t.0, t.1, t.2].map(|id| mesh.get_vertex(&id).unwrap());
+                Triangle::from_vertices(a, b, c).area()
+            })
+            .sum()
+    }
+
+    #[apply(all_polygons)]
+    fn test_ruppert(case: PolygonTestCase) {
+        let ruppert = Ruppert::default();
+        let mesh = ruppert.refine(&case.polygon).unwrap();
+        let polygon_ids: HashSet<_> = case.polygon.vertex_ids().into_iter().collect();
+        let stats = mesh.after();
+
+        assert!(mesh
+            .steiner_vertices()
+            .iter()
+            .all(|id| !polygon_ids.contains(id)));
+        assert_approx_eq!(area(&mesh), case.polygon.area(), 1e-6 * case.polygon.area());
+        assert!(is_delaunay(mesh.vertices(), mesh.triangulation()));
+        assert_eq!(stats.num_triangles, mesh.triangulation().len());
+        assert_eq!(
+            stats.angle_histogram.iter().sum::<usize>(),
+            3 * stats.num_triangles
+        );
+
+        // Sharp polygon corners can keep some triangles under the minimum
+        // angle, but refinement never makes the worst triangle worse
+        let target = ruppert.min_angle.min(mesh.before().min_angle);
+        assert!(stats.min_angle >= target - 1e-9);
+    }
+
+    #[test]
+    fn test_ruppert_max_area() {
+        let polygon = Polygon::from_coords(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]);
+        let ruppert = Ruppert {
+            max_area: Some(0.5),
+            ..Default::default()
+        };
+        let mesh = ruppert.refine(&polygon).unwrap();
+        assert_eq!(mesh.before().num_triangles, 2);
+        assert!(mesh.after().num_triangles >= 32);
+        assert!(mesh.after().min_angle >= ruppert.min_angle);
+        assert_approx_eq!(area(&mesh), 16.0);
+        for t in mesh.triangulation().iter() {
+            let [a, b, c] = [t.0, t.1, t.2].map(|id| mesh.get_vertex(&id).unwrap());
+            assert!(Triangle::from_vertices(a, b, c).area() <= 0.5);
+        }
+        let first_steiner_id = VertexId::from(4usize);
+        assert!(mesh
+            .steiner_vertices()
+            .iter()
+            .all(|id| *id >= first_steiner_id));
+    }
+
+    #[rstest]
+    fn test_ruppert_invalid_min_angle(
+        #[values(f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 0.0, -0.1, FRAC_PI_3, PI)]
+        min_angle: f64,
+    ) {
+        let polygon = Polygon::from_coords(square_coords(0.0, 0.0, 4.0));
+        let ruppert = Ruppert {
+            min_angle,
+            ..Default::default()
+        };
+        assert!(matches!(
+            ruppert.refine(&polygon),
+            Err(TriangulationError::DegenerateInput(_))
+        ));
+    }
+
+    #[rstest]
+    fn test_ruppert_invalid_max_area(#[values(f64::NAN, f64::INFINITY, 0.0, -1.0)] max_area: f64) {
+        let polygon = Polygon::from_coords(square_coords(0.0, 0.0, 4.0));
+        let ruppert = Ruppert {
+            max_area: Some(max_area),
+            ..Default::default()
+        };
+        assert!(matches!(
+            ruppert.refine(&polygon),
+            Err(TriangulationError::DegenerateInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_ruppert_steiner_limit() {
+        let polygon = Polygon::from_coords(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]);
+        let ruppert = Ruppert {
+            max_area: Some(0.01),
+            max_steiner_vertices: 10,
+            ..Default::default()
+        };
+        assert!(matches!(
+            ruppert.refine(&polygon),
+            Err(TriangulationError::SteinerLimitReached(10))
+        ));
+    }
+}
@@ -0,0 +1,300 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use std::collections::VecDeque;
+
+use crate::{
+    error::QueryPointError,
+    geometry::Geometry,
+    mesh::{TriangleId, TriangleMesh},
+    polygon::Polygon,
+    predicates::orientation,
+    triangulation::{EarClipping, TriangulationComputer},
+    vertex::{Vertex, VertexId},
+};
+
+/// A geodesic path between two points inside a polygon.
+#[derive(Clone, Debug)]
+pub struct ShortestPath {
+    /// The two endpoints with the polygon vertices the path bends around
+    /// between them, which are all reflex vertices.
+    pub points: Vec<Vertex>,
+    pub length: f64,
+}
+
+/// Shortest path inside a simple polygon by the funnel algorithm of Lee
+/// and Preparata in $O(n)$ after triangulating.
+///
+/// The triangles of the sleeve connecting the two points in the dual tree
+/// are crossed through the diagonals between them, called portals. The
+/// shortest paths from the start to both ends of the last portal share a
+/// prefix and then split into two concave chains forming a funnel, and
+/// each portal narrows the funnel from one side. When a side crosses over
+/// the other the tip of the funnel moves along that chain, adding its
+/// vertices to the path.
+#[derive(Default)]
+pub struct Funnel;
+
+impl Funnel {
+    /// Finds the shortest path between two points inside the polygon or on
+    /// its boundary.
+    pub fn shortest_path(
+        &self,
+        polygon: &Polygon,
+        from: &Vertex,
+        to: &Vertex,
+    ) -> Result<ShortestPath, QueryPointError> {
+        info!("Computing shortest path from {from:?} to {to:?}");
+        let triangulation = EarClipping.triangulation(polygon)?;
+        let mesh = TriangleMesh::from_triangulation(&triangulation);
+        let start = locate(polygon, &mesh, from)?;
+        let end = locate(polygon, &mesh, to)?;
+        let sleeve = mesh.dual_path(start, end).unwrap();
+        debug!("Sleeve from triangle {start} to {end}: {sleeve:?}");
+
+        // Each portal is the diagonal crossed leaving a triangle, as seen
+        // facing the next triangle
+        let portals = sleeve
+            .iter()
+            .tuple_windows()
+            .map(|(t, next)| {
+                let ids = mesh.triangle(*t);
+                let (right, left) = [(ids.0, ids.1), (ids.1, ids.2), (ids.2, ids.0)]
+                    .into_iter()
+                    .find(|(a, b)| mesh.triangle_with_edge(b, a) == Some(*next))
+                    .unwrap();
+                (left, right)
+            })
+            .collect_vec();
+
+        let mut funnel = FunnelState::new(from);
+        let mut previous: Option<(VertexId, VertexId)> = None;
+        for (left, right) in portals {
+            let v = |id| polygon.get_vertex(&id).unwrap();
+            if previous.map_or(true, |(l, _)| l != left) {
+                funnel.push_left(v(left));
+            }
+            if previous.map_or(true, |(_, r)| r != right) {
+                funnel.push_right(v(right));
+            }
+            previous = Some((left, right));
+        }
+        funnel.push_left(to);
+
+        let mut points: Vec<Vertex> = Vec::new();
+        // The end was added on the left, so the rest of the path runs along
+        // the left chain from the apex
+        let left_chain = funnel.chain.into_iter().take(funnel.apex + 1).rev();
+        for v in funnel.path.into_iter().chain(left_chain) {
+            if points.last().map_or(true, |u| u.coords() != v.coords()) {
+                points.push(v.clone());
+            }
+        }
+        if points.len() == 1 {
+            points.push(to.clone());
+        }
+        let length = points
+            .iter()
+            .tuple_windows()
+            .map(|(u, v)| u.distance_to(v))
+            .sum();
+
+        info!(
+            "Computed shortest path of length {length} through {} points",
+            points.len()
+        );
+        Ok(ShortestPath { points, length })
+    }
+}
+
+/// A triangle of the mesh containing the point, including its boundary.
+fn locate(
+    polygon: &Polygon,
+    mesh: &TriangleMesh,
+    v: &Vertex,
+) -> Result<TriangleId, QueryPointError> {
+    (0..mesh.num_triangles())
+        .find(|t| {
+            let ids = mesh.triangle(*t);
+            polygon
+                .get_triangle(&ids.0, &ids.1, &ids.2)
+                .unwrap()
+                .contains(v)
+        })
+        .ok_or_else(|| QueryPointError::OutsidePolygon(v.clone()))
+}
+
+/// The funnel as a deque with the left chain at the front and the right
+/// chain at the back, meeting at the apex. Walking out from the apex the
+/// left chain only turns left and the right chain only turns right.
+struct FunnelState<'a> {
+    // Path from the start up to but not including the apex
+    path: Vec<&'a Vertex>,
+    chain: VecDeque<&'a Vertex>,
+    apex: usize,
+}
+
+impl<'a> FunnelState<'a> {
+    fn new(start: &'a Vertex) -> Self {
+        FunnelState {
+            path: Vec::new(),
+            chain: VecDeque::from([start]),
+            apex: 0,
+        }
+    }
+
+    fn push_left(&mut self, p: &'a Vertex) {
+        while self.apex > 0 && orientation(self.chain[1], self.chain[0], p) <= 0.0 {
+            self.chain.pop_front();
+            self.apex -= 1;
+        }
+        // Once the left chain is gone the new side can cross the right
+        // chain, in which case the path has to bend around it
+        if self.apex == 0 {
+            while self.chain.len() > 1 && orientation(self.chain[0], self.chain[1], p) < 0.0 {
+                let apex = self.chain.pop_front().unwrap();
+                trace!("Apex moves right from {apex:?}");
+                self.path.push(apex);
+            }
+        }
+        self.chain.push_front(p);
+        self.apex += 1;
+    }
+
+    fn push_right(&mut self, p: &'a Vertex) {
+        let back = |chain: &VecDeque<&'a Vertex>, i: usize| chain[chain.len() - 1 - i];
+        while self.apex < self.chain.len() - 1
+            && orientation(back(&self.chain, 1), back(&self.chain, 0), p) >= 0.0
+        {
+            self.chain.pop_back();
+        }
+        if self.apex == self.chain.len() - 1 {
+            while self.apex > 0
+                && orientation(self.chain[self.apex], self.chain[self.apex - 1], p) > 0.0
+            {
+                let apex = self.chain.pop_back().unwrap();
+                trace!("Apex moves left from {apex:?}");
+                self.path.push(apex);
+                self.apex -= 1;
+            }
+        }
+        self.chain.push_back(p);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::F64_ASSERT_PRECISION;
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    fn check_shortest_path(polygon: &Polygon, from: &Vertex, to: &Vertex) {
+        let path = Funnel.shortest_path(polygon, from, to).unwrap();
+        let points = &path.points;
+        assert_eq!(points[0].coords(), from.coords());
+        assert_eq!(points[points.len() - 1].coords(), to.coords());
+
+        let reflex = polygon.reflex_vertices();
+        for v in &points[1..points.len() - 1] {
+            assert_eq!(polygon.get_vertex(&v.id), Some(v));
+            assert!(reflex.contains(&v.id), "{v:?}");
+        }
+        for (u, v) in points.iter().tuple_windows() {
+            assert!(polygon.sees(u, v), "{u:?} {v:?}");
+        }
+
+        let direct = from.distance_to(to);
+        assert!(path.length >= direct - F64_ASSERT_PRECISION);
+        if polygon.sees(from, to) {
+            assert_eq!(points.len(), 2);
+            assert_approx_eq!(path.length, direct, F64_ASSERT_PRECISION);
+        }
+    }
+
+    #[apply(all_polygons)]
+    fn test_shortest_path(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        let triangulation = EarClipping.triangulation(polygon).unwrap();
+        let centroids = triangulation
+            .iter()
+            .step_by(triangulation.len() / 4 + 1)
+            .map(|ids| triangle_centroid(polygon, ids))
+            .collect_vec();
+        for (from, to) in centroids.iter().tuple_combinations() {
+            check_shortest_path(polygon, from, to);
+            check_shortest_path(polygon, to, from);
+        }
+
+        let vertices = polygon.vertices();
+        check_shortest_path(polygon, vertices[0], vertices[vertices.len() / 2]);
+
+        // The path is as short as the one found by Dijkstra's algorithm on
+        // the visibility graph of the reflex vertices
+        let (from, to) = (&centroids[0], &centroids[centroids.len() - 1]);
+        let path = Funnel.shortest_path(polygon, from, to).unwrap();
+        assert_approx_eq!(
+            path.length,
+            visibility_graph_distance(polygon, from, to),
+            F64_ASSERT_PRECISION
+        );
+    }
+
+    fn visibility_graph_distance(polygon: &Polygon, from: &Vertex, to: &Vertex) -> f64 {
+        let mut nodes = vec![from];
+        nodes.extend(
+            polygon
+                .reflex_vertices()
+                .iter()
+                .map(|id| polygon.get_vertex(id).unwrap()),
+        );
+        nodes.push(to);
+        let mut distances = vec![f64::INFINITY; nodes.len()];
+        let mut done = vec![false; nodes.len()];
+        distances[0] = 0.0;
+        while let Some(i) = (0..nodes.len())
+            .filter(|i| !done[*i])
+            .min_by(|i, j| distances[*i].total_cmp(&distances[*j]))
+        {
+            done[i] = true;
+            for j in 0..nodes.len() {
+                if !done[j] && polygon.sees(nodes[i], nodes[j]) {
+                    let d = distances[i] + nodes[i].distance_to(nodes[j]);
+                    distances[j] = distances[j].min(d);
+                }
+            }
+        }
+        distances[nodes.len() - 1]
+    }
+
+    #[test]
+    fn test_shortest_path_around_notch() {
+        // A U shape where getting from one arm to the other means going
+        // around both inner corners
+        let coords = vec![
+            (0.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 3.0),
+            (2.0, 3.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 3.0),
+            (0.0, 3.0),
+        ];
+        let polygon = Polygon::from_coords(coords);
+        let from = Vertex::new(VertexId::default(), 2.5, 2.5);
+        let to = Vertex::new(VertexId::default(), 0.5, 2.5);
+        let path = Funnel.shortest_path(&polygon, &from, &to).unwrap();
+        let ids = path.points.iter().map(|v| v.id).collect_vec();
+        assert_eq!(ids[1..3], [VertexId::from(4usize), VertexId::from(5usize)]);
+        let expected = 2.0 * (0.5f64.powi(2) + 1.5f64.powi(2)).sqrt() + 1.0;
+        assert_approx_eq!(path.length, expected, F64_ASSERT_PRECISION);
+
+        let outside = Vertex::new(VertexId::default(), 1.5, 2.0);
+        assert!(matches!(
+            Funnel.shortest_path(&polygon, &from, &outside),
+            Err(QueryPointError::OutsidePolygon(_))
+        ));
+    }
+}
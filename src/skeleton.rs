@@ -0,0 +1,954 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use ordered_float::OrderedFloat as OF;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    delaunay::Delaunay,
+    error::SkeletonError,
+    geometry::Geometry,
+    mesh::TriangleMesh,
+    polygon::Polygon,
+    triangle::Triangle,
+    vertex::{Vertex, VertexId},
+};
+
+/// Relative tolerance for wavefront vertices and edges to be counted as
+/// meeting at an event, scaled by the size of the polygon.
+const EVENT_TOLERANCE: f64 = 1e-8;
+
+/// Below this, directions are treated as parallel.
+const PARALLEL_TOLERANCE: f64 = 1e-12;
+
+/// Directions of edges around an event closer than this in radians are
+/// treated as going the same way.
+const ANGLE_TOLERANCE: f64 = 1e-6;
+
+/// Index of a node in a [`StraightSkeleton`].
+pub type SkeletonNodeId = usize;
+
+#[derive(Clone, Debug)]
+pub struct SkeletonNode {
+    pub x: f64,
+    pub y: f64,
+    /// When the wavefront reached the node, which is also its distance to
+    /// the lines of the edges whose faces meet there. Leaves are at the
+    /// polygon vertices at time 0, and for a roof with 45 degree slopes
+    /// this is the height of the node.
+    pub time: f64,
+    /// The polygon vertex for a leaf.
+    pub vertex: Option<VertexId>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SkeletonArc {
+    pub from: SkeletonNodeId,
+    pub to: SkeletonNodeId,
+    /// The two polygon edges whose faces the arc separates, which the arc
+    /// bisects.
+    pub edges: [(VertexId, VertexId); 2],
+}
+
+/// Straight skeleton of a simple polygon, traced out by the vertices of the
+/// wavefront as every edge moves inward at the same speed. Each edge sweeps
+/// out a face of the skeleton, and the arcs between faces are where two
+/// edges meet.
+///
+/// The wavefront is simulated event by event. An edge event is when an
+/// edge shrinks away and its neighbors meet, and a split event is when a
+/// reflex vertex runs into an edge and splits the wavefront in two. Every
+/// vertex and edge of the wavefront passing through the point of an event
+/// is handled together, which takes care of vertex events where several
+/// events happen at once, as is common for rectilinear polygons. The next
+/// event is found by checking every vertex against every edge, which is
+/// $O(n^3)$ overall.
+pub struct StraightSkeleton {
+    nodes: Vec<SkeletonNode>,
+    arcs: Vec<SkeletonArc>,
+    edges: Vec<(VertexId, VertexId)>,
+}
+
+impl StraightSkeleton {
+    pub fn from_polygon(polygon: &Polygon) -> Result<Self, SkeletonError> {
+        info!("Computing straight skeleton");
+        let mut wavefront = Wavefront::new(polygon);
+        let max_events = 10 * polygon.num_vertices();
+        let mut num_events = 0;
+        while wavefront.vertices.iter().any(|v| v.active) {
+            let Some((time, point)) = wavefront.next_event() else {
+                return Err(SkeletonError::NumericalFailure(format!(
+                    "no event found at time {} with {} wavefront vertices left",
+                    wavefront.time,
+                    wavefront.vertices.iter().filter(|v| v.active).count()
+                )));
+            };
+            wavefront.handle_event(time, point)?;
+            num_events += 1;
+            if num_events > max_events {
+                return Err(SkeletonError::NumericalFailure(format!(
+                    "wavefront did not collapse within {max_events} events"
+                )));
+            }
+        }
+        info!(
+            "Computed straight skeleton with {} nodes and {} arcs in {num_events} events",
+            wavefront.nodes.len(),
+            wavefront.arcs.len()
+        );
+        Ok(StraightSkeleton {
+            nodes: wavefront.nodes,
+            arcs: wavefront.arcs,
+            edges: wavefront.edges.into_iter().map(|e| e.ids).collect(),
+        })
+    }
+
+    pub fn nodes(&self) -> &[SkeletonNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, id: SkeletonNodeId) -> &SkeletonNode {
+        &self.nodes[id]
+    }
+
+    pub fn arcs(&self) -> &[SkeletonArc] {
+        &self.arcs
+    }
+
+    /// The leaf at the polygon vertex.
+    pub fn leaf(&self, id: &VertexId) -> Option<SkeletonNodeId> {
+        self.nodes.iter().position(|n| n.vertex == Some(*id))
+    }
+
+    /// The face swept out by the polygon edge from `a` to `b`, as nodes in
+    /// CCW order starting from the leaves at `a` and `b`. For a roof this
+    /// is the outline of the roof plane rising from the edge.
+    pub fn face(&self, a: &VertexId, b: &VertexId) -> Option<Vec<SkeletonNodeId>> {
+        if !self.edges.contains(&(*a, *b)) {
+            return None;
+        }
+        let mut adjacency: HashMap<SkeletonNodeId, Vec<SkeletonNodeId>> = HashMap::new();
+        for arc in self.arcs.iter().filter(|arc| arc.edges.contains(&(*a, *b))) {
+            adjacency.entry(arc.from).or_default().push(arc.to);
+            adjacency.entry(arc.to).or_default().push(arc.from);
+        }
+
+        // The arcs around the face form a path between the two leaves
+        let (start, end) = (self.leaf(a)?, self.leaf(b)?);
+        let mut parents = HashMap::from([(end, end)]);
+        let mut queue = VecDeque::from([end]);
+        while let Some(node) = queue.pop_front() {
+            for next in adjacency.get(&node).into_iter().flatten() {
+                if !parents.contains_key(next) {
+                    parents.insert(*next, node);
+                    queue.push_back(*next);
+                }
+            }
+        }
+        parents.get(&start)?;
+        let mut path = Vec::new();
+        let mut current = parents[&start];
+        while current != end {
+            path.push(current);
+            current = parents[&current];
+        }
+        let mut face = vec![start, end];
+        face.extend(path.into_iter().rev());
+        Some(face)
+    }
+}
+
+/// A polygon edge moving inward, which at time $t$ is on the line
+/// $n \cdot x = c + t$ for its inward unit normal $n$.
+struct WavefrontEdge {
+    ids: (VertexId, VertexId),
+    direction: (f64, f64),
+    normal: (f64, f64),
+    offset: f64,
+}
+
+/// A vertex of the wavefront where the edges `e_in` and `e_out` meet, which
+/// was at `(x, y)` at `time` and moves along the bisector of the edges.
+struct WavefrontVertex {
+    x: f64,
+    y: f64,
+    time: f64,
+    velocity: (f64, f64),
+    e_in: usize,
+    e_out: usize,
+    prev: usize,
+    next: usize,
+    node: SkeletonNodeId,
+    active: bool,
+}
+
+impl WavefrontVertex {
+    fn position(&self, time: f64) -> (f64, f64) {
+        let dt = time - self.time;
+        (self.x + self.velocity.0 * dt, self.y + self.velocity.1 * dt)
+    }
+}
+
+/// A piece of the wavefront passing through the point of an event, where
+/// the wavefront comes in along edge `e_in` from `prev` and leaves along
+/// `e_out` to `next`. The vertices of the piece at the point are gone after
+/// the event, and an edge passing through the point is a piece with no
+/// vertices.
+struct Chain {
+    e_in: usize,
+    e_out: usize,
+    prev: usize,
+    next: usize,
+}
+
+struct Wavefront {
+    edges: Vec<WavefrontEdge>,
+    vertices: Vec<WavefrontVertex>,
+    nodes: Vec<SkeletonNode>,
+    arcs: Vec<SkeletonArc>,
+    time: f64,
+    tolerance: f64,
+}
+
+fn dot(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn cross(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+impl Wavefront {
+    fn new(polygon: &Polygon) -> Self {
+        let polygon_vertices = polygon.vertices();
+        let n = polygon_vertices.len();
+        let edges = polygon_vertices
+            .iter()
+            .circular_tuple_windows()
+            .map(|(a, b)| {
+                let length = a.distance_to(b);
+                let direction = ((b.x - a.x) / length, (b.y - a.y) / length);
+                let normal = (-direction.1, direction.0);
+                WavefrontEdge {
+                    ids: (a.id, b.id),
+                    direction,
+                    normal,
+                    offset: dot(normal, a.coords()),
+                }
+            })
+            .collect_vec();
+        let nodes = polygon_vertices
+            .iter()
+            .map(|v| SkeletonNode {
+                x: v.x,
+                y: v.y,
+                time: 0.0,
+                vertex: Some(v.id),
+            })
+            .collect_vec();
+        let bbox = polygon.bounding_box();
+        let size = (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+
+        let mut wavefront = Wavefront {
+            edges,
+            vertices: Vec::new(),
+            nodes,
+            arcs: Vec::new(),
+            time: 0.0,
+            tolerance: EVENT_TOLERANCE * size,
+        };
+        for (i, v) in polygon_vertices.iter().enumerate() {
+            let (e_in, e_out) = ((i + n - 1) % n, i);
+            let velocity = wavefront.velocity(e_in, e_out);
+            wavefront.vertices.push(WavefrontVertex {
+                x: v.x,
+                y: v.y,
+                time: 0.0,
+                velocity,
+                e_in,
+                e_out,
+                prev: (i + n - 1) % n,
+                next: (i + 1) % n,
+                node: i,
+                active: true,
+            });
+        }
+        wavefront
+    }
+
+    /// Velocity of a vertex staying on both moving edges. A vertex between
+    /// edges going the same way moves with them, and one between edges
+    /// going opposite ways is where they have met head on and is left in
+    /// place until the events that get rid of it.
+    fn velocity(&self, e_in: usize, e_out: usize) -> (f64, f64) {
+        let n1 = self.edges[e_in].normal;
+        let n2 = self.edges[e_out].normal;
+        let det = cross(n1, n2);
+        if det.abs() < PARALLEL_TOLERANCE {
+            return if dot(n1, n2) > 0.0 { n1 } else { (0.0, 0.0) };
+        }
+        ((n2.1 - n1.1) / det, (n1.0 - n2.0) / det)
+    }
+
+    fn is_reflex(&self, v: &WavefrontVertex) -> bool {
+        cross(self.edges[v.e_in].direction, self.edges[v.e_out].direction) < -PARALLEL_TOLERANCE
+    }
+
+    /// When the edge between `u` and the next vertex shrinks away. Every
+    /// piece of the wavefront through the point of an event is handled at
+    /// once, so new vertices can't have events right away.
+    fn edge_event(&self, u: usize) -> Option<f64> {
+        let u = &self.vertices[u];
+        let w = &self.vertices[u.next];
+        let d = self.edges[u.e_out].direction;
+        let shrinking = dot(w.velocity, d) - dot(u.velocity, d);
+        if shrinking > -PARALLEL_TOLERANCE {
+            return None;
+        }
+        let time = (dot((u.x - w.x, u.y - w.y), d) + dot(w.velocity, d) * w.time
+            - dot(u.velocity, d) * u.time)
+            / shrinking;
+        (time > u.time.max(w.time) + self.tolerance).then_some(time)
+    }
+
+    /// When the reflex vertex `r` runs into the edge between `a` and the
+    /// next vertex, if it hits the edge between them.
+    fn split_event(&self, r: usize, a: usize) -> Option<f64> {
+        let (r, a) = (&self.vertices[r], &self.vertices[a]);
+        let b = &self.vertices[a.next];
+        let edge = &self.edges[a.e_out];
+        let approach = dot(edge.normal, r.velocity) - 1.0;
+        if approach > -PARALLEL_TOLERANCE {
+            return None;
+        }
+        let time =
+            (edge.offset - dot(edge.normal, (r.x, r.y)) + (approach + 1.0) * r.time) / approach;
+        if time < self.time - self.tolerance || time <= r.time + self.tolerance {
+            return None;
+        }
+        let point = r.position(time);
+        let (pa, pb) = (a.position(time), b.position(time));
+        let along = dot((point.0 - pa.0, point.1 - pa.1), edge.direction);
+        let length = dot((pb.0 - pa.0, pb.1 - pa.1), edge.direction);
+        (along >= -self.tolerance && along <= length + self.tolerance).then_some(time)
+    }
+
+    /// The time and point of the next event.
+    fn next_event(&self) -> Option<(f64, (f64, f64))> {
+        let active = (0..self.vertices.len())
+            .filter(|i| self.vertices[*i].active)
+            .collect_vec();
+        let mut next: Option<(f64, usize)> = None;
+        let mut update = |time: f64, v: usize| {
+            if time >= self.time - self.tolerance && next.map_or(true, |(t, _)| time < t) {
+                next = Some((time, v));
+            }
+        };
+        for &u in active.iter() {
+            if let Some(time) = self.edge_event(u) {
+                update(time, u);
+            }
+            if !self.is_reflex(&self.vertices[u]) {
+                continue;
+            }
+            for &a in active.iter() {
+                let v = &self.vertices[a];
+                if a == u || v.next == u {
+                    continue;
+                }
+                if let Some(time) = self.split_event(u, a) {
+                    update(time, u);
+                }
+            }
+        }
+        next.map(|(time, v)| {
+            let time = time.max(self.time);
+            (time, self.vertices[v].position(time))
+        })
+    }
+
+    fn handle_event(&mut self, time: f64, point: (f64, f64)) -> Result<(), SkeletonError> {
+        self.time = time;
+        let near = |p: (f64, f64)| (p.0 - point.0).hypot(p.1 - point.1) <= self.tolerance;
+        let at_point: HashSet<usize> = (0..self.vertices.len())
+            .filter(|i| self.vertices[*i].active && near(self.vertices[*i].position(time)))
+            .collect();
+        trace!("Event at time {time} at {point:?} with vertices {at_point:?}");
+
+        let node = at_point
+            .iter()
+            .map(|i| self.vertices[*i].node)
+            .find(|n| near((self.nodes[*n].x, self.nodes[*n].y)))
+            .unwrap_or_else(|| {
+                self.nodes.push(SkeletonNode {
+                    x: point.0,
+                    y: point.1,
+                    time,
+                    vertex: None,
+                });
+                self.nodes.len() - 1
+            });
+        for &i in at_point.iter().sorted() {
+            let v = &self.vertices[i];
+            if v.node != node {
+                self.arcs.push(SkeletonArc {
+                    from: v.node,
+                    to: node,
+                    edges: [self.edges[v.e_in].ids, self.edges[v.e_out].ids],
+                });
+            }
+        }
+
+        // Pieces of the wavefront through the point, where a vertex whose
+        // whole loop of the wavefront is at the point just collapses
+        let mut chains = Vec::new();
+        for &i in at_point.iter().sorted() {
+            let v = &self.vertices[i];
+            if at_point.contains(&v.prev) {
+                continue;
+            }
+            let mut last = i;
+            while at_point.contains(&self.vertices[last].next) {
+                last = self.vertices[last].next;
+            }
+            chains.push(Chain {
+                e_in: v.e_in,
+                e_out: self.vertices[last].e_out,
+                prev: v.prev,
+                next: self.vertices[last].next,
+            });
+        }
+        for a in 0..self.vertices.len() {
+            let v = &self.vertices[a];
+            if !v.active || at_point.contains(&a) || at_point.contains(&v.next) {
+                continue;
+            }
+            let edge = &self.edges[v.e_out];
+            let (pa, pb) = (v.position(time), self.vertices[v.next].position(time));
+            let along = dot((point.0 - pa.0, point.1 - pa.1), edge.direction);
+            let length = dot((pb.0 - pa.0, pb.1 - pa.1), edge.direction);
+            let off = dot(edge.normal, point) - edge.offset - time;
+            if off.abs() <= self.tolerance && along > 0.0 && along < length {
+                chains.push(Chain {
+                    e_in: v.e_out,
+                    e_out: v.e_out,
+                    prev: a,
+                    next: v.next,
+                });
+            }
+        }
+        for &i in at_point.iter() {
+            self.vertices[i].active = false;
+        }
+        debug!(
+            "Event at time {time} at {point:?} with {} vertices and {} pieces of wavefront",
+            at_point.len(),
+            chains.len()
+        );
+
+        // Around the point each piece leaves along its outgoing edge and
+        // comes back along its incoming edge, with the unswept region in
+        // between. Reconnecting each incoming edge to the closest outgoing
+        // edge clockwise from it keeps the unswept regions apart, where an
+        // outgoing edge going the same way as an incoming edge comes first
+        // since the region between them has been swept away.
+        let mut arms = chains
+            .iter()
+            .enumerate()
+            .flat_map(|(c, chain)| {
+                let (ox, oy) = self.edges[chain.e_out].direction;
+                let (ix, iy) = self.edges[chain.e_in].direction;
+                [(oy.atan2(ox), false, c), ((-iy).atan2(-ix), true, c)]
+            })
+            .sorted_by_key(|(angle, incoming, _)| (OF(*angle), *incoming))
+            .collect_vec();
+        if arms.is_empty() {
+            return Ok(());
+        }
+        let widest_gap = (0..arms.len())
+            .max_by_key(|i| {
+                let next = arms
+                    .get(i + 1)
+                    .map_or(arms[0].0 + 2.0 * std::f64::consts::PI, |a| a.0);
+                OF(next - arms[*i].0)
+            })
+            .unwrap();
+        arms.rotate_left(widest_gap + 1);
+        let start_angle = arms[0].0;
+        for arm in arms.iter_mut().filter(|arm| arm.0 < start_angle) {
+            arm.0 += 2.0 * std::f64::consts::PI;
+        }
+        let mut start = 0;
+        for end in 1..=arms.len() {
+            if end == arms.len() || arms[end].0 - arms[end - 1].0 > ANGLE_TOLERANCE {
+                arms[start..end].sort_by_key(|(_, incoming, _)| *incoming);
+                start = end;
+            }
+        }
+        let first_outgoing = arms
+            .iter()
+            .position(|(_, incoming, _)| !incoming)
+            .unwrap_or(0);
+        arms.rotate_left(first_outgoing);
+
+        let mut outgoing = Vec::new();
+        let mut created = Vec::new();
+        for (_, incoming, c) in arms {
+            if !incoming {
+                outgoing.push(c);
+                continue;
+            }
+            let Some(out) = outgoing.pop() else {
+                return Err(SkeletonError::NumericalFailure(format!(
+                    "wavefront is inconsistent around {point:?} at time {time}"
+                )));
+            };
+            let (into, from) = (&chains[c], &chains[out]);
+            let id = self.vertices.len();
+            let (prev, next) = (into.prev, from.next);
+            self.vertices.push(WavefrontVertex {
+                x: point.0,
+                y: point.1,
+                time,
+                velocity: self.velocity(into.e_in, from.e_out),
+                e_in: into.e_in,
+                e_out: from.e_out,
+                prev,
+                next,
+                node,
+                active: true,
+            });
+            self.vertices[prev].next = id;
+            self.vertices[next].prev = id;
+            created.push(id);
+        }
+
+        // What is left of the wavefront may be a loop with no area, where
+        // edges have met head on along a line
+        for id in created {
+            if !self.vertices[id].active {
+                continue;
+            }
+            let mut lav = vec![id];
+            let mut current = self.vertices[id].next;
+            while current != id {
+                lav.push(current);
+                current = self.vertices[current].next;
+            }
+            let points = lav
+                .iter()
+                .map(|v| self.vertices[*v].position(time))
+                .collect_vec();
+            let area: f64 = points
+                .iter()
+                .circular_tuple_windows()
+                .map(|(p, q)| cross(*p, *q))
+                .sum();
+            let perimeter: f64 = points
+                .iter()
+                .circular_tuple_windows()
+                .map(|(p, q)| (q.0 - p.0).hypot(q.1 - p.1))
+                .sum();
+            if area.abs() <= self.tolerance * perimeter {
+                self.collapse(&lav, time);
+            }
+        }
+        Ok(())
+    }
+
+    /// Collapses a loop of the wavefront with no area onto the line it has
+    /// flattened into. Each piece of the line is an arc between the faces
+    /// of the two wavefront edges that meet along it.
+    fn collapse(&mut self, lav: &[usize], time: f64) {
+        let points = lav
+            .iter()
+            .map(|v| self.vertices[*v].position(time))
+            .collect_vec();
+        let far = points
+            .iter()
+            .max_by_key(|p| OF((p.0 - points[0].0).hypot(p.1 - points[0].1)))
+            .unwrap();
+        let length = (far.0 - points[0].0).hypot(far.1 - points[0].1);
+        let direction = if length > 0.0 {
+            (
+                (far.0 - points[0].0) / length,
+                (far.1 - points[0].1) / length,
+            )
+        } else {
+            (1.0, 0.0)
+        };
+        let along = points
+            .iter()
+            .map(|p| dot((p.0 - points[0].0, p.1 - points[0].1), direction))
+            .collect_vec();
+        debug!("Collapsing wavefront loop {lav:?} at time {time}");
+
+        // Points along the line the vertices end up at, in order
+        let mut stations: Vec<(f64, SkeletonNodeId)> = Vec::new();
+        for i in (0..lav.len()).sorted_by_key(|i| OF(along[*i])) {
+            if stations
+                .last()
+                .is_some_and(|(s, _)| along[i] - s <= self.tolerance)
+            {
+                continue;
+            }
+            let node = lav
+                .iter()
+                .zip(along.iter())
+                .filter(|(_, s)| (**s - along[i]).abs() <= self.tolerance)
+                .map(|(v, _)| self.vertices[*v].node)
+                .find(|n| {
+                    let node = &self.nodes[*n];
+                    (node.x - points[i].0).hypot(node.y - points[i].1) <= self.tolerance
+                })
+                .unwrap_or_else(|| {
+                    self.nodes.push(SkeletonNode {
+                        x: points[i].0,
+                        y: points[i].1,
+                        time,
+                        vertex: None,
+                    });
+                    self.nodes.len() - 1
+                });
+            stations.push((along[i], node));
+        }
+        let station = |s: f64| {
+            stations
+                .iter()
+                .position(|(t, _)| (s - t).abs() <= self.tolerance)
+                .unwrap()
+        };
+
+        let mut covers: Vec<Vec<usize>> = vec![Vec::new(); stations.len()];
+        for (i, &v) in lav.iter().enumerate() {
+            let vertex = &self.vertices[v];
+            let node = stations[station(along[i])].1;
+            if vertex.node != node {
+                self.arcs.push(SkeletonArc {
+                    from: vertex.node,
+                    to: node,
+                    edges: [self.edges[vertex.e_in].ids, self.edges[vertex.e_out].ids],
+                });
+            }
+            let (a, b) = (station(along[i]), station(along[(i + 1) % lav.len()]));
+            for cover in covers.iter_mut().take(a.max(b)).skip(a.min(b)) {
+                if !cover.contains(&vertex.e_out) {
+                    cover.push(vertex.e_out);
+                }
+            }
+        }
+        for (k, cover) in covers.iter().enumerate().take(stations.len() - 1) {
+            let edges = [cover[0], cover[cover.len() - 1]].map(|e| self.edges[e].ids);
+            self.arcs.push(SkeletonArc {
+                from: stations[k].1,
+                to: stations[k + 1].1,
+                edges,
+            });
+        }
+        for &v in lav {
+            self.vertices[v].active = false;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MedialAxisNode {
+    pub x: f64,
+    pub y: f64,
+    /// Radius of the largest circle centered on the node inside the polygon.
+    pub radius: f64,
+}
+
+/// Approximate medial axis of a polygon, the set of centers of circles
+/// inside it touching its boundary in more than one place. For points
+/// sampled densely along the boundary the Voronoi diagram of the samples
+/// converges to the medial axis, other than the Voronoi edges between
+/// neighboring samples which run off to the boundary. The Voronoi vertices
+/// are the circumcenters of the Delaunay triangles, and two of them are
+/// joined across each Delaunay edge, so this keeps the circumcenters inside
+/// the polygon joined across Delaunay edges between samples that aren't
+/// neighbors.
+pub struct MedialAxis {
+    nodes: Vec<MedialAxisNode>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl MedialAxis {
+    /// Samples the boundary at most `spacing` apart, so the radius of each
+    /// node is within `spacing` of its distance to the boundary. The spacing
+    /// must be finite and greater than zero.
+    pub fn from_polygon(polygon: &Polygon, spacing: f64) -> Result<Self, SkeletonError> {
+        if !spacing.is_finite() || spacing <= 0.0 {
+            return Err(SkeletonError::InvalidSpacing(spacing));
+        }
+        info!("Computing medial axis with samples {spacing} apart");
+        let mut samples = Vec::new();
+        for (a, b) in polygon.vertices().into_iter().circular_tuple_windows() {
+            let num_pieces = (a.distance_to(b) / spacing).ceil().max(1.0) as usize;
+            for i in 0..num_pieces {
+                let t = i as f64 / num_pieces as f64;
+                let id = VertexId::from(samples.len());
+                samples.push(Vertex::new(
+                    id,
+                    a.x + t * (b.x - a.x),
+                    a.y + t * (b.y - a.y),
+                ));
+            }
+        }
+        let num_samples = samples.len();
+        let delaunay = Delaunay::from_vertices(samples.iter());
+        let mesh = TriangleMesh::from_triangulation(delaunay.triangulation());
+        debug!(
+            "Delaunay triangulation of {num_samples} samples has {} triangles",
+            mesh.num_triangles()
+        );
+
+        let mut nodes = Vec::new();
+        let mut node_ids = HashMap::new();
+        for t in 0..mesh.num_triangles() {
+            let ids = mesh.triangle(t);
+            let [v1, v2, v3] = [ids.0, ids.1, ids.2].map(|id| &samples[usize::from(id)]);
+            let triangle = Triangle::from_vertices(v1, v2, v3);
+            let (x, y) = triangle.circumcenter();
+            let center = Vertex::new(VertexId::default(), x, y);
+            let centroid = Vertex::new(
+                VertexId::default(),
+                (v1.x + v2.x + v3.x) / 3.0,
+                (v1.y + v2.y + v3.y) / 3.0,
+            );
+            if polygon.contains(&center) && polygon.contains(&centroid) {
+                node_ids.insert(t, nodes.len());
+                nodes.push(MedialAxisNode {
+                    x,
+                    y,
+                    radius: triangle.circumradius(),
+                });
+            }
+        }
+
+        let mut edges = Vec::new();
+        for (a, b) in mesh.diagonals() {
+            let gap = usize::from(a).abs_diff(usize::from(b));
+            if gap <= 1 || gap == num_samples - 1 {
+                continue;
+            }
+            let t1 = mesh.triangle_with_edge(&a, &b).unwrap();
+            let t2 = mesh.triangle_with_edge(&b, &a).unwrap();
+            if let (Some(n1), Some(n2)) = (node_ids.get(&t1), node_ids.get(&t2)) {
+                edges.push((*n1, *n2));
+            }
+        }
+
+        // Only keep nodes on some edge, renumbering them in order
+        let used = edges
+            .iter()
+            .flat_map(|(a, b)| [*a, *b])
+            .sorted()
+            .dedup()
+            .collect_vec();
+        let renumber: HashMap<_, _> = used.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+        let nodes = used.iter().map(|n| nodes[*n].clone()).collect_vec();
+        let edges = edges
+            .into_iter()
+            .map(|(a, b)| (renumber[&a], renumber[&b]))
+            .collect_vec();
+        info!(
+            "Computed medial axis with {} nodes and {} edges",
+            nodes.len(),
+            edges.len()
+        );
+        Ok(MedialAxis { nodes, edges })
+    }
+
+    pub fn nodes(&self) -> &[MedialAxisNode] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[(usize, usize)] {
+        &self.edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_segment::LineSegment;
+    use crate::test_util::*;
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    fn check_skeleton(polygon: &Polygon) -> StraightSkeleton {
+        let skeleton = StraightSkeleton::from_polygon(polygon).unwrap();
+        let bbox = polygon.bounding_box();
+        let tolerance = 1e-6 * (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+
+        // The skeleton is a tree with a leaf at each vertex
+        let nodes = skeleton.nodes();
+        assert_eq!(skeleton.arcs().len(), nodes.len() - 1);
+        let leaves = nodes.iter().filter(|n| n.vertex.is_some()).count();
+        assert_eq!(leaves, polygon.num_vertices());
+
+        // Each node is as far from the lines of the edges its arcs bisect
+        // as the time the wavefront got there
+        for arc in skeleton.arcs() {
+            for id in [arc.from, arc.to] {
+                let node = skeleton.node(id);
+                let p = Vertex::new(VertexId::default(), node.x, node.y);
+                assert!(node.time >= 0.0 && polygon.contains(&p) || node.vertex.is_some());
+                for (a, b) in arc.edges {
+                    let (a, b) = (
+                        polygon.get_vertex(&a).unwrap(),
+                        polygon.get_vertex(&b).unwrap(),
+                    );
+                    let distance = ((b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x))
+                        / LineSegment::from_vertices(a, b).length();
+                    assert_approx_eq!(distance, node.time, tolerance);
+                }
+            }
+        }
+
+        // The faces swept out by the edges tile the polygon
+        let mut area = 0.0;
+        for (a, b) in polygon.vertices().into_iter().circular_tuple_windows() {
+            let face = skeleton.face(&a.id, &b.id).unwrap();
+            area += face
+                .iter()
+                .map(|id| skeleton.node(*id))
+                .circular_tuple_windows()
+                .map(|(p, q)| p.x * q.y - q.x * p.y)
+                .sum::<f64>()
+                / 2.0;
+        }
+        assert_approx_eq!(area, polygon.area(), tolerance * tolerance.sqrt().max(1.0));
+        skeleton
+    }
+
+    fn internal_nodes(skeleton: &StraightSkeleton) -> Vec<(f64, f64, f64)> {
+        skeleton
+            .nodes()
+            .iter()
+            .filter(|n| n.vertex.is_none())
+            .map(|n| (n.x, n.y, n.time))
+            .sorted_by_key(|(x, y, _)| (OF(*x), OF(*y)))
+            .collect_vec()
+    }
+
+    #[apply(all_polygons)]
+    fn test_straight_skeleton(case: PolygonTestCase) {
+        check_skeleton(&case.polygon);
+    }
+
+    #[test]
+    fn test_straight_skeleton_rectangles() {
+        let square = Polygon::from_coords(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]);
+        let skeleton = check_skeleton(&square);
+        assert_eq!(internal_nodes(&skeleton), vec![(2.0, 2.0, 2.0)]);
+
+        // A rectangle has a ridge down the middle
+        let rectangle = Polygon::from_coords(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 2.0), (0.0, 2.0)]);
+        let skeleton = check_skeleton(&rectangle);
+        assert_eq!(
+            internal_nodes(&skeleton),
+            vec![(1.0, 1.0, 1.0), (3.0, 1.0, 1.0)]
+        );
+        let face = skeleton
+            .face(&VertexId::from(0usize), &VertexId::from(1usize))
+            .unwrap();
+        assert_eq!(face.len(), 4);
+
+        // The two arms of an L meet at the reflex corner
+        let coords = vec![
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 2.0),
+            (2.0, 2.0),
+            (2.0, 4.0),
+            (0.0, 4.0),
+        ];
+        let skeleton = check_skeleton(&Polygon::from_coords(coords));
+        assert_eq!(
+            internal_nodes(&skeleton),
+            vec![(1.0, 1.0, 1.0), (1.0, 3.0, 1.0), (3.0, 1.0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn test_straight_skeleton_split_event() {
+        // The reflex vertex at the bottom of the notch runs into the bottom
+        // edge, splitting the wavefront into the two sides of the notch
+        let coords = vec![(0.0, 0.0), (8.0, 0.0), (8.0, 6.0), (4.0, 2.0), (0.0, 6.0)];
+        let skeleton = check_skeleton(&Polygon::from_coords(coords));
+        let split = skeleton
+            .nodes()
+            .iter()
+            .find(|n| n.vertex.is_none() && (n.x - 4.0).abs() < 1e-9)
+            .unwrap();
+        assert_approx_eq!(split.y, split.time);
+    }
+
+    fn boundary_distance(polygon: &Polygon, p: &Vertex) -> f64 {
+        polygon
+            .vertices()
+            .into_iter()
+            .circular_tuple_windows()
+            .map(|(a, b)| {
+                let (dx, dy) = (b.x - a.x, b.y - a.y);
+                let t = ((p.x - a.x) * dx + (p.y - a.y) * dy) / (dx * dx + dy * dy);
+                let t = t.clamp(0.0, 1.0);
+                (p.x - a.x - t * dx).hypot(p.y - a.y - t * dy)
+            })
+            .min_by(f64::total_cmp)
+            .unwrap()
+    }
+
+    #[apply(all_polygons)]
+    fn test_medial_axis(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        let perimeter: f64 = polygon
+            .vertices()
+            .into_iter()
+            .circular_tuple_windows()
+            .map(|(a, b)| a.distance_to(b))
+            .sum();
+        let spacing = perimeter / 500.0;
+        let medial_axis = MedialAxis::from_polygon(polygon, spacing).unwrap();
+        assert!(!medial_axis.edges().is_empty());
+
+        for node in medial_axis.nodes() {
+            let p = Vertex::new(VertexId::default(), node.x, node.y);
+            assert!(polygon.contains(&p));
+            let distance = boundary_distance(polygon, &p);
+            assert!(distance <= node.radius + 1e-9 * perimeter);
+            assert!(node.radius <= distance + spacing);
+        }
+    }
+
+    #[test]
+    fn test_medial_axis_rectangle() {
+        // Away from the ends the medial axis of a long rectangle is its
+        // center line
+        let rectangle =
+            Polygon::from_coords(vec![(0.0, 0.0), (10.0, 0.0), (10.0, 2.0), (0.0, 2.0)]);
+        let medial_axis = MedialAxis::from_polygon(&rectangle, 0.05).unwrap();
+        let middle = medial_axis
+            .nodes()
+            .iter()
+            .filter(|n| n.x > 1.5 && n.x < 8.5)
+            .collect_vec();
+        assert!(!middle.is_empty());
+        for node in middle {
+            assert!((node.y - 1.0).abs() < 0.05);
+            assert!((node.radius - 1.0).abs() < 0.05);
+        }
+    }
+    #[rstest]
+    fn test_medial_axis_invalid_spacing(
+        #[values(0.0, -1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY)] spacing: f64,
+    ) {
+        let square = Polygon::from_coords(square_coords(0.0, 0.0, 1.0));
+        assert!(matches!(
+            MedialAxis::from_polygon(&square, spacing),
+            Err(SkeletonError::InvalidSpacing(_))
+        ));
+    }
+}
@@ -5,7 +5,9 @@ use std::{fs, path::PathBuf};
 use crate::{
     error::FileError,
     polygon::{Polygon, PolygonMetadata},
+    triangulation::TriangleVertexIds,
     util::load_polygon,
+    vertex::{Vertex, VertexId},
 };
 
 pub struct PolygonTestCase {
@@ -19,6 +21,41 @@ impl PolygonTestCase {
     }
 }
 
+/// Vertices at the coordinates, with IDs counting up from 0.
+pub fn vertices_from_coords(coords: &[(f64, f64)]) -> Vec<Vertex> {
+    coords
+        .iter()
+        .enumerate()
+        .map(|(i, (x, y))| Vertex::new(VertexId::from(i), *x, *y))
+        .collect()
+}
+
+/// Corners of an axis-aligned square in CCW order, from its lower left
+/// corner.
+pub fn square_coords(x: f64, y: f64, size: f64) -> Vec<(f64, f64)> {
+    vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)]
+}
+
+/// Deterministic pseudo-random numbers below the bound given to each call,
+/// from a linear congruential generator started at the seed.
+pub fn seeded_random(seed: u64) -> impl FnMut(u64) -> u64 {
+    let mut state = seed;
+    move |bound| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) % bound
+    }
+}
+
+/// Centroid of a triangle of the polygon, which is strictly inside it.
+pub fn triangle_centroid(polygon: &Polygon, ids: &TriangleVertexIds) -> Vertex {
+    let t = polygon.get_triangle(&ids.0, &ids.1, &ids.2).unwrap();
+    let x = (t.v1.x + t.v2.x + t.v3.x) / 3.0;
+    let y = (t.v1.y + t.v2.y + t.v3.y) / 3.0;
+    Vertex::new(VertexId::default(), x, y)
+}
+
 fn load_metadata(name: &str, folder: &str) -> Result<PolygonMetadata, FileError> {
     let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     path.push("polygons");
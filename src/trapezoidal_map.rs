@@ -0,0 +1,874 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use ordered_float::OrderedFloat as OF;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    error::TrapezoidalMapError,
+    geometry::Geometry,
+    line_segment::LineSegment,
+    polygon::Polygon,
+    vertex::{Vertex, VertexId},
+};
+
+// Fixed so that the map, and anything built from it, is reproducible
+const SHUFFLE_SEED: u64 = 0x9E3779B97F4A7C15;
+
+/// Index of a trapezoid in a [`TrapezoidalMap`].
+pub type TrapezoidId = usize;
+
+/// Index of a face of a [`TrapezoidalMap`], which is a connected region of
+/// the plane bounded by its segments.
+pub type FaceId = usize;
+
+/// An edge of the map with its endpoints ordered left to right.
+struct Segment {
+    left: usize,
+    right: usize,
+    // Whether the polygon interior is above the segment, which is the
+    // case when the CCW boundary passes through it left to right
+    inside_above: bool,
+}
+
+/// A trapezoid bounded above and below by segments, and on the left and
+/// right by vertical walls through its left and right points.
+struct Trapezoid {
+    top: usize,
+    bottom: usize,
+    leftp: usize,
+    rightp: usize,
+    node: usize,
+    alive: bool,
+}
+
+enum Node {
+    X {
+        point: usize,
+        left: usize,
+        right: usize,
+    },
+    Y {
+        segment: usize,
+        above: usize,
+        below: usize,
+    },
+    Leaf(TrapezoidId),
+}
+
+/// The part of a vertical wall through a point that borders a trapezoid,
+/// which is cut off at the point wherever a segment ends there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WallSpan {
+    Both,
+    Upper,
+    Lower,
+    Empty,
+}
+
+/// Trapezoidal decomposition of a polygon or a set of segments built by
+/// randomized incremental insertion, along with the DAG search structure
+/// built up while inserting segments that makes it a point location
+/// structure answering queries in expected $O(\log n)$.
+///
+/// For a polygon this is Seidel's algorithm, where edges are inserted in
+/// random order over $\log^* n$ phases. Between phases the polygon boundary
+/// is threaded through the map to find the trapezoid each remaining vertex
+/// is in, so later searches for those vertices start from there rather than
+/// the root of the DAG. This is what brings the expected running time down
+/// to $O(n \log^* n)$. Arbitrary segments have no boundary to thread, so
+/// they take the expected $O(n \log n)$ of de Berg et al.
+///
+/// Points are compared lexicographically by (x, y), which is the same as
+/// shearing the plane very slightly so that no two points share an x
+/// coordinate and vertical edges need no special handling.
+pub struct TrapezoidalMap {
+    points: Vec<Vertex>,
+    num_points: usize,
+    segments: Vec<Segment>,
+    segment_inserted: Vec<bool>,
+    point_inserted: Vec<bool>,
+    trapezoids: Vec<Trapezoid>,
+    nodes: Vec<Node>,
+    // Live trapezoids having each point as their left and right point,
+    // which is at most three on either side of any point of a polygon
+    starting_at: Vec<Vec<TrapezoidId>>,
+    ending_at: Vec<Vec<TrapezoidId>>,
+    // Face of each trapezoid, labeled once all segments are inserted
+    faces: Vec<FaceId>,
+    num_faces: usize,
+}
+
+impl TrapezoidalMap {
+    pub fn from_polygon(polygon: &Polygon) -> Result<Self, TrapezoidalMapError> {
+        info!("Computing trapezoidal map");
+        let points = polygon.vertices().into_iter().cloned().collect_vec();
+        let n = points.len();
+        let segments = (0..n)
+            .map(|i| {
+                let j = (i + 1) % n;
+                let inside_above = lex_less(&points[i], &points[j]);
+                let (left, right) = if inside_above { (i, j) } else { (j, i) };
+                Segment {
+                    left,
+                    right,
+                    inside_above,
+                }
+            })
+            .collect_vec();
+        let mut map = TrapezoidalMap::new(points, segments);
+        let order = shuffled(n, SHUFFLE_SEED);
+
+        // Node of the trapezoid each uninserted vertex was last found in,
+        // for starting searches closer to the leaves of the DAG
+        let mut located = vec![None; n];
+        let mut num_inserted = 0;
+        for phase in 1.. {
+            let phase_end = phase_end(n, phase);
+            debug!("Phase {phase} inserting edges {num_inserted}..{phase_end}");
+            for s in order[num_inserted..phase_end].iter() {
+                map.insert_segment(*s, &located)?;
+            }
+            num_inserted = phase_end;
+            if num_inserted == n {
+                break;
+            }
+            map.thread(&mut located)?;
+        }
+        map.label_faces();
+
+        info!(
+            "Computed trapezoidal map with {} trapezoids",
+            map.num_trapezoids()
+        );
+        Ok(map)
+    }
+
+    /// Builds the map of a set of segments that don't cross, though they
+    /// may share endpoints. No segment may have an endpoint in the interior
+    /// of another. Segments of zero length and repeated segments are
+    /// skipped. Segments that do cross may be caught when they leave the
+    /// map inconsistent, which is returned as an error.
+    pub fn from_segments(segments: &[LineSegment]) -> Result<Self, TrapezoidalMapError> {
+        info!("Computing trapezoidal map of {} segments", segments.len());
+        // Segments sharing an endpoint have to share the point in the map
+        // so that inserting one can find the other from that point
+        let mut points = Vec::new();
+        let mut point_index = HashMap::new();
+        let mut index = |v: &Vertex| {
+            *point_index.entry((OF(v.x), OF(v.y))).or_insert_with(|| {
+                points.push(v.clone());
+                points.len() - 1
+            })
+        };
+        let mut edges = Vec::new();
+        for ls in segments {
+            let (a, b) = (index(ls.v1), index(ls.v2));
+            if a != b {
+                edges.push((a.min(b), a.max(b)));
+            }
+        }
+        let edges = edges
+            .into_iter()
+            .unique()
+            .map(|(a, b)| {
+                let (left, right) = if lex_less(&points[a], &points[b]) {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                Segment {
+                    left,
+                    right,
+                    inside_above: false,
+                }
+            })
+            .collect_vec();
+
+        let mut map = TrapezoidalMap::new(points, edges);
+        let located = vec![None; map.num_points];
+        for s in shuffled(map.segment_inserted.len(), SHUFFLE_SEED) {
+            map.insert_segment(s, &located)?;
+        }
+        map.label_faces();
+
+        info!(
+            "Computed trapezoidal map with {} trapezoids and {} faces",
+            map.num_trapezoids(),
+            map.num_faces
+        );
+        Ok(map)
+    }
+
+    fn new(mut points: Vec<Vertex>, mut segments: Vec<Segment>) -> Self {
+        let n = points.len();
+        let num_segments = segments.len();
+
+        // Corners of a box around the points bound the initial trapezoid,
+        // with the bottom and top of the box as sentinel segments
+        let min_x = points.iter().map(|v| OF(v.x)).min().map_or(0.0, |x| x.0);
+        let max_x = points.iter().map(|v| OF(v.x)).max().map_or(0.0, |x| x.0);
+        let min_y = points.iter().map(|v| OF(v.y)).min().map_or(0.0, |y| y.0);
+        let max_y = points.iter().map(|v| OF(v.y)).max().map_or(0.0, |y| y.0);
+        for (x, y) in [
+            (min_x - 1.0, min_y - 1.0),
+            (max_x + 1.0, min_y - 1.0),
+            (min_x - 1.0, max_y + 1.0),
+            (max_x + 1.0, max_y + 1.0),
+        ] {
+            points.push(Vertex::new(VertexId::default(), x, y));
+        }
+
+        for (left, right) in [(n, n + 1), (n + 2, n + 3)] {
+            segments.push(Segment {
+                left,
+                right,
+                inside_above: false,
+            });
+        }
+
+        let mut map = TrapezoidalMap {
+            points,
+            num_points: n,
+            segment_inserted: vec![false; num_segments],
+            point_inserted: vec![false; n],
+            segments,
+            trapezoids: Vec::new(),
+            nodes: Vec::new(),
+            starting_at: vec![Vec::new(); n + 4],
+            ending_at: vec![Vec::new(); n + 4],
+            faces: Vec::new(),
+            num_faces: 0,
+        };
+        map.add_trapezoid(num_segments + 1, num_segments, n, n + 3);
+        map
+    }
+
+    pub fn num_trapezoids(&self) -> usize {
+        self.trapezoids().count()
+    }
+
+    pub fn trapezoids(&self) -> impl Iterator<Item = TrapezoidId> + '_ {
+        (0..self.trapezoids.len()).filter(|t| self.trapezoids[*t].alive)
+    }
+
+    /// The trapezoid containing the vertex, found by searching the DAG in
+    /// expected $O(\log n)$. Vertices on a segment are placed above it and
+    /// vertices on a wall are placed to its right.
+    pub fn locate(&self, v: &Vertex) -> TrapezoidId {
+        self.locate_from(0, v)
+    }
+
+    /// The face containing the vertex, with the same conventions for
+    /// vertices on segments and walls as [`TrapezoidalMap::locate`].
+    pub fn locate_face(&self, v: &Vertex) -> FaceId {
+        self.face(self.locate(v))
+    }
+
+    pub fn face(&self, id: TrapezoidId) -> FaceId {
+        self.faces[id]
+    }
+
+    pub fn num_faces(&self) -> usize {
+        self.num_faces
+    }
+
+    /// Live trapezoids making up the face.
+    pub fn face_trapezoids(&self, face: FaceId) -> Vec<TrapezoidId> {
+        self.trapezoids()
+            .filter(|t| self.faces[*t] == face)
+            .collect_vec()
+    }
+
+    /// Whether the vertex is inside the polygon the map was built from,
+    /// which is always false for a map of segments.
+    pub fn contains(&self, v: &Vertex) -> bool {
+        self.is_inside(self.locate(v))
+    }
+
+    pub fn is_inside(&self, id: TrapezoidId) -> bool {
+        let t = &self.trapezoids[id];
+        self.segments[t.bottom].inside_above
+    }
+
+    /// Edge bounding the trapezoid from above, which is `None` for
+    /// trapezoids bounded by the box around the polygon or segments.
+    pub fn top_edge(&self, id: TrapezoidId) -> Option<(VertexId, VertexId)> {
+        self.edge(self.trapezoids[id].top)
+    }
+
+    /// Edge bounding the trapezoid from below, which is `None` for
+    /// trapezoids bounded by the box around the polygon or segments.
+    pub fn bottom_edge(&self, id: TrapezoidId) -> Option<(VertexId, VertexId)> {
+        self.edge(self.trapezoids[id].bottom)
+    }
+
+    /// Vertex the left wall of the trapezoid goes through, which is `None`
+    /// for the leftmost trapezoid.
+    pub fn left_vertex(&self, id: TrapezoidId) -> Option<VertexId> {
+        self.vertex_id(self.trapezoids[id].leftp)
+    }
+
+    /// Vertex the right wall of the trapezoid goes through, which is `None`
+    /// for the rightmost trapezoid.
+    pub fn right_vertex(&self, id: TrapezoidId) -> Option<VertexId> {
+        self.vertex_id(self.trapezoids[id].rightp)
+    }
+
+    /// Diagonals between the left and right vertex of each trapezoid inside
+    /// the polygon, skipping those that are polygon edges. These split the
+    /// polygon into pieces that are monotone in x.
+    pub fn monotone_diagonals(&self) -> Vec<(VertexId, VertexId)> {
+        let mut diagonals = HashSet::new();
+        for t in self.trapezoids().filter(|t| self.is_inside(*t)) {
+            let t = &self.trapezoids[t];
+            let is_edge = [t.top, t.bottom].iter().any(|s| {
+                let s = &self.segments[*s];
+                s.left == t.leftp && s.right == t.rightp
+            });
+            if !is_edge {
+                diagonals.insert((self.points[t.leftp].id, self.points[t.rightp].id));
+            }
+        }
+        diagonals.into_iter().sorted().collect_vec()
+    }
+
+    fn edge(&self, s: usize) -> Option<(VertexId, VertexId)> {
+        let s = &self.segments[s];
+        Some((self.vertex_id(s.left)?, self.vertex_id(s.right)?))
+    }
+
+    fn vertex_id(&self, p: usize) -> Option<VertexId> {
+        (p < self.num_points).then(|| self.points[p].id)
+    }
+
+    fn lex_less(&self, a: usize, b: usize) -> bool {
+        lex_less(&self.points[a], &self.points[b])
+    }
+
+    fn above(&self, v: &Vertex, s: usize) -> bool {
+        let s = &self.segments[s];
+        v.left(&LineSegment::from_vertices(
+            &self.points[s.left],
+            &self.points[s.right],
+        ))
+    }
+
+    fn locate_from(&self, mut node: usize, v: &Vertex) -> TrapezoidId {
+        loop {
+            node = match self.nodes[node] {
+                Node::Leaf(t) => return t,
+                Node::X { point, left, right } => {
+                    if lex_less(v, &self.points[point]) {
+                        left
+                    } else {
+                        right
+                    }
+                }
+                Node::Y {
+                    segment,
+                    above,
+                    below,
+                } => {
+                    let s = &self.segments[segment];
+                    let ls =
+                        LineSegment::from_vertices(&self.points[s.left], &self.points[s.right]);
+                    if v.left_on(&ls) {
+                        above
+                    } else {
+                        below
+                    }
+                }
+            }
+        }
+    }
+
+    fn left_span(&self, t: &Trapezoid) -> WallSpan {
+        wall_span(
+            self.segments[t.top].left == t.leftp,
+            self.segments[t.bottom].left == t.leftp,
+        )
+    }
+
+    fn right_span(&self, t: &Trapezoid) -> WallSpan {
+        wall_span(
+            self.segments[t.top].right == t.rightp,
+            self.segments[t.bottom].right == t.rightp,
+        )
+    }
+
+    /// The trapezoid that a segment leaving the inserted point `p` towards
+    /// `q` first passes through, or `None` if none of the trapezoids at `p`
+    /// open towards `q`, which can only happen when the map is inconsistent.
+    fn trapezoid_near(&self, p: usize, q: usize) -> Option<TrapezoidId> {
+        let v = &self.points[q];
+        let (candidates, rightwards) = if self.lex_less(p, q) {
+            (&self.starting_at[p], true)
+        } else {
+            (&self.ending_at[p], false)
+        };
+        candidates.iter().copied().find(|t| {
+            let t = &self.trapezoids[*t];
+            let span = if rightwards {
+                self.left_span(t)
+            } else {
+                self.right_span(t)
+            };
+            let above_bottom = self.above(v, t.bottom);
+            let below_top = !self.above(v, t.top);
+            match span {
+                WallSpan::Both => true,
+                WallSpan::Upper => above_bottom,
+                WallSpan::Lower => below_top,
+                WallSpan::Empty => above_bottom && below_top,
+            }
+        })
+    }
+
+    /// The right neighbor of the trapezoid that the line through the
+    /// points `a` and `b` passes into, or `None` if it has none there.
+    fn step_right(&self, t: TrapezoidId, a: usize, b: usize) -> Option<TrapezoidId> {
+        let r = self.trapezoids[t].rightp;
+        let wanted = self.wanted_span(r, a, b);
+        self.starting_at[r]
+            .iter()
+            .copied()
+            .find(|u| [wanted, WallSpan::Both].contains(&self.left_span(&self.trapezoids[*u])))
+    }
+
+    /// The left neighbor of the trapezoid that the line through the points
+    /// `a` and `b` passes into, or `None` if it has none there.
+    fn step_left(&self, t: TrapezoidId, a: usize, b: usize) -> Option<TrapezoidId> {
+        let l = self.trapezoids[t].leftp;
+        let wanted = self.wanted_span(l, a, b);
+        self.ending_at[l]
+            .iter()
+            .copied()
+            .find(|u| [wanted, WallSpan::Both].contains(&self.right_span(&self.trapezoids[*u])))
+    }
+
+    fn lost(&self, a: usize, b: usize) -> TrapezoidalMapError {
+        TrapezoidalMapError::NumericalFailure(format!(
+            "no trapezoid to follow the segment from {:?} to {:?} through",
+            self.points[a], self.points[b]
+        ))
+    }
+
+    fn wanted_span(&self, wall: usize, a: usize, b: usize) -> WallSpan {
+        let (a, b) = if self.lex_less(a, b) { (a, b) } else { (b, a) };
+        let ls = LineSegment::from_vertices(&self.points[a], &self.points[b]);
+        if self.points[wall].left(&ls) {
+            WallSpan::Lower
+        } else {
+            WallSpan::Upper
+        }
+    }
+
+    fn add_trapezoid(
+        &mut self,
+        top: usize,
+        bottom: usize,
+        leftp: usize,
+        rightp: usize,
+    ) -> TrapezoidId {
+        let id = self.trapezoids.len();
+        self.nodes.push(Node::Leaf(id));
+        self.trapezoids.push(Trapezoid {
+            top,
+            bottom,
+            leftp,
+            rightp,
+            node: self.nodes.len() - 1,
+            alive: true,
+        });
+        self.starting_at[leftp].push(id);
+        self.ending_at[rightp].push(id);
+        id
+    }
+
+    fn add_leaf(&mut self, (top, bottom, leftp, rightp): (usize, usize, usize, usize)) -> usize {
+        let id = self.add_trapezoid(top, bottom, leftp, rightp);
+        self.trapezoids[id].node
+    }
+
+    fn remove_trapezoid(&mut self, id: TrapezoidId) {
+        let t = &mut self.trapezoids[id];
+        t.alive = false;
+        self.starting_at[t.leftp].retain(|u| *u != id);
+        self.ending_at[t.rightp].retain(|u| *u != id);
+    }
+
+    fn insert_segment(
+        &mut self,
+        s: usize,
+        located: &[Option<usize>],
+    ) -> Result<(), TrapezoidalMapError> {
+        let (p, q) = (self.segments[s].left, self.segments[s].right);
+        trace!(p:?=self.points[p].id, q:?=self.points[q].id; "Inserting segment");
+
+        // Find every trapezoid the segment crosses, left to right
+        let first = if self.point_inserted[p] {
+            self.trapezoid_near(p, q).ok_or_else(|| self.lost(p, q))?
+        } else {
+            self.locate_from(located[p].unwrap_or(0), &self.points[p])
+        };
+        let mut crossed = vec![first];
+        while self.lex_less(self.trapezoids[*crossed.last().unwrap()].rightp, q) {
+            let next = self.step_right(*crossed.last().unwrap(), p, q);
+            crossed.push(next.ok_or_else(|| self.lost(p, q))?);
+        }
+        let first = &self.trapezoids[crossed[0]];
+        let last = &self.trapezoids[*crossed.last().unwrap()];
+
+        // New points cut off the ends of the first and last trapezoids
+        let left = (!self.point_inserted[p]).then_some((first.top, first.bottom, first.leftp, p));
+        let right = (!self.point_inserted[q]).then_some((last.top, last.bottom, q, last.rightp));
+
+        // Above and below the segment the crossed trapezoids are split,
+        // and the walls between them are cut back to the segment. Where
+        // a wall is cut away the trapezoids on either side of it merge.
+        let mut uppers = Vec::new();
+        let mut lowers = Vec::new();
+        let mut upper_of = Vec::new();
+        let mut lower_of = Vec::new();
+        let mut upper_start = (first.top, p);
+        let mut lower_start = (first.bottom, p);
+        for (i, t) in crossed.iter().enumerate() {
+            upper_of.push(uppers.len());
+            lower_of.push(lowers.len());
+            let t = &self.trapezoids[*t];
+            if i == crossed.len() - 1 {
+                break;
+            }
+            let next = &self.trapezoids[crossed[i + 1]];
+            if self.above(&self.points[t.rightp], s) {
+                uppers.push((upper_start.0, s, upper_start.1, t.rightp));
+                upper_start = (next.top, t.rightp);
+            } else {
+                lowers.push((s, lower_start.0, lower_start.1, t.rightp));
+                lower_start = (next.bottom, t.rightp);
+            }
+        }
+        uppers.push((upper_start.0, s, upper_start.1, q));
+        lowers.push((s, lower_start.0, lower_start.1, q));
+
+        let old_nodes = crossed
+            .iter()
+            .map(|t| self.trapezoids[*t].node)
+            .collect_vec();
+        for t in crossed.iter() {
+            self.remove_trapezoid(*t);
+        }
+        let left = left.map(|t| self.add_leaf(t));
+        let right = right.map(|t| self.add_leaf(t));
+        let uppers = uppers.into_iter().map(|t| self.add_leaf(t)).collect_vec();
+        let lowers = lowers.into_iter().map(|t| self.add_leaf(t)).collect_vec();
+
+        // The leaf of each crossed trapezoid becomes the root of a small
+        // tree that separates the pieces it was split into
+        let k = crossed.len() - 1;
+        for (i, old) in old_nodes.into_iter().enumerate() {
+            let mut node = Node::Y {
+                segment: s,
+                above: uppers[upper_of[i]],
+                below: lowers[lower_of[i]],
+            };
+            if let (true, Some(right)) = (i == k, right) {
+                self.nodes.push(node);
+                node = Node::X {
+                    point: q,
+                    left: self.nodes.len() - 1,
+                    right,
+                };
+            }
+            if let (0, Some(left)) = (i, left) {
+                self.nodes.push(node);
+                node = Node::X {
+                    point: p,
+                    left,
+                    right: self.nodes.len() - 1,
+                };
+            }
+            self.nodes[old] = node;
+        }
+
+        self.segment_inserted[s] = true;
+        self.point_inserted[p] = true;
+        self.point_inserted[q] = true;
+        Ok(())
+    }
+
+    /// Groups the trapezoids into faces, where trapezoids are in the same
+    /// face when they border each other across a wall. A trapezoid and its
+    /// neighbor to the right share a top or bottom segment, and their walls
+    /// only meet when some of the wall is left between the segments.
+    fn label_faces(&mut self) {
+        let mut faces = vec![usize::MAX; self.trapezoids.len()];
+        let mut num_faces = 0;
+        for start in self.trapezoids().collect_vec() {
+            if faces[start] != usize::MAX {
+                continue;
+            }
+            faces[start] = num_faces;
+            let mut stack = vec![start];
+            while let Some(t) = stack.pop() {
+                let tz = &self.trapezoids[t];
+                let right = self.starting_at[tz.rightp]
+                    .iter()
+                    .filter(|u| self.right_span(tz) != WallSpan::Empty && self.borders(tz, u));
+                let left = self.ending_at[tz.leftp]
+                    .iter()
+                    .filter(|u| self.left_span(tz) != WallSpan::Empty && self.borders(tz, u));
+                for u in right.chain(left) {
+                    if faces[*u] == usize::MAX {
+                        faces[*u] = num_faces;
+                        stack.push(*u);
+                    }
+                }
+            }
+            num_faces += 1;
+        }
+        debug!("Labeled {num_faces} faces");
+        self.faces = faces;
+        self.num_faces = num_faces;
+    }
+
+    fn borders(&self, t: &Trapezoid, u: &TrapezoidId) -> bool {
+        let u = &self.trapezoids[*u];
+        t.top == u.top || t.bottom == u.bottom
+    }
+
+    /// Walks the polygon boundary through the current map, recording the
+    /// trapezoid each uninserted vertex is in. Every uninserted edge is
+    /// followed from one end to the other through the trapezoids it
+    /// crosses, which is linear in expectation.
+    fn thread(&self, located: &mut [Option<usize>]) -> Result<(), TrapezoidalMapError> {
+        let n = self.num_points;
+        let Some(start) = (0..n).find(|i| self.point_inserted[*i]) else {
+            return Ok(());
+        };
+        let mut containing = vec![None; n];
+        for a in (start..n).chain(0..start) {
+            let b = (a + 1) % n;
+            if self.segment_inserted[a] {
+                continue;
+            }
+            let mut t = match containing[a] {
+                Some(t) => t,
+                None => self.trapezoid_near(a, b).ok_or_else(|| self.lost(a, b))?,
+            };
+            if self.lex_less(a, b) {
+                while self.lex_less(self.trapezoids[t].rightp, b) {
+                    t = self.step_right(t, a, b).ok_or_else(|| self.lost(a, b))?;
+                }
+            } else {
+                while self.lex_less(b, self.trapezoids[t].leftp) {
+                    t = self.step_left(t, a, b).ok_or_else(|| self.lost(a, b))?;
+                }
+            }
+            if !self.point_inserted[b] {
+                containing[b] = Some(t);
+                located[b] = Some(self.trapezoids[t].node);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn lex_less(a: &Vertex, b: &Vertex) -> bool {
+    (OF(a.x), OF(a.y)) < (OF(b.x), OF(b.y))
+}
+
+fn wall_span(top_touches: bool, bottom_touches: bool) -> WallSpan {
+    match (top_touches, bottom_touches) {
+        (false, false) => WallSpan::Both,
+        (true, false) => WallSpan::Lower,
+        (false, true) => WallSpan::Upper,
+        (true, true) => WallSpan::Empty,
+    }
+}
+
+/// Number of edges inserted by the end of the phase, $n / \log^{(h)} n$
+/// for phase $h$ where $\log^{(h)}$ is the logarithm iterated $h$ times.
+fn phase_end(n: usize, phase: usize) -> usize {
+    let mut log = n as f64;
+    for _ in 0..phase {
+        log = log.log2();
+    }
+    if log < 1.0 {
+        n
+    } else {
+        ((n as f64 / log).ceil() as usize).min(n)
+    }
+}
+
+/// Fisher-Yates shuffle of `0..n` driven by a xorshift generator.
+fn shuffled(n: usize, seed: u64) -> Vec<usize> {
+    let mut state = seed;
+    let mut order = (0..n).collect_vec();
+    for i in (1..n).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        order.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::triangulation::{EarClipping, TriangulationComputer};
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    #[apply(all_polygons)]
+    fn test_trapezoidal_map(case: PolygonTestCase) {
+        let map = TrapezoidalMap::from_polygon(&case.polygon).unwrap();
+        // Each vertex of a polygon adds a wall that splits off two more
+        // trapezoids from the one the bounding box starts with
+        assert_eq!(map.num_trapezoids(), 2 * case.metadata.num_vertices + 1);
+
+        // Inside trapezoids are always bounded by polygon edges and walls
+        for t in map.trapezoids().filter(|t| map.is_inside(*t)) {
+            assert!(map.top_edge(t).is_some());
+            assert!(map.bottom_edge(t).is_some());
+            assert!(map.left_vertex(t).is_some());
+            assert!(map.right_vertex(t).is_some());
+        }
+
+        // Centroids of the triangles of a triangulation are inside the
+        // polygon and points off of the bounding box are outside
+        for ids in EarClipping.triangulation(&case.polygon).unwrap().iter() {
+            assert!(map.contains(&triangle_centroid(&case.polygon, ids)));
+        }
+        let bbox = case.polygon.bounding_box();
+        for (x, y) in [
+            (bbox.min_x - 0.5, bbox.min_y),
+            (bbox.max_x + 0.5, bbox.max_y),
+            (bbox.min_x, bbox.max_y + 0.5),
+        ] {
+            assert!(!map.contains(&Vertex::new(VertexId::default(), x, y)));
+        }
+
+        // The polygon splits the plane into the inside and outside faces
+        assert_eq!(map.num_faces(), 2);
+        let inside = map.trapezoids().find(|t| map.is_inside(*t)).unwrap();
+        for t in map.trapezoids() {
+            assert_eq!(map.is_inside(t), map.face(t) == map.face(inside));
+        }
+    }
+
+    #[apply(all_polygons)]
+    fn test_trapezoidal_map_from_segments(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        let segments = polygon
+            .edges()
+            .into_iter()
+            .map(|(a, b)| polygon.get_line_segment(&a, &b).unwrap())
+            .collect_vec();
+        let map = TrapezoidalMap::from_segments(&segments).unwrap();
+        let polygon_map = TrapezoidalMap::from_polygon(polygon).unwrap();
+        assert_eq!(map.num_trapezoids(), polygon_map.num_trapezoids());
+        assert_eq!(map.num_faces(), 2);
+
+        // Every point inside the polygon is in the same face, which is the
+        // one the polygon map says is inside
+        let triangulation = EarClipping.triangulation(polygon).unwrap();
+        let centroids = triangulation
+            .iter()
+            .map(|ids| triangle_centroid(polygon, ids))
+            .collect_vec();
+        let inside = map.locate_face(&centroids[0]);
+        for v in centroids.iter() {
+            assert_eq!(map.locate_face(v), inside);
+            let t = polygon_map.locate(v);
+            assert!(polygon_map
+                .face_trapezoids(polygon_map.face(t))
+                .contains(&t));
+        }
+        let bbox = polygon.bounding_box();
+        let outside = Vertex::new(VertexId::default(), bbox.min_x - 0.5, bbox.min_y);
+        assert_ne!(map.locate_face(&outside), inside);
+    }
+
+    #[test]
+    fn test_trapezoidal_map_faces() {
+        let vertex = |id: usize, x, y| Vertex::new(VertexId::from(id), x, y);
+        let vertices = [
+            // Two triangles, one with a segment hanging into it
+            vertex(0, 0.0, 0.0),
+            vertex(1, 4.0, 0.0),
+            vertex(2, 2.0, 4.0),
+            vertex(3, 2.0, 1.0),
+            vertex(4, 5.0, 1.0),
+            vertex(5, 8.0, 1.0),
+            vertex(6, 6.5, 3.0),
+            // A star of segments sharing its center with a vertical one
+            vertex(7, 10.0, 0.0),
+            vertex(8, 10.0, 2.0),
+            vertex(9, 9.0, 1.0),
+            vertex(10, 11.0, 1.0),
+            vertex(11, 10.0, 1.0),
+        ];
+        let v = |i: usize| &vertices[i];
+        let segments = [
+            (0, 1),
+            (1, 2),
+            (2, 0),
+            (2, 3),
+            (4, 5),
+            (5, 6),
+            (6, 4),
+            (7, 11),
+            (8, 11),
+            (9, 11),
+            (10, 11),
+            (11, 10),
+        ]
+        .map(|(a, b)| LineSegment::from_vertices(v(a), v(b)));
+        let map = TrapezoidalMap::from_segments(&segments).unwrap();
+        assert_eq!(map.num_faces(), 3);
+
+        let point = |x, y| Vertex::new(VertexId::default(), x, y);
+        let outside = map.locate_face(&point(-1.0, -1.0));
+        let first = map.locate_face(&point(1.0, 0.5));
+        let second = map.locate_face(&point(6.5, 2.0));
+        assert!(outside != first && outside != second && first != second);
+        // Either side of the hanging segment is the same face
+        assert_eq!(map.locate_face(&point(1.9, 2.0)), first);
+        assert_eq!(map.locate_face(&point(2.1, 2.0)), first);
+        // Points between the arms of the star are all outside
+        for (x, y) in [(9.5, 1.5), (10.5, 1.5), (9.5, 0.5), (10.5, 0.5), (3.0, 3.0)] {
+            assert_eq!(map.locate_face(&point(x, y)), outside);
+        }
+        assert!(!map.contains(&point(1.0, 0.5)));
+    }
+
+    #[test]
+    fn test_inconsistent_map_is_an_error() {
+        // A point marked as inserted without any trapezoids starting or
+        // ending at it leaves nothing to follow a segment through
+        let points = vertices_from_coords(&[(0.0, 0.0), (2.0, 1.0)]);
+        let segment = Segment {
+            left: 0,
+            right: 1,
+            inside_above: false,
+        };
+        let mut map = TrapezoidalMap::new(points, vec![segment]);
+        map.point_inserted[0] = true;
+        assert!(map.trapezoid_near(0, 1).is_none());
+        assert!(matches!(
+            map.insert_segment(0, &[None, None]),
+            Err(TrapezoidalMapError::NumericalFailure(_))
+        ));
+    }
+
+    #[test]
+    fn test_phase_end() {
+        assert_eq!(phase_end(3, 1), 2);
+        assert_eq!(phase_end(3, 2), 3);
+        assert_eq!(phase_end(1000, 1), 101);
+        assert_eq!(phase_end(1000, 4), 1000);
+    }
+}
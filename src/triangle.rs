@@ -3,6 +3,7 @@ use std::{cell::OnceCell, collections::HashSet};
 use crate::{
     geometry::Geometry,
     line_segment::LineSegment,
+    predicates::{incircle, orientation},
     vertex::{Vertex, VertexId},
 };
 
@@ -90,6 +91,60 @@ impl<'a> Triangle<'a> {
         })
     }
 
+    pub fn circumradius(&self) -> f64 {
+        let a = self.v1.distance_to(self.v2);
+        let b = self.v2.distance_to(self.v3);
+        let c = self.v3.distance_to(self.v1);
+        (a * b * c) / (4.0 * self.area().abs())
+    }
+
+    pub fn circumcenter(&self) -> (f64, f64) {
+        let (bx, by) = (self.v2.x - self.v1.x, self.v2.y - self.v1.y);
+        let (cx, cy) = (self.v3.x - self.v1.x, self.v3.y - self.v1.y);
+        let b_lift = bx * bx + by * by;
+        let c_lift = cx * cx + cy * cy;
+        let d = 4.0 * self.area();
+        (
+            self.v1.x + (cy * b_lift - by * c_lift) / d,
+            self.v1.y + (bx * c_lift - cx * b_lift) / d,
+        )
+    }
+
+    /// Ratio of the circumradius to twice the inradius, which is 1 for an
+    /// equilateral triangle and grows without bound as it degenerates.
+    pub fn aspect_ratio(&self) -> f64 {
+        let perimeter = self.v1.distance_to(self.v2)
+            + self.v2.distance_to(self.v3)
+            + self.v3.distance_to(self.v1);
+        let inradius = 2.0 * self.area().abs() / perimeter;
+        self.circumradius() / (2.0 * inradius)
+    }
+
+    /// Interior angles in radians at `v1`, `v2` and `v3` respectively.
+    pub fn angles(&self) -> [f64; 3] {
+        let angle = |v: &Vertex, u: &Vertex, w: &Vertex| {
+            let (ux, uy) = (u.x - v.x, u.y - v.y);
+            let (wx, wy) = (w.x - v.x, w.y - v.y);
+            (ux * wy - uy * wx).abs().atan2(ux * wx + uy * wy)
+        };
+        [
+            angle(self.v1, self.v2, self.v3),
+            angle(self.v2, self.v3, self.v1),
+            angle(self.v3, self.v1, self.v2),
+        ]
+    }
+
+    pub fn min_angle(&self) -> f64 {
+        self.angles().into_iter().fold(f64::MAX, f64::min)
+    }
+
+    pub fn circumcircle_contains(&self, v: &Vertex) -> bool {
+        // The incircle test is positive for points strictly inside the
+        // circumcircle of a CCW triangle, so flip the sign for CW
+        let sign = orientation(self.v1, self.v2, self.v3).signum();
+        incircle(self.v1, self.v2, self.v3, v) * sign > 0.0
+    }
+
     pub fn has_collinear_points(&self) -> bool {
         self.area() == 0.0
     }
@@ -111,7 +166,9 @@ impl<'a> Triangle<'a> {
 mod tests {
     use super::*;
     use crate::vertex::VertexId;
+    use assert_approx_eq::assert_approx_eq;
     use itertools::Itertools;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
 
     #[test]
     fn test_area_right_triangle() {
@@ -157,6 +214,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_circumcircle() {
+        let a = Vertex::new(VertexId::from(0u32), 0.0, 0.0);
+        let b = Vertex::new(VertexId::from(1u32), 4.0, 0.0);
+        let c = Vertex::new(VertexId::from(2u32), 0.0, 4.0);
+        let inside = Vertex::new(VertexId::from(3u32), 3.0, 3.0);
+        let on = Vertex::new(VertexId::from(4u32), 4.0, 4.0);
+        let outside = Vertex::new(VertexId::from(5u32), 5.0, 4.0);
+
+        for triangle in [
+            Triangle::from_vertices(&a, &b, &c),
+            Triangle::from_vertices(&a, &c, &b),
+        ] {
+            assert_approx_eq!(triangle.circumradius(), 8.0f64.sqrt());
+            let (x, y) = triangle.circumcenter();
+            assert_approx_eq!(x, 2.0);
+            assert_approx_eq!(y, 2.0);
+            assert!(triangle.circumcircle_contains(&inside));
+            assert!(!triangle.circumcircle_contains(&on));
+            assert!(!triangle.circumcircle_contains(&outside));
+        }
+    }
+
+    #[test]
+    fn test_angles() {
+        let a = Vertex::new(VertexId::from(0u32), 0.0, 0.0);
+        let b = Vertex::new(VertexId::from(1u32), 4.0, 0.0);
+        let c = Vertex::new(VertexId::from(2u32), 0.0, 4.0);
+        let angles = Triangle::from_vertices(&a, &b, &c).angles();
+        assert_approx_eq!(angles[0], FRAC_PI_2);
+        assert_approx_eq!(angles[1], FRAC_PI_4);
+        assert_approx_eq!(angles[2], FRAC_PI_4);
+        assert_approx_eq!(Triangle::from_vertices(&c, &b, &a).min_angle(), FRAC_PI_4);
+        assert_approx_eq!(
+            Triangle::from_vertices(&a, &b, &c).aspect_ratio(),
+            (1.0 + 2.0f64.sqrt()) / 2.0
+        );
+
+        let d = Vertex::new(VertexId::from(3u32), 2.0, 12.0f64.sqrt());
+        assert_approx_eq!(Triangle::from_vertices(&a, &b, &d).aspect_ratio(), 1.0);
+    }
+
     #[test]
     fn test_area_collinear() {
         let a = Vertex::new(VertexId::from(0u32), 0.0, 0.0);
@@ -1,17 +1,23 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use ordered_float::OrderedFloat as OF;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::f64::consts::PI;
 use std::{fmt, slice::Iter};
 
-use crate::{geometry::Geometry, polygon::Polygon, vertex::VertexId};
+use crate::{
+    data_structure::{OrderedList, Stack},
+    error::TriangulationError,
+    geometry::Geometry,
+    line_segment::LineSegment,
+    polygon::Polygon,
+    predicates::orientation,
+    trapezoidal_map::TrapezoidalMap,
+    triangle::Triangle,
+    vertex::{Vertex, VertexId},
+};
 
-#[derive(Debug, Clone)]
-pub struct EarNotFoundError;
-
-impl fmt::Display for EarNotFoundError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "polygon is likely invalid")
-    }
-}
-
-#[derive(Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct TriangleVertexIds(pub VertexId, pub VertexId, pub VertexId);
 
 #[derive(Default)]
@@ -37,68 +43,1033 @@ impl Triangulation {
     }
 }
 
+/// Why a vertex was rejected as an ear while ear clipping.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EarRejection {
+    /// The vertex is reflex, so the segment between its neighbors is not
+    /// in the cone of the polygon at the vertex.
+    Reflex,
+    /// The vertex is inside or on the triangle formed by the candidate and
+    /// its neighbors, so the segment between the neighbors crosses or
+    /// touches the boundary around that vertex.
+    ContainsVertex(VertexId),
+}
+
+/// An ear candidate as the triangle it would clip, with the candidate in
+/// the middle, and the reason it was rejected.
+#[derive(Clone, Debug)]
+pub struct EarCandidate {
+    pub triangle: TriangleVertexIds,
+    pub rejection: EarRejection,
+}
+
+#[derive(Clone, Debug)]
+pub struct TriangulationTracerStep {
+    /// Candidates tested and rejected before finding the ear, in order.
+    pub rejected: Vec<EarCandidate>,
+    pub ear: VertexId,
+    pub triangle: TriangleVertexIds,
+    /// The polygon left after clipping the ear, in CCW order.
+    pub remaining: Vec<VertexId>,
+}
+
+impl fmt::Display for TriangulationTracerStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for candidate in self.rejected.iter() {
+            writeln!(
+                f,
+                "\tRejected: {:?} ({:?})",
+                candidate.triangle.1, candidate.rejection
+            )?;
+        }
+        writeln!(f, "\tEar: {:?}", self.ear)?;
+        writeln!(f, "\tRemaining Vertices: {:?}", self.remaining)
+    }
+}
+
+#[derive(Default)]
+pub struct TriangulationTracer {
+    pub steps: Vec<TriangulationTracerStep>,
+}
+
+impl fmt::Debug for TriangulationTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(f, "STEP {}:\n{}", i, step)?;
+        }
+        Ok(())
+    }
+}
+
 pub trait TriangulationComputer {
-    fn triangulation(&self, polygon: &Polygon) -> Triangulation;
+    fn triangulation(&self, polygon: &Polygon) -> Result<Triangulation, TriangulationError>;
+}
+
+/// Checks that the polygon can be triangulated at all, so that the
+/// algorithms can assume at least 3 vertices with finite coordinates in
+/// CCW order. Polygons are validated on construction but can be modified
+/// into a degenerate state afterwards.
+pub fn check_triangulation_input(polygon: &Polygon) -> Result<(), TriangulationError> {
+    let num_vertices = polygon.num_vertices();
+    if num_vertices < 3 {
+        return Err(TriangulationError::DegenerateInput(format!(
+            "polygon has {num_vertices} vertices"
+        )));
+    }
+    if let Some(v) = polygon
+        .vertices()
+        .into_iter()
+        .find(|v| !v.x.is_finite() || !v.y.is_finite())
+    {
+        return Err(TriangulationError::DegenerateInput(format!(
+            "vertex has coordinates that are not finite: {v:?}"
+        )));
+    }
+    let area = polygon.area();
+    if area <= 0.0 {
+        return Err(TriangulationError::DegenerateInput(format!(
+            "polygon area must be positive, area={area}"
+        )));
+    }
+    Ok(())
 }
 
+/// Checks that a triangulation has the $n - 2$ triangles every
+/// triangulation of a polygon with $n$ vertices has, which may not be the
+/// case when rounding error led an algorithm astray.
+fn check_num_triangles(
+    polygon: &Polygon,
+    triangulation: &Triangulation,
+) -> Result<(), TriangulationError> {
+    let expected = polygon.num_vertices() - 2;
+    if triangulation.len() != expected {
+        return Err(TriangulationError::NumericalFailure(format!(
+            "expected {expected} triangles but computed {}",
+            triangulation.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Triangulation by repeatedly clipping ears off of the polygon. Whether
+/// each vertex is an ear is cached and only rechecked for the neighbors of
+/// a clipped ear, and ears are only checked against the non-convex vertices
+/// since only they can poke into an ear, giving $O(n^2)$ in the worst case.
 #[derive(Default)]
 pub struct EarClipping;
 
-impl EarClipping {
-    fn find_ear(&self, polygon: &Polygon) -> Result<VertexId, EarNotFoundError> {
-        for v in polygon.vertices() {
-            let prev = polygon.get_prev_vertex(&v.id).unwrap();
-            let next = polygon.get_next_vertex(&v.id).unwrap();
-            if polygon.diagonal(prev, next) {
-                return Ok(v.id);
+/// Ear clipping as in [`EarClipping`], but with the non-convex vertices
+/// indexed along a z-order curve, as in earcut, so that checking an ear
+/// only visits the vertices near its bounding box. This is close to
+/// linear in practice.
+#[derive(Default)]
+pub struct ZOrderEarClipping;
+
+/// The polygon being clipped as a linked list over local indices, so that
+/// clipping an ear does not have to touch a [`Polygon`].
+struct EarClippingState<'a> {
+    vertices: Vec<&'a Vertex>,
+    prev: Vec<usize>,
+    next: Vec<usize>,
+    is_ear: Vec<bool>,
+    // Non-convex vertices keyed by their z-order code, which is always 0
+    // when hashing is disabled so that every query visits all of them
+    candidates: BTreeSet<(u32, usize)>,
+    z_transform: Option<(f64, f64, f64)>,
+}
+
+impl<'a> EarClippingState<'a> {
+    fn new(polygon: &'a Polygon, z_order_hash: bool) -> Self {
+        let vertices = polygon.vertices();
+        let n = vertices.len();
+        let index: HashMap<VertexId, usize> = vertices
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v.id, i))
+            .collect();
+        let prev = vertices
+            .iter()
+            .map(|v| index[&polygon.prev_vertex_id(&v.id).unwrap()])
+            .collect_vec();
+        let next = vertices
+            .iter()
+            .map(|v| index[&polygon.next_vertex_id(&v.id).unwrap()])
+            .collect_vec();
+
+        // Scale coordinates into 15 bits so two of them interleave into a
+        // single z-order code
+        let z_transform = z_order_hash.then(|| {
+            let bbox = polygon.bounding_box();
+            let size = (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+            (bbox.min_x, bbox.min_y, 32767.0 / size)
+        });
+
+        let mut state = EarClippingState {
+            vertices,
+            prev,
+            next,
+            is_ear: vec![false; n],
+            candidates: BTreeSet::new(),
+            z_transform,
+        };
+        for i in 0..n {
+            if !state.is_convex(i) {
+                state
+                    .candidates
+                    .insert((state.z_code(state.vertices[i].x, state.vertices[i].y), i));
+            }
+        }
+        for i in 0..n {
+            state.is_ear[i] = state.check_ear(i);
+        }
+        state
+    }
+
+    fn is_convex(&self, i: usize) -> bool {
+        self.triangle(i).area() > 0.0
+    }
+
+    fn triangle(&self, i: usize) -> Triangle<'_> {
+        Triangle::from_vertices(
+            self.vertices[self.prev[i]],
+            self.vertices[i],
+            self.vertices[self.next[i]],
+        )
+    }
+
+    fn check_ear(&self, i: usize) -> bool {
+        self.ear_rejection(i).is_none()
+    }
+
+    /// A convex vertex is an ear if no other vertex is inside or on the
+    /// triangle it forms with its neighbors. Any vertex that pokes into
+    /// that triangle means a non-convex vertex does too, so those are the
+    /// only ones that need checking.
+    fn ear_rejection(&self, i: usize) -> Option<EarRejection> {
+        if !self.is_convex(i) {
+            return Some(EarRejection::Reflex);
+        }
+        let triangle = self.triangle(i);
+        let min_x = triangle.v1.x.min(triangle.v2.x).min(triangle.v3.x);
+        let max_x = triangle.v1.x.max(triangle.v2.x).max(triangle.v3.x);
+        let min_y = triangle.v1.y.min(triangle.v2.y).min(triangle.v3.y);
+        let max_y = triangle.v1.y.max(triangle.v2.y).max(triangle.v3.y);
+
+        // Every point in the bounding box has a z-order code between the
+        // codes of its corners, so only that range needs to be visited
+        let min_z = self.z_code(min_x, min_y);
+        let max_z = self.z_code(max_x, max_y);
+        let (prev, next) = (self.prev[i], self.next[i]);
+        self.candidates
+            .range((min_z, 0)..=(max_z, usize::MAX))
+            .map(|(_, j)| self.vertices[*j])
+            .filter(|v| v.id != self.vertices[prev].id && v.id != self.vertices[next].id)
+            .find(|v| {
+                min_x <= v.x && v.x <= max_x && min_y <= v.y && v.y <= max_y && triangle.contains(v)
+            })
+            .map(|v| EarRejection::ContainsVertex(v.id))
+    }
+
+    fn triangle_ids(&self, i: usize) -> TriangleVertexIds {
+        TriangleVertexIds(
+            self.vertices[self.prev[i]].id,
+            self.vertices[i].id,
+            self.vertices[self.next[i]].id,
+        )
+    }
+
+    fn z_code(&self, x: f64, y: f64) -> u32 {
+        match self.z_transform {
+            Some((min_x, min_y, scale)) => {
+                z_order(((x - min_x) * scale) as u32, ((y - min_y) * scale) as u32)
             }
+            None => 0,
+        }
+    }
+
+    /// Clips the ear at `i` and rechecks its neighbors, returning the
+    /// clipped triangle.
+    fn clip(&mut self, i: usize) -> TriangleVertexIds {
+        let (prev, next) = (self.prev[i], self.next[i]);
+        let triangle = self.triangle_ids(i);
+        self.next[prev] = next;
+        self.prev[next] = prev;
+
+        // Clipping only ever shrinks the interior angle at the neighbors,
+        // so they can only go from non-convex to convex and never back
+        for j in [prev, next] {
+            if self.is_convex(j) {
+                self.candidates
+                    .remove(&(self.z_code(self.vertices[j].x, self.vertices[j].y), j));
+            }
+        }
+        self.is_ear[prev] = self.check_ear(prev);
+        self.is_ear[next] = self.check_ear(next);
+        triangle
+    }
+
+    /// Candidates walked past from `start` to get to the ear at `ear`.
+    fn rejected_from(&self, start: usize, ear: usize) -> Vec<EarCandidate> {
+        let mut rejected = Vec::new();
+        let mut i = start;
+        while i != ear {
+            rejected.push(EarCandidate {
+                triangle: self.triangle_ids(i),
+                rejection: self.ear_rejection(i).unwrap(),
+            });
+            i = self.next[i];
+        }
+        rejected
+    }
+
+    /// Vertices still in the linked list, in CCW order from `start`.
+    fn remaining(&self, start: usize) -> Vec<Vertex> {
+        let mut remaining = vec![self.vertices[start].clone()];
+        let mut i = self.next[start];
+        while i != start {
+            remaining.push(self.vertices[i].clone());
+            i = self.next[i];
+        }
+        remaining
+    }
+}
+
+/// Interleaves the bits of the coordinates, which must fit in 15 bits, to
+/// get the position along a z-order curve.
+fn z_order(x: u32, y: u32) -> u32 {
+    let spread = |mut v: u32| {
+        v = (v | (v << 8)) & 0x00FF00FF;
+        v = (v | (v << 4)) & 0x0F0F0F0F;
+        v = (v | (v << 2)) & 0x33333333;
+        (v | (v << 1)) & 0x55555555
+    };
+    spread(x) | (spread(y) << 1)
+}
+
+fn find_ear(state: &EarClippingState, start: usize) -> Result<usize, TriangulationError> {
+    let mut i = start;
+    loop {
+        if state.is_ear[i] {
+            return Ok(i);
+        }
+        i = state.next[i];
+        if i == start {
+            return Err(TriangulationError::EarNotFound(state.remaining(start)));
         }
-        Err(EarNotFoundError)
+    }
+}
+
+/// Triangulates the polygon by ear clipping, recording every ear clipped
+/// and the candidates rejected on the way to it when a tracer is given.
+fn clip_ears(
+    polygon: &Polygon,
+    z_order_hash: bool,
+    tracer: &mut Option<TriangulationTracer>,
+) -> Result<Triangulation, TriangulationError> {
+    info!("Computing triangulation by ear clipping");
+    check_triangulation_input(polygon)?;
+    let mut triangulation = Triangulation::default();
+    let mut state = EarClippingState::new(polygon, z_order_hash);
+
+    let mut current = 0;
+    for _ in 0..(polygon.num_vertices() - 3) {
+        let id = find_ear(&state, current)?;
+        let rejected = tracer.is_some().then(|| state.rejected_from(current, id));
+        triangulation.push(state.clip(id));
+        current = state.next[id];
+        if let (Some(t), Some(rejected)) = (tracer.as_mut(), rejected) {
+            t.steps.push(TriangulationTracerStep {
+                rejected,
+                ear: state.vertices[id].id,
+                triangle: *triangulation.iter().last().unwrap(),
+                remaining: state.remaining(current).iter().map(|v| v.id).collect(),
+            });
+        }
+    }
+    // At this stage there should be exactly 3 vertices left,
+    // which form the final triangle of the triangulation
+    triangulation.push(state.triangle_ids(current));
+    if let Some(t) = tracer.as_mut() {
+        t.steps.push(TriangulationTracerStep {
+            rejected: Vec::new(),
+            ear: state.vertices[current].id,
+            triangle: state.triangle_ids(current),
+            remaining: Vec::new(),
+        });
+    }
+    info!(
+        "Computed triangulation with {} triangles",
+        triangulation.len()
+    );
+    Ok(triangulation)
+}
+
+impl EarClipping {
+    /// Triangulates the polygon, recording every ear clipped and the
+    /// candidates rejected on the way to it when a tracer is given.
+    pub fn triangulation_with_tracer(
+        &self,
+        polygon: &Polygon,
+        tracer: &mut Option<TriangulationTracer>,
+    ) -> Result<Triangulation, TriangulationError> {
+        clip_ears(polygon, false, tracer)
+    }
+}
+
+impl ZOrderEarClipping {
+    /// Triangulates the polygon, recording every ear clipped and the
+    /// candidates rejected on the way to it when a tracer is given.
+    pub fn triangulation_with_tracer(
+        &self,
+        polygon: &Polygon,
+        tracer: &mut Option<TriangulationTracer>,
+    ) -> Result<Triangulation, TriangulationError> {
+        clip_ears(polygon, true, tracer)
     }
 }
 
 impl TriangulationComputer for EarClipping {
-    fn triangulation(&self, polygon: &Polygon) -> Triangulation {
+    fn triangulation(&self, polygon: &Polygon) -> Result<Triangulation, TriangulationError> {
+        self.triangulation_with_tracer(polygon, &mut None)
+    }
+}
+
+impl TriangulationComputer for ZOrderEarClipping {
+    fn triangulation(&self, polygon: &Polygon) -> Result<Triangulation, TriangulationError> {
+        self.triangulation_with_tracer(polygon, &mut None)
+    }
+}
+
+/// Type of a vertex for a sweep from top to bottom, depending on whether
+/// its neighbors are above or below it and whether it is convex.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SweepVertexType {
+    /// Convex with both neighbors below, where the polygon starts.
+    Start,
+    /// Reflex with both neighbors below, where the polygon splits in two.
+    Split,
+    /// Convex with both neighbors above, where the polygon ends.
+    End,
+    /// Reflex with both neighbors above, where two parts of the polygon
+    /// merge into one.
+    Merge,
+    /// On the left chain, with the interior to its right.
+    RegularLeft,
+    /// On the right chain, with the interior to its left.
+    RegularRight,
+}
+
+/// Triangulation in $O(n \log n)$ by first partitioning the polygon into
+/// y-monotone pieces with a plane sweep, then triangulating each piece in
+/// linear time with a stack.
+#[derive(Default)]
+pub struct MonotonePartition;
+
+impl MonotonePartition {
+    /// Sweep order from top to bottom, where vertices at the same height
+    /// are ordered left to right. Breaking ties this way is the same as
+    /// rotating the polygon very slightly, so no two vertices are ever at
+    /// the same height as far as the sweep is concerned.
+    fn sweep_key(v: &Vertex) -> (OF<f64>, OF<f64>) {
+        (OF(-v.y), OF(v.x))
+    }
+
+    fn below(a: &Vertex, b: &Vertex) -> bool {
+        Self::sweep_key(a) > Self::sweep_key(b)
+    }
+
+    /// Types of all vertices of the polygon for a sweep from top to bottom.
+    /// The polygon is y-monotone as far as the sweep is concerned when
+    /// none of them are split or merge vertices.
+    pub fn vertex_types(&self, polygon: &Polygon) -> HashMap<VertexId, SweepVertexType> {
+        polygon
+            .vertices()
+            .into_iter()
+            .map(|v| (v.id, self.vertex_type(polygon, v)))
+            .collect()
+    }
+
+    /// Partitions the polygon into y-monotone pieces, by adding a diagonal
+    /// at every split and merge vertex. The pieces keep the vertex IDs of
+    /// the polygon.
+    pub fn monotone_pieces(&self, polygon: &Polygon) -> Result<Vec<Polygon>, TriangulationError> {
+        info!("Computing monotone partition");
+        check_triangulation_input(polygon)?;
+        let pieces = split_by_diagonals(polygon, &self.monotone_diagonals(polygon)?)
+            .into_iter()
+            .map(|piece| polygon.get_polygon(piece, false, false))
+            .collect_vec();
+        info!("Computed monotone partition with {} pieces", pieces.len());
+        Ok(pieces)
+    }
+
+    fn vertex_type(&self, polygon: &Polygon, v: &Vertex) -> SweepVertexType {
+        let prev = polygon.get_prev_vertex(&v.id).unwrap();
+        let next = polygon.get_next_vertex(&v.id).unwrap();
+        let convex = Triangle::from_vertices(prev, v, next).area() > 0.0;
+        match (Self::below(prev, v), Self::below(next, v)) {
+            (true, true) if convex => SweepVertexType::Start,
+            (true, true) => SweepVertexType::Split,
+            (false, false) if convex => SweepVertexType::End,
+            (false, false) => SweepVertexType::Merge,
+            // Going CCW the left chain is walked downwards, so the
+            // interior of the polygon is to the right of these vertices
+            (false, true) => SweepVertexType::RegularLeft,
+            (true, false) => SweepVertexType::RegularRight,
+        }
+    }
+
+    /// Diagonals that split the polygon into y-monotone pieces, found by
+    /// sweeping downwards and connecting every split vertex up to and
+    /// every merge vertex down to a helper vertex.
+    fn monotone_diagonals(
+        &self,
+        polygon: &Polygon,
+    ) -> Result<Vec<(VertexId, VertexId)>, TriangulationError> {
+        let events = polygon
+            .vertices()
+            .into_iter()
+            .sorted_by_key(|v| Self::sweep_key(v))
+            .collect_vec();
+        let types = self.vertex_types(polygon);
+
+        // The sweep status holds the edges crossing the sweep line that
+        // have the polygon interior to their right, ordered left to right.
+        // Edges are identified by their source vertex ID, and since edges
+        // never cross their order only changes on insert and remove, so
+        // the status can be kept in a balanced tree that is searched by
+        // which side of each edge the event vertex is on.
+        let mut status: OrderedList<VertexId> = OrderedList::new();
+        let mut helper: HashMap<VertexId, VertexId> = HashMap::new();
+        let mut diagonals = Vec::new();
+
+        let edge_left_of = |e: &VertexId, v: &Vertex| {
+            let src = polygon.get_vertex(e).unwrap();
+            let dst = polygon.get_next_vertex(e).unwrap();
+            v.left(&LineSegment::from_vertices(src, dst))
+        };
+        let is_merge = |id: &VertexId| types[id] == SweepVertexType::Merge;
+        let out_of_order = |v: &Vertex| {
+            TriangulationError::NumericalFailure(format!("sweep status out of order at {v:?}"))
+        };
+
+        for v in events {
+            let prev = polygon.prev_vertex_id(&v.id).unwrap();
+            let vertex_type = types[&v.id];
+            trace!(v:?, vertex_type:?; "Sweep event");
+
+            // End, merge and left regular vertices end the edge coming
+            // into them, connecting down to its helper first if that was
+            // a merge vertex
+            if matches!(
+                vertex_type,
+                SweepVertexType::End | SweepVertexType::Merge | SweepVertexType::RegularLeft
+            ) {
+                let h = helper.remove(&prev).ok_or_else(|| out_of_order(v))?;
+                if is_merge(&h) {
+                    diagonals.push((v.id, h));
+                }
+                let e = status
+                    .partition_point(|e| edge_left_of(&status[e], v))
+                    .filter(|e| status[*e] == prev)
+                    .ok_or_else(|| out_of_order(v))?;
+                status.remove(e);
+            }
+
+            // Split, merge and right regular vertices become the helper of
+            // the edge directly to their left. Split vertices always connect
+            // up to the old helper, the others only if it was a merge vertex.
+            if matches!(
+                vertex_type,
+                SweepVertexType::Split | SweepVertexType::Merge | SweepVertexType::RegularRight
+            ) {
+                let right = status.partition_point(|e| edge_left_of(&status[e], v));
+                let left = match right {
+                    Some(right) => status.prev(right),
+                    None => status.last(),
+                };
+                let left = left.ok_or_else(|| out_of_order(v))?;
+                let h = helper
+                    .insert(status[left], v.id)
+                    .ok_or_else(|| out_of_order(v))?;
+                if vertex_type == SweepVertexType::Split || is_merge(&h) {
+                    diagonals.push((v.id, h));
+                }
+            }
+
+            // Start, split and left regular vertices begin a new edge that
+            // has the interior to its right
+            if matches!(
+                vertex_type,
+                SweepVertexType::Start | SweepVertexType::Split | SweepVertexType::RegularLeft
+            ) {
+                let right = status.partition_point(|e| edge_left_of(&status[e], v));
+                status.insert_before(right, v.id);
+                helper.insert(v.id, v.id);
+            }
+        }
+
+        debug!("Monotone partition diagonals: {diagonals:?}");
+        Ok(diagonals)
+    }
+}
+
+impl TriangulationComputer for MonotonePartition {
+    fn triangulation(&self, polygon: &Polygon) -> Result<Triangulation, TriangulationError> {
+        info!("Computing triangulation by monotone partition");
+        check_triangulation_input(polygon)?;
+        let mut triangulation = Triangulation::default();
+        for piece in split_by_diagonals(polygon, &self.monotone_diagonals(polygon)?) {
+            triangulate_monotone(polygon, &piece, Self::sweep_key, &mut triangulation);
+        }
+        check_num_triangles(polygon, &triangulation)?;
+        info!(
+            "Computed triangulation with {} triangles",
+            triangulation.len()
+        );
+        Ok(triangulation)
+    }
+}
+
+/// Splits the polygon along non-crossing diagonals into pieces, each given
+/// as vertex IDs in CCW order.
+fn split_by_diagonals(polygon: &Polygon, diagonals: &[(VertexId, VertexId)]) -> Vec<Vec<VertexId>> {
+    // Every piece is a face of the planar graph made of the polygon
+    // edges and the diagonals. Faces are traced by always leaving a
+    // vertex on the first edge clockwise from the one arrived on, so
+    // sort the neighbors of each vertex by angle to look that up.
+    let mut half_edges = polygon.edges().into_iter().collect_vec();
+    half_edges.extend(diagonals.iter().flat_map(|(a, b)| [(*a, *b), (*b, *a)]));
+    let mut neighbors: HashMap<VertexId, Vec<VertexId>> = HashMap::new();
+    for (a, b) in half_edges.iter() {
+        neighbors.entry(*a).or_default().push(*b);
+        neighbors.entry(*b).or_default().push(*a);
+    }
+    let mut position: HashMap<(VertexId, VertexId), usize> = HashMap::new();
+    for (id, ids) in neighbors.iter_mut() {
+        let v = polygon.get_vertex(id).unwrap();
+        ids.sort_by_key(|w| {
+            let w = polygon.get_vertex(w).unwrap();
+            OF((w.y - v.y).atan2(w.x - v.x).rem_euclid(2.0 * PI))
+        });
+        ids.dedup();
+        for (i, w) in ids.iter().enumerate() {
+            position.insert((*id, *w), i);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut pieces = Vec::new();
+    for (a, b) in half_edges.into_iter().sorted() {
+        if visited.contains(&(a, b)) {
+            continue;
+        }
+        let mut piece = Vec::new();
+        let (mut u, mut v) = (a, b);
+        while visited.insert((u, v)) {
+            piece.push(u);
+            let ids = &neighbors[&v];
+            let w = ids[(position[&(v, u)] + ids.len() - 1) % ids.len()];
+            (u, v) = (v, w);
+        }
+        trace!("Monotone piece: {piece:?}");
+        pieces.push(piece);
+    }
+    pieces
+}
+
+/// Triangulates a piece that is monotone with respect to the sweep order
+/// given by `sweep_key`, by sweeping over it and fanning out from each
+/// vertex to the vertices on the stack it can see.
+fn triangulate_monotone(
+    polygon: &Polygon,
+    piece: &[VertexId],
+    sweep_key: fn(&Vertex) -> (OF<f64>, OF<f64>),
+    triangulation: &mut Triangulation,
+) {
+    let n = piece.len();
+    let vertices = polygon.get_vertices(piece.iter().copied());
+    let top = (0..n).min_by_key(|i| sweep_key(&vertices[*i])).unwrap();
+    let bottom = (0..n).max_by_key(|i| sweep_key(&vertices[*i])).unwrap();
+
+    // Going CCW from the first vertex in sweep order walks one chain to
+    // the last vertex, and the rest of the vertices make up the other
+    let on_left = |i: usize| (i + n - top) % n < (bottom + n - top) % n;
+    let sorted = (0..n)
+        .sorted_by_key(|i| sweep_key(&vertices[*i]))
+        .collect_vec();
+
+    let mut push_triangle = |a: usize, b: usize, c: usize| {
+        let (v1, v2, v3) = (&vertices[a], &vertices[b], &vertices[c]);
+        if Triangle::from_vertices(v1, v2, v3).area() < 0.0 {
+            triangulation.push(TriangleVertexIds(v1.id, v3.id, v2.id));
+        } else {
+            triangulation.push(TriangleVertexIds(v1.id, v2.id, v3.id));
+        }
+    };
+    let convex = |a: usize, b: usize, c: usize| {
+        Triangle::from_vertices(&vertices[a], &vertices[b], &vertices[c]).area() > 0.0
+    };
+
+    let mut stack = Stack::with_name("monotone".to_string());
+    stack.push(sorted[0]);
+    stack.push(sorted[1]);
+    for &j in sorted.iter().take(n - 1).skip(2) {
+        let top = *stack.last().unwrap();
+        if on_left(j) != on_left(top) {
+            // On the opposite chain to the stack, so every vertex on
+            // the stack is visible and the whole stack is fanned
+            for (a, b) in stack.iter().tuple_windows() {
+                push_triangle(j, *a, *b);
+            }
+            stack.clear();
+            stack.push(top);
+            stack.push(j);
+        } else {
+            // On the same chain, so only cut off triangles for as long
+            // as the stack vertex being cut off is convex
+            let mut last = stack.pop().unwrap();
+            while let Some(&s) = stack.last() {
+                let visible = if on_left(j) {
+                    convex(s, last, j)
+                } else {
+                    convex(j, last, s)
+                };
+                if !visible {
+                    break;
+                }
+                push_triangle(j, last, s);
+                last = stack.pop().unwrap();
+            }
+            stack.push(last);
+            stack.push(j);
+        }
+    }
+    for (a, b) in stack.iter().tuple_windows() {
+        push_triangle(sorted[n - 1], *a, *b);
+    }
+}
+
+/// Seidel's triangulation, which builds a [`TrapezoidalMap`] of the polygon
+/// in expected $O(n \log^* n)$ and then uses it to split the polygon into
+/// x-monotone pieces that are triangulated in linear time.
+#[derive(Default)]
+pub struct Seidel;
+
+impl Seidel {
+    /// Triangulates the polygon, also returning the trapezoidal map it was
+    /// computed from so that it can be used for point location afterwards.
+    pub fn triangulation_with_map(
+        &self,
+        polygon: &Polygon,
+    ) -> Result<(Triangulation, TrapezoidalMap), TriangulationError> {
+        info!("Computing triangulation by Seidel's algorithm");
+        check_triangulation_input(polygon)?;
+        let map = TrapezoidalMap::from_polygon(polygon)?;
         let mut triangulation = Triangulation::default();
-        let mut polygon = polygon.clone();
+        for piece in split_by_diagonals(polygon, &map.monotone_diagonals()) {
+            triangulate_monotone(polygon, &piece, |v| (OF(v.x), OF(v.y)), &mut triangulation);
+        }
+        check_num_triangles(polygon, &triangulation)?;
+        info!(
+            "Computed triangulation with {} triangles",
+            triangulation.len()
+        );
+        Ok((triangulation, map))
+    }
+}
+
+impl TriangulationComputer for Seidel {
+    fn triangulation(&self, polygon: &Polygon) -> Result<Triangulation, TriangulationError> {
+        Ok(self.triangulation_with_map(polygon)?.0)
+    }
+}
+
+/// What an [`OptimalTriangulation`] optimizes for.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TriangulationObjective {
+    /// Minimize the total length of the diagonals, the minimum weight
+    /// triangulation.
+    #[default]
+    MinWeight,
+    /// Maximize the smallest angle over all triangles.
+    MaxMinAngle,
+    /// Minimize the largest [`Triangle::aspect_ratio`] over all triangles.
+    MinMaxAspectRatio,
+}
+
+impl TriangulationObjective {
+    /// Value of the objective for a triangulation of the polygon, which is
+    /// the total diagonal length, the minimum angle in radians or the
+    /// maximum aspect ratio respectively.
+    pub fn evaluate(&self, polygon: &Polygon, triangulation: &Triangulation) -> f64 {
+        let edges = polygon.edges();
+        let triangles = triangulation
+            .iter()
+            .map(|ids| polygon.get_triangle(&ids.0, &ids.1, &ids.2).unwrap());
+        match self {
+            TriangulationObjective::MinWeight => {
+                // Every diagonal is an edge of two triangles
+                let total: f64 = triangles
+                    .flat_map(|t| {
+                        t.to_line_segments()
+                            .into_iter()
+                            .filter(|ls| !edges.contains(&(ls.v1.id, ls.v2.id)))
+                            .map(|ls| ls.length())
+                            .collect_vec()
+                    })
+                    .sum();
+                total / 2.0
+            }
+            TriangulationObjective::MaxMinAngle => {
+                triangles.map(|t| t.min_angle()).fold(f64::MAX, f64::min)
+            }
+            TriangulationObjective::MinMaxAspectRatio => {
+                triangles.map(|t| t.aspect_ratio()).fold(0.0, f64::max)
+            }
+        }
+    }
+
+    /// Cost of the triangle $(i, k, j)$ closing off the sub-polygon from
+    /// $i$ to $j$, which for the weight is the diagonals it adds below it.
+    fn triangle_cost(&self, vertices: &[&Vertex], i: usize, k: usize, j: usize) -> f64 {
+        match self {
+            TriangulationObjective::MinWeight => {
+                let length = |a: usize, b: usize| match b - a {
+                    1 => 0.0,
+                    _ => vertices[a].distance_to(vertices[b]),
+                };
+                length(i, k) + length(k, j)
+            }
+            TriangulationObjective::MaxMinAngle => {
+                -Triangle::from_vertices(vertices[i], vertices[k], vertices[j]).min_angle()
+            }
+            TriangulationObjective::MinMaxAspectRatio => {
+                Triangle::from_vertices(vertices[i], vertices[k], vertices[j]).aspect_ratio()
+            }
+        }
+    }
+
+    /// Combines the costs of two sub-problems, such that minimizing the
+    /// combined cost optimizes the objective.
+    fn combine(&self, a: f64, b: f64) -> f64 {
+        match self {
+            TriangulationObjective::MinWeight => a + b,
+            _ => a.max(b),
+        }
+    }
+
+    fn empty_cost(&self) -> f64 {
+        match self {
+            TriangulationObjective::MinWeight => 0.0,
+            _ => f64::NEG_INFINITY,
+        }
+    }
+}
 
-        while polygon.num_vertices() > 3 {
-            let id = self
-                .find_ear(&polygon)
-                .expect("valid polygons with 3 or more vertices should have an ear");
+/// Triangulation that is optimal for a [`TriangulationObjective`], by
+/// dynamic programming over the sub-polygons closed off by each diagonal
+/// in $O(n^3)$. This is far slower than the other triangulations but gives
+/// a baseline to compare how good their triangulations are.
+#[derive(Default)]
+pub struct OptimalTriangulation {
+    pub objective: TriangulationObjective,
+}
+
+impl OptimalTriangulation {
+    fn collect_triangles(
+        &self,
+        vertices: &[&Vertex],
+        splits: &[Vec<Option<usize>>],
+        i: usize,
+        j: usize,
+        triangulation: &mut Triangulation,
+    ) {
+        if let Some(k) = splits[i][j] {
+            self.collect_triangles(vertices, splits, i, k, triangulation);
+            self.collect_triangles(vertices, splits, k, j, triangulation);
             triangulation.push(TriangleVertexIds(
-                polygon.prev_vertex_id(&id).unwrap(),
-                id,
-                polygon.next_vertex_id(&id).unwrap(),
+                vertices[i].id,
+                vertices[k].id,
+                vertices[j].id,
             ));
-            // TODO instead of unwrap, return result with error
-            polygon.remove_vertex(&id).unwrap();
-        }
-        // At this stage there should be exactly 3 vertices left,
-        // which form the final triangle of the triangulation
-        let v = polygon.vertices()[0];
-        triangulation.push(TriangleVertexIds(
-            polygon.prev_vertex_id(&v.id).unwrap(),
-            v.id,
-            polygon.next_vertex_id(&v.id).unwrap(),
-        ));
-        triangulation
+        }
+    }
+}
+
+impl TriangulationComputer for OptimalTriangulation {
+    fn triangulation(&self, polygon: &Polygon) -> Result<Triangulation, TriangulationError> {
+        info!(
+            "Computing optimal triangulation for {:?} by dynamic programming",
+            self.objective
+        );
+        check_triangulation_input(polygon)?;
+
+        let vertices = polygon.vertices();
+        let n = vertices.len();
+        let valid = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| {
+                        j == i + 1
+                            || (i == 0 && j == n - 1)
+                            || (i < j && polygon.diagonal(vertices[i], vertices[j]))
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        // Solve sub-polygons in order of increasing size, where the whole
+        // polygon is the sub-polygon closed off by the edge (0, n - 1). The
+        // cost is None where there is no diagonal closing off a sub-polygon
+        let mut costs: Vec<Vec<Option<f64>>> = vec![vec![None; n]; n];
+        let mut splits: Vec<Vec<Option<usize>>> = vec![vec![None; n]; n];
+        for i in 0..(n - 1) {
+            costs[i][i + 1] = Some(self.objective.empty_cost());
+        }
+        for gap in 2..n {
+            for i in 0..(n - gap) {
+                let j = i + gap;
+                if !valid[i][j] {
+                    continue;
+                }
+                for k in (i + 1)..j {
+                    let (Some(c_ik), Some(c_kj)) = (costs[i][k], costs[k][j]) else {
+                        continue;
+                    };
+                    let triangle = self.objective.triangle_cost(&vertices, i, k, j);
+                    let cost = self
+                        .objective
+                        .combine(self.objective.combine(c_ik, c_kj), triangle);
+                    if costs[i][j].map_or(true, |c| cost < c) {
+                        costs[i][j] = Some(cost);
+                        splits[i][j] = Some(k);
+                    }
+                }
+                trace!(i, j, cost:? = costs[i][j]; "Solved sub-polygon");
+            }
+        }
+
+        let mut triangulation = Triangulation::default();
+        self.collect_triangles(&vertices, &splits, 0, n - 1, &mut triangulation);
+        check_num_triangles(polygon, &triangulation)?;
+        info!(
+            "Computed triangulation with {} triangles",
+            triangulation.len()
+        );
+        Ok(triangulation)
+    }
+}
+
+/// Triangulation of a star-shaped polygon by joining a vertex in its kernel
+/// to every edge. Finding the vertex takes $O(n^2)$ in the worst case,
+/// after which the fan itself is $O(n)$. Polygons whose kernel holds none of
+/// their vertices are rejected by [`TriangulationComputer::triangulation`],
+/// and can be fanned out from a Steiner vertex with
+/// [`FanTriangulation::steiner_fan`] instead.
+#[derive(Default)]
+pub struct FanTriangulation;
+
+impl FanTriangulation {
+    /// A vertex strictly in front of every edge it isn't on, so that none of
+    /// the triangles in its fan are flat.
+    pub fn fan_vertex(&self, polygon: &Polygon) -> Option<VertexId> {
+        polygon.kernel()?;
+        let vertices = polygon.vertices();
+        vertices
+            .iter()
+            .find(|v| {
+                vertices
+                    .iter()
+                    .circular_tuple_windows()
+                    .filter(|(a, b)| a.id != v.id && b.id != v.id)
+                    .all(|(a, b)| orientation(a, b, v) > 0.0)
+            })
+            .map(|v| v.id)
+    }
+
+    /// Fans out from the centroid of the kernel's vertices, which is
+    /// strictly inside the kernel and so in front of every edge. This works
+    /// for any star-shaped polygon, at the cost of a Steiner vertex whose ID
+    /// follows on from the largest in the polygon, and gives $n$ triangles
+    /// rather than $n - 2$. The kernel and the fan are both $O(n)$.
+    pub fn steiner_fan(
+        &self,
+        polygon: &Polygon,
+    ) -> Result<(Vertex, Triangulation), TriangulationError> {
+        info!("Computing fan triangulation from a Steiner vertex");
+        check_triangulation_input(polygon)?;
+        let kernel = polygon.kernel().ok_or(TriangulationError::NotStarShaped)?;
+        let n = kernel.num_vertices() as f64;
+        let (x, y) = kernel
+            .vertices()
+            .iter()
+            .fold((0.0, 0.0), |(x, y), v| (x + v.x / n, y + v.y / n));
+        let id = polygon.vertex_ids().into_iter().max().unwrap();
+        let center = Vertex::new(VertexId::from(usize::from(id) + 1), x, y);
+        debug!("Fanning out from Steiner vertex {center:?}");
+
+        let mut triangulation = Triangulation::default();
+        for (a, b) in polygon.vertices().into_iter().circular_tuple_windows() {
+            triangulation.push(TriangleVertexIds(center.id, a.id, b.id));
+        }
+        info!(
+            "Computed triangulation with {} triangles",
+            triangulation.len()
+        );
+        Ok((center, triangulation))
+    }
+}
+
+impl TriangulationComputer for FanTriangulation {
+    fn triangulation(&self, polygon: &Polygon) -> Result<Triangulation, TriangulationError> {
+        info!("Computing fan triangulation");
+        check_triangulation_input(polygon)?;
+        let center = self
+            .fan_vertex(polygon)
+            .ok_or(TriangulationError::NoKernelVertex)?;
+        debug!("Fanning out from vertex {center}");
+
+        let mut triangulation = Triangulation::default();
+        let mut current = polygon.next_vertex_id(&center).unwrap();
+        let last = polygon.prev_vertex_id(&center).unwrap();
+        while current != last {
+            let next = polygon.next_vertex_id(&current).unwrap();
+            triangulation.push(TriangleVertexIds(center, current, next));
+            current = next;
+        }
+        info!(
+            "Computed triangulation with {} triangles",
+            triangulation.len()
+        );
+        Ok(triangulation)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::delaunay::ConstrainedDelaunay;
     use crate::test_util::*;
+    use crate::vector::Vector;
+    use crate::F64_ASSERT_PRECISION;
+    use assert_approx_eq::assert_approx_eq;
     use rstest::rstest;
     use rstest_reuse::{self, *};
 
     #[apply(all_polygons)]
     fn test_triangulation(
         #[case] case: PolygonTestCase,
-        #[values(EarClipping)] computer: impl TriangulationComputer,
+        #[values(
+            EarClipping,
+            ZOrderEarClipping,
+            MonotonePartition,
+            Seidel,
+            ConstrainedDelaunay
+        )]
+        computer: impl TriangulationComputer,
     ) {
-        let triangulation = computer.triangulation(&case.polygon);
+        let triangulation = computer.triangulation(&case.polygon).unwrap();
         assert_eq!(triangulation.len(), case.metadata.num_triangles);
         // This meta-assert is only valid for polygons without holes, holes
         // are not yet supported. Will need a flag in the metadata to know
@@ -113,4 +1084,302 @@ mod tests {
         }
         assert_eq!(triangulation_area, case.metadata.area);
     }
+
+    #[apply(all_polygons)]
+    fn test_fan_triangulation(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        let Some(center) = FanTriangulation.fan_vertex(polygon) else {
+            assert!(matches!(
+                FanTriangulation.triangulation(polygon),
+                Err(TriangulationError::NoKernelVertex)
+            ));
+            return;
+        };
+        assert!(polygon.is_star_shaped());
+        let triangulation = FanTriangulation.triangulation(polygon).unwrap();
+        assert_eq!(triangulation.len(), case.metadata.num_triangles);
+        let mut area = 0.0;
+        for ids in triangulation.iter() {
+            assert_eq!(ids.0, center);
+            let t = polygon.get_triangle(&ids.0, &ids.1, &ids.2).unwrap();
+            assert!(t.area() > 0.0);
+            area += t.area();
+        }
+        assert_approx_eq!(area, case.metadata.area, F64_ASSERT_PRECISION);
+    }
+
+    #[apply(all_polygons)]
+    fn test_steiner_fan(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        if polygon.kernel().is_none() {
+            assert!(matches!(
+                FanTriangulation.steiner_fan(polygon),
+                Err(TriangulationError::NotStarShaped)
+            ));
+            return;
+        }
+        let (center, triangulation) = FanTriangulation.steiner_fan(polygon).unwrap();
+        assert!(polygon.get_vertex(&center.id).is_none());
+        assert_eq!(triangulation.len(), case.metadata.num_vertices);
+        let mut area = 0.0;
+        for ids in triangulation.iter() {
+            assert_eq!(ids.0, center.id);
+            let (a, b) = (
+                polygon.get_vertex(&ids.1).unwrap(),
+                polygon.get_vertex(&ids.2).unwrap(),
+            );
+            let t = Triangle::from_vertices(&center, a, b);
+            assert!(t.area() > 0.0);
+            area += t.area();
+        }
+        assert_approx_eq!(area, case.metadata.area, F64_ASSERT_PRECISION);
+    }
+
+    #[test]
+    fn test_steiner_fan_star() {
+        // The kernel of a five-pointed star is a pentagon in the middle that
+        // none of its vertices are in
+        let coords = (0..10)
+            .map(|i| {
+                let angle = PI * i as f64 / 5.0;
+                let radius = if i % 2 == 0 { 4.0 } else { 1.5 };
+                (radius * angle.cos(), radius * angle.sin())
+            })
+            .collect_vec();
+        let polygon = Polygon::from_coords(coords);
+        assert!(polygon.is_star_shaped());
+        assert_eq!(FanTriangulation.fan_vertex(&polygon), None);
+        assert!(matches!(
+            FanTriangulation.triangulation(&polygon),
+            Err(TriangulationError::NoKernelVertex)
+        ));
+
+        let (center, triangulation) = FanTriangulation.steiner_fan(&polygon).unwrap();
+        assert_eq!(center.id, VertexId::from(10usize));
+        assert_approx_eq!(center.x, 0.0, F64_ASSERT_PRECISION);
+        assert_approx_eq!(center.y, 0.0, F64_ASSERT_PRECISION);
+        assert_eq!(triangulation.len(), 10);
+    }
+
+    #[test]
+    fn test_fan_triangulation_notch() {
+        // Only the reflex vertex at the bottom of the notch sees everything
+        let coords = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (2.0, 1.0), (0.0, 4.0)];
+        let polygon = Polygon::from_coords(coords);
+        let center = VertexId::from(3usize);
+        assert_eq!(FanTriangulation.fan_vertex(&polygon), Some(center));
+        let triangulation = FanTriangulation.triangulation(&polygon).unwrap();
+        let expected = [(4usize, 0usize), (0, 1), (1, 2)]
+            .map(|(a, b)| TriangleVertexIds(center, VertexId::from(a), VertexId::from(b)));
+        assert_eq!(triangulation.iter().copied().collect_vec(), expected);
+    }
+
+    #[apply(all_polygons)]
+    fn test_triangulation_tracer(
+        #[case] case: PolygonTestCase,
+        #[values(false, true)] z_order_hash: bool,
+    ) {
+        let tracer = &mut Some(TriangulationTracer::default());
+        let triangulation = clip_ears(&case.polygon, z_order_hash, tracer).unwrap();
+        let steps = tracer.take().unwrap().steps;
+        assert_eq!(steps.len(), case.metadata.num_triangles);
+
+        let mut num_remaining = case.metadata.num_vertices;
+        for (step, triangle) in steps.iter().zip(triangulation.iter()) {
+            assert_eq!(step.triangle, *triangle);
+            assert_eq!(step.ear, triangle.1);
+            assert!(!step.remaining.contains(&step.ear));
+            if !step.remaining.is_empty() {
+                assert_eq!(step.remaining.len(), num_remaining - 1);
+            }
+            num_remaining -= 1;
+
+            for candidate in step.rejected.iter() {
+                let ids = candidate.triangle;
+                let t = case.polygon.get_triangle(&ids.0, &ids.1, &ids.2).unwrap();
+                match candidate.rejection {
+                    EarRejection::Reflex => assert!(t.area() <= 0.0),
+                    EarRejection::ContainsVertex(id) => {
+                        assert!(t.contains(case.polygon.get_vertex(&id).unwrap()))
+                    }
+                }
+            }
+        }
+        assert!(steps.last().unwrap().remaining.is_empty());
+    }
+
+    #[apply(all_polygons)]
+    fn test_optimal_triangulation(
+        #[case] case: PolygonTestCase,
+        #[values(
+            TriangulationObjective::MinWeight,
+            TriangulationObjective::MaxMinAngle,
+            TriangulationObjective::MinMaxAspectRatio
+        )]
+        objective: TriangulationObjective,
+    ) {
+        let polygon = &case.polygon;
+        let triangulation = OptimalTriangulation { objective }
+            .triangulation(polygon)
+            .unwrap();
+        let area: f64 = triangulation
+            .iter()
+            .map(|ids| polygon.get_triangle(&ids.0, &ids.1, &ids.2).unwrap().area())
+            .sum();
+        assert_approx_eq!(area, case.metadata.area, F64_ASSERT_PRECISION);
+
+        // No other triangulation does better on the objective
+        let optimal = objective.evaluate(polygon, &triangulation);
+        for computer in [
+            Box::new(EarClipping) as Box<dyn TriangulationComputer>,
+            Box::new(MonotonePartition),
+            Box::new(ConstrainedDelaunay),
+        ] {
+            let value = objective.evaluate(polygon, &computer.triangulation(polygon).unwrap());
+            match objective {
+                TriangulationObjective::MaxMinAngle => assert!(optimal >= value - 1e-9),
+                _ => assert!(optimal <= value + 1e-9),
+            }
+        }
+
+        // The constrained Delaunay triangulation maximizes the minimum angle
+        if objective == TriangulationObjective::MaxMinAngle {
+            let delaunay = ConstrainedDelaunay.triangulation(polygon).unwrap();
+            assert_approx_eq!(optimal, objective.evaluate(polygon, &delaunay));
+        }
+    }
+
+    #[test]
+    fn test_min_weight_triangulation() {
+        // Both diagonals of a kite split it into two triangles, but the
+        // short one is the minimum weight triangulation
+        let polygon = Polygon::from_coords(vec![(0.0, 0.0), (1.0, -4.0), (2.0, 0.0), (1.0, 1.0)]);
+        let objective = TriangulationObjective::MinWeight;
+        let triangulation = OptimalTriangulation { objective }
+            .triangulation(&polygon)
+            .unwrap();
+        assert_approx_eq!(objective.evaluate(&polygon, &triangulation), 2.0);
+    }
+
+    #[apply(all_polygons)]
+    fn test_monotone_pieces(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        let pieces = MonotonePartition.monotone_pieces(polygon).unwrap();
+        let types = MonotonePartition.vertex_types(polygon);
+        let num_split_merge = types
+            .values()
+            .filter(|t| matches!(t, SweepVertexType::Split | SweepVertexType::Merge))
+            .count();
+        assert!(pieces.len() <= num_split_merge + 1);
+
+        let y_axis = Vector::new(0.0, 1.0);
+        let mut area = 0.0;
+        for piece in pieces.iter() {
+            assert!(piece.is_monotone(&y_axis));
+            for v in piece.vertices() {
+                assert_eq!(polygon.get_vertex(&v.id), Some(v));
+            }
+            area += piece.area();
+        }
+        assert_approx_eq!(area, case.metadata.area, F64_ASSERT_PRECISION);
+    }
+
+    #[test]
+    fn test_sweep_vertex_types() {
+        // A bow tie standing upright, pinched in at the top and bottom
+        let coords = vec![
+            (0.0, 0.0),
+            (2.0, 1.0),
+            (4.0, 0.0),
+            (4.0, 4.0),
+            (2.0, 3.0),
+            (0.0, 4.0),
+        ];
+        let polygon = Polygon::from_coords(coords);
+        let types = MonotonePartition.vertex_types(&polygon);
+        let expected = [
+            SweepVertexType::End,
+            SweepVertexType::Split,
+            SweepVertexType::End,
+            SweepVertexType::Start,
+            SweepVertexType::Merge,
+            SweepVertexType::Start,
+        ];
+        for (i, t) in expected.iter().enumerate() {
+            assert_eq!(types[&VertexId::from(i)], *t);
+        }
+
+        let pieces = MonotonePartition.monotone_pieces(&polygon).unwrap();
+        assert_eq!(pieces.len(), 2);
+        for piece in pieces {
+            assert!(piece.is_monotone(&Vector::new(0.0, 1.0)));
+            assert_eq!(piece.num_vertices(), 4);
+        }
+    }
+
+    #[test]
+    fn test_ear_not_found() {
+        // Moving the first vertex above the top edge makes the boundary
+        // cross itself, which leaves a sub-polygon without any ears
+        let coords = vec![
+            (0.0, 0.0),
+            (6.0, 0.0),
+            (6.0, 6.0),
+            (4.0, 6.0),
+            (3.0, 2.0),
+            (2.0, 6.0),
+            (0.0, 6.0),
+        ];
+        let mut polygon = Polygon::from_coords(coords);
+        let v = polygon.get_vertex_mut(&VertexId::from(0usize)).unwrap();
+        (v.x, v.y) = (-1.0, 8.0);
+
+        let Err(TriangulationError::EarNotFound(remaining)) = EarClipping.triangulation(&polygon)
+        else {
+            panic!("Ear clipping should not find an ear");
+        };
+        assert_eq!(remaining.len(), 4);
+        for v in remaining.iter() {
+            assert_eq!(polygon.get_vertex(&v.id), Some(v));
+        }
+    }
+
+    #[rstest]
+    fn test_degenerate_input(
+        #[values(
+            EarClipping,
+            ZOrderEarClipping,
+            MonotonePartition,
+            Seidel,
+            OptimalTriangulation::default(),
+            FanTriangulation,
+            ConstrainedDelaunay
+        )]
+        computer: impl TriangulationComputer,
+    ) {
+        let square = || Polygon::from_coords(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]);
+
+        let mut too_few = square();
+        too_few.remove_vertex(&VertexId::from(1usize));
+        too_few.remove_vertex(&VertexId::from(2usize));
+
+        let mut collinear = square();
+        for (i, x) in [(2usize, 8.0), (3, 12.0)] {
+            let v = collinear.get_vertex_mut(&VertexId::from(i)).unwrap();
+            (v.x, v.y) = (x, 0.0);
+        }
+
+        let mut not_finite = square();
+        not_finite
+            .get_vertex_mut(&VertexId::from(2usize))
+            .unwrap()
+            .x = f64::NAN;
+
+        for polygon in [too_few, collinear, not_finite] {
+            assert!(matches!(
+                computer.triangulation(&polygon),
+                Err(TriangulationError::DegenerateInput(_))
+            ));
+        }
+    }
 }
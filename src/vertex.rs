@@ -18,6 +18,12 @@ impl From<usize> for VertexId {
     }
 }
 
+impl From<VertexId> for usize {
+    fn from(id: VertexId) -> Self {
+        id.0 as usize
+    }
+}
+
 impl fmt::Display for VertexId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -0,0 +1,473 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use ordered_float::OrderedFloat as OF;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::{
+    data_structure::OrderedList,
+    error::QueryPointError,
+    geometry::Geometry,
+    polygon::Polygon,
+    predicates::orientation,
+    vertex::{Vertex, VertexId},
+};
+
+/// Where a vertex of a [`VisibilityPolygon`] came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VisibilityVertex {
+    /// A vertex of the polygon, which keeps its ID.
+    Original,
+    /// A new point where a window from a reflex vertex out to the boundary
+    /// meets the polygon edge between the two vertices.
+    Intersection(VertexId, VertexId),
+    /// The viewpoint itself, when it is on an edge of the polygon.
+    Viewpoint,
+}
+
+/// The part of a polygon that can be seen from a viewpoint, which is a
+/// polygon made up of pieces of the boundary and windows that run from a
+/// reflex vertex away from the viewpoint until they hit the boundary.
+pub struct VisibilityPolygon {
+    polygon: Polygon,
+    kinds: HashMap<VertexId, VisibilityVertex>,
+}
+
+impl VisibilityPolygon {
+    pub fn polygon(&self) -> &Polygon {
+        &self.polygon
+    }
+
+    /// Where the vertex came from, with new vertices numbered after the
+    /// largest vertex ID of the original polygon.
+    pub fn kind(&self, id: &VertexId) -> Option<VisibilityVertex> {
+        self.kinds.get(id).copied()
+    }
+}
+
+/// Visibility polygon by sweeping a ray around the viewpoint in
+/// $O(n \log n)$. The edges the ray crosses are kept ordered by how close
+/// they are to the viewpoint, and the visibility polygon changes direction
+/// wherever the closest edge changes.
+///
+/// A viewpoint on the boundary only sees inside the interior angle of the
+/// polygon there, so for a viewpoint on a vertex or an edge the sweep only
+/// covers the angle between the boundary on either side of it.
+#[derive(Default)]
+pub struct AngularSweep;
+
+/// An edge oriented CCW around the viewpoint, so that the sweep reaches
+/// `start` first and leaves it at `end`.
+struct SweepEdge<'a> {
+    start: &'a Vertex,
+    end: &'a Vertex,
+    // The edge as it is directed around the polygon
+    edge: (VertexId, VertexId),
+}
+
+enum BoundaryPoint {
+    Vertex(VertexId),
+    Intersection(f64, f64, (VertexId, VertexId)),
+    Viewpoint,
+}
+
+impl BoundaryPoint {
+    fn coords(&self, polygon: &Polygon, viewpoint: &Vertex) -> (f64, f64) {
+        match self {
+            BoundaryPoint::Vertex(id) => polygon.get_vertex(id).unwrap().coords(),
+            BoundaryPoint::Intersection(x, y, _) => (*x, *y),
+            BoundaryPoint::Viewpoint => viewpoint.coords(),
+        }
+    }
+}
+
+struct Sweep<'a> {
+    viewpoint: &'a Vertex,
+    // Point on the ray the sweep starts from
+    start_ray: Vertex,
+}
+
+impl Sweep<'_> {
+    /// Orders points by the angle CCW from the start ray, where points on
+    /// the same ray compare equal.
+    fn angle_cmp(&self, u: &Vertex, v: &Vertex) -> Ordering {
+        let q = self.viewpoint;
+        let half = |w: &Vertex| {
+            let o = orientation(q, &self.start_ray, w);
+            let dot =
+                (w.x - q.x) * (self.start_ray.x - q.x) + (w.y - q.y) * (self.start_ray.y - q.y);
+            !(o > 0.0 || (o == 0.0 && dot > 0.0))
+        };
+        half(u).cmp(&half(v)).then_with(|| {
+            let o = orientation(q, u, v);
+            if o > 0.0 {
+                Ordering::Less
+            } else if o < 0.0 {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        })
+    }
+
+    /// Whether the edge `e` is closer to the viewpoint than `f` along the
+    /// rays crossing both of them. The viewpoint is always to the left of
+    /// a sweep edge, and since edges don't cross one of them has to be
+    /// entirely on one side of the other.
+    fn in_front(&self, e: &SweepEdge, f: &SweepEdge) -> bool {
+        let side = |a: &Vertex, b: &Vertex, p: &Vertex| orientation(a, b, p);
+        let (fs, ft) = (side(e.start, e.end, f.start), side(e.start, e.end, f.end));
+        if fs * ft >= 0.0 && (fs != 0.0 || ft != 0.0) {
+            return fs + ft < 0.0;
+        }
+        let (es, et) = (side(f.start, f.end, e.start), side(f.start, f.end, e.end));
+        es + et > 0.0
+    }
+
+    /// The point where the ray through `towards` meets the edge, which is
+    /// one of its endpoints if either is on the ray.
+    fn point_on(&self, e: &SweepEdge, towards: &Vertex) -> BoundaryPoint {
+        let q = self.viewpoint;
+        for v in [e.start, e.end] {
+            if self.angle_cmp(v, towards) == Ordering::Equal {
+                return BoundaryPoint::Vertex(v.id);
+            }
+        }
+        let (dx, dy) = (towards.x - q.x, towards.y - q.y);
+        let (ex, ey) = (e.end.x - e.start.x, e.end.y - e.start.y);
+        let t = ((e.start.x - q.x) * ey - (e.start.y - q.y) * ex) / (dx * ey - dy * ex);
+        BoundaryPoint::Intersection(q.x + t * dx, q.y + t * dy, e.edge)
+    }
+}
+
+impl AngularSweep {
+    pub fn visibility_polygon(
+        &self,
+        polygon: &Polygon,
+        viewpoint: &Vertex,
+    ) -> Result<VisibilityPolygon, QueryPointError> {
+        info!("Computing visibility polygon from {viewpoint:?}");
+        let q = viewpoint;
+
+        // The sweep goes all the way around a viewpoint inside the polygon,
+        // otherwise it goes from the next vertex around to the previous one
+        let on_vertex = polygon
+            .vertices()
+            .into_iter()
+            .find(|v| v.coords() == q.coords());
+        let on_edge = polygon
+            .edges()
+            .into_iter()
+            .map(|(a, b)| {
+                (
+                    polygon.get_vertex(&a).unwrap(),
+                    polygon.get_vertex(&b).unwrap(),
+                )
+            })
+            .find(|(a, b)| orientation(a, b, q) == 0.0 && q.between(a, b));
+        let (first, wedge) = if let Some(v) = on_vertex {
+            let next = polygon.get_next_vertex(&v.id).unwrap();
+            let prev = polygon.get_prev_vertex(&v.id).unwrap();
+            (Some(BoundaryPoint::Vertex(v.id)), Some((next, prev)))
+        } else if let Some((a, b)) = on_edge {
+            (Some(BoundaryPoint::Viewpoint), Some((b, a)))
+        } else if polygon.contains(q) {
+            (None, None)
+        } else {
+            return Err(QueryPointError::OutsidePolygon(q.clone()));
+        };
+        debug!("Viewpoint on vertex: {on_vertex:?}, on edge: {on_edge:?}");
+
+        let start_ray = match wedge {
+            Some((next, _)) => next.clone(),
+            None => Vertex::new(VertexId::default(), q.x + 1.0, q.y),
+        };
+        let sweep = Sweep {
+            viewpoint: q,
+            start_ray,
+        };
+
+        // Edges collinear with the viewpoint are only ever seen edge on, so
+        // they can't hide anything and are left out of the sweep
+        let vertices = polygon.vertices();
+        let edges = vertices
+            .iter()
+            .copied()
+            .circular_tuple_windows()
+            .filter_map(|(a, b)| {
+                let o = orientation(q, a, b);
+                let edge = (a.id, b.id);
+                match o.partial_cmp(&0.0) {
+                    Some(Ordering::Greater) => Some(SweepEdge {
+                        start: a,
+                        end: b,
+                        edge,
+                    }),
+                    Some(Ordering::Less) => Some(SweepEdge {
+                        start: b,
+                        end: a,
+                        edge,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect_vec();
+        let mut starting: HashMap<VertexId, Vec<usize>> = HashMap::new();
+        let mut ending: HashMap<VertexId, Vec<usize>> = HashMap::new();
+        for (i, e) in edges.iter().enumerate() {
+            starting.entry(e.start.id).or_default().push(i);
+            ending.entry(e.end.id).or_default().push(i);
+        }
+
+        // Edges crossing the start ray are there from the start, ordered
+        // from closest to farthest. The status is kept in a balanced tree
+        // with the handle of each edge in it, so edges are removed without
+        // searching for them.
+        let initial = (0..edges.len())
+            .filter(|i| sweep.angle_cmp(edges[*i].start, edges[*i].end) == Ordering::Greater)
+            .sorted_by(|i, j| {
+                if sweep.in_front(&edges[*i], &edges[*j]) {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            });
+        let mut status = OrderedList::new();
+        let mut handles = HashMap::new();
+        for e in initial {
+            handles.insert(e, status.insert_before(None, e));
+        }
+
+        let events = vertices
+            .iter()
+            .filter(|v| v.coords() != q.coords())
+            .sorted_by(|u, v| {
+                sweep
+                    .angle_cmp(u, v)
+                    .then_with(|| OF(q.distance_to(u)).cmp(&OF(q.distance_to(v))))
+            })
+            .collect_vec();
+        let mut groups: Vec<Vec<&Vertex>> = Vec::new();
+        for v in events {
+            match groups.last_mut() {
+                Some(group) if sweep.angle_cmp(group[0], v).is_eq() => group.push(v),
+                _ => groups.push(vec![v]),
+            }
+        }
+
+        let mut boundary = Vec::new();
+        boundary.extend(first);
+        for group in groups.iter() {
+            let ray = group[0];
+            let (at_start, at_end) = match wedge {
+                Some((_, prev)) => match sweep.angle_cmp(ray, prev) {
+                    Ordering::Greater => break,
+                    order => (
+                        sweep.angle_cmp(ray, &sweep.start_ray).is_eq(),
+                        order.is_eq(),
+                    ),
+                },
+                None => (false, false),
+            };
+
+            let old = status.first().map(|h| status[h]);
+            for v in group.iter() {
+                for e in ending.get(&v.id).into_iter().flatten() {
+                    if let Some(h) = handles.remove(e) {
+                        status.remove(h);
+                    }
+                }
+            }
+            for v in group.iter() {
+                for e in starting.get(&v.id).into_iter().flatten() {
+                    let behind =
+                        status.partition_point(|f| sweep.in_front(&edges[status[f]], &edges[*e]));
+                    handles.insert(*e, status.insert_before(behind, *e));
+                }
+            }
+            let new = status.first().map(|h| status[h]);
+            trace!(ray:?, old:?, new:?; "Sweep event");
+
+            if old != new || at_start || at_end {
+                // The wedge is bounded by the polygon edges on either side of
+                // the viewpoint, which are left out of the sweep
+                let old = match (wedge, at_start) {
+                    (Some((next, _)), true) => Some(BoundaryPoint::Vertex(next.id)),
+                    _ => old.map(|e| sweep.point_on(&edges[e], ray)),
+                };
+                let new = match (wedge, at_end) {
+                    (Some((_, prev)), true) => Some(BoundaryPoint::Vertex(prev.id)),
+                    _ => new.map(|e| sweep.point_on(&edges[e], ray)),
+                };
+
+                // Vertices of collinear edges along the ray between the two
+                // points are on the boundary of the visibility polygon too
+                let distance = |p: &Option<BoundaryPoint>| {
+                    p.as_ref().map(|p| {
+                        let (x, y) = p.coords(polygon, q);
+                        Vertex::new(VertexId::default(), x, y).distance_to(q)
+                    })
+                };
+                let (from, to) = (distance(&old), distance(&new));
+                let mut between = group
+                    .iter()
+                    .filter(|v| {
+                        let d = Some(q.distance_to(v));
+                        (from < d && d < to) || (to < d && d < from)
+                    })
+                    .map(|v| BoundaryPoint::Vertex(v.id))
+                    .collect_vec();
+                if from > to {
+                    between.reverse();
+                }
+                boundary.extend(old);
+                boundary.extend(between);
+                boundary.extend(new);
+            }
+            if at_end {
+                break;
+            }
+        }
+
+        let mut next_id = polygon
+            .vertex_ids()
+            .into_iter()
+            .max()
+            .map_or(0, |id| usize::from(id) + 1);
+        let mut kinds = HashMap::new();
+        let mut result: Vec<Vertex> = Vec::new();
+        for point in boundary {
+            let (v, kind) = match point {
+                BoundaryPoint::Vertex(id) => (
+                    polygon.get_vertex(&id).unwrap().clone(),
+                    VisibilityVertex::Original,
+                ),
+                BoundaryPoint::Intersection(x, y, (a, b)) => (
+                    Vertex::new(VertexId::from(next_id), x, y),
+                    VisibilityVertex::Intersection(a, b),
+                ),
+                BoundaryPoint::Viewpoint => (
+                    Vertex::new(VertexId::from(next_id), q.x, q.y),
+                    VisibilityVertex::Viewpoint,
+                ),
+            };
+            if result.last().is_some_and(|u| u.coords() == v.coords())
+                || result.first().is_some_and(|u| u.id == v.id)
+            {
+                continue;
+            }
+            if kind != VisibilityVertex::Original {
+                next_id += 1;
+            }
+            kinds.insert(v.id, kind);
+            result.push(v);
+        }
+        if result.len() > 1 && result[0].coords() == result[result.len() - 1].coords() {
+            let v = result.pop().unwrap();
+            kinds.remove(&v.id);
+        }
+
+        info!("Computed visibility polygon with {} vertices", result.len());
+        Ok(VisibilityPolygon {
+            polygon: Polygon::from_vertices(result),
+            kinds,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::triangulation::{EarClipping, TriangulationComputer};
+    use crate::F64_ASSERT_PRECISION;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    fn check_visibility_polygon(polygon: &Polygon, q: &Vertex) {
+        let visibility = AngularSweep.visibility_polygon(polygon, q).unwrap();
+        let result = visibility.polygon();
+        assert!(result.area() <= polygon.area() + F64_ASSERT_PRECISION);
+
+        for v in result.vertices() {
+            match visibility.kind(&v.id).unwrap() {
+                VisibilityVertex::Original => {
+                    assert_eq!(polygon.get_vertex(&v.id), Some(v));
+                }
+                VisibilityVertex::Intersection(a, b) => {
+                    let ls = polygon.get_line_segment(&a, &b).unwrap();
+                    assert!(ls.distance_to_vertex(v) < F64_ASSERT_PRECISION);
+                }
+                VisibilityVertex::Viewpoint => assert_eq!(v.coords(), q.coords()),
+            }
+        }
+
+        // Every vertex that can be seen from the viewpoint is a vertex of
+        // the visibility polygon, and no others are
+        for v in polygon.vertices() {
+            assert_eq!(
+                polygon.sees(q, v),
+                result.get_vertex(&v.id).is_some(),
+                "{v:?}"
+            );
+        }
+    }
+
+    #[apply(all_polygons)]
+    fn test_visibility_polygon(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        let triangulation = EarClipping.triangulation(polygon).unwrap();
+        for ids in triangulation.iter().take(5) {
+            check_visibility_polygon(polygon, &triangle_centroid(polygon, ids));
+        }
+    }
+
+    #[apply(all_polygons)]
+    fn test_visibility_polygon_on_boundary(case: PolygonTestCase) {
+        let polygon = &case.polygon;
+        for v in polygon.vertices().into_iter().take(5) {
+            check_visibility_polygon(polygon, v);
+            let next = polygon.get_next_vertex(&v.id).unwrap();
+            let mid = Vertex::new(
+                VertexId::default(),
+                (v.x + next.x) / 2.0,
+                (v.y + next.y) / 2.0,
+            );
+            check_visibility_polygon(polygon, &mid);
+        }
+    }
+
+    #[test]
+    fn test_visibility_polygon_notch() {
+        let coords = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (2.0, 1.0), (0.0, 4.0)];
+        let polygon = Polygon::from_coords(coords);
+
+        // From low down in the middle everything is visible
+        let q = Vertex::new(VertexId::default(), 2.0, 0.5);
+        let visibility = AngularSweep.visibility_polygon(&polygon, &q).unwrap();
+        assert_eq!(visibility.polygon().num_vertices(), 5);
+
+        // From the bottom left corner the notch hides the top right corner,
+        // and the window past the reflex vertex meets the right edge
+        let q = polygon.get_vertex(&VertexId::from(0usize)).unwrap();
+        let visibility = AngularSweep.visibility_polygon(&polygon, q).unwrap();
+        let result = visibility.polygon();
+        assert_eq!(result.num_vertices(), 5);
+        assert!(result.get_vertex(&VertexId::from(2usize)).is_none());
+        let window = result.get_vertex(&VertexId::from(5usize)).unwrap();
+        assert_eq!(
+            visibility.kind(&window.id),
+            Some(VisibilityVertex::Intersection(
+                VertexId::from(1usize),
+                VertexId::from(2usize)
+            ))
+        );
+        assert!((window.x - 4.0).abs() < 1e-12 && (window.y - 2.0).abs() < 1e-12);
+
+        let outside = Vertex::new(VertexId::default(), 2.0, 3.0);
+        assert!(matches!(
+            AngularSweep.visibility_polygon(&polygon, &outside),
+            Err(QueryPointError::OutsidePolygon(_))
+        ));
+    }
+}
@@ -0,0 +1,900 @@
+use itertools::Itertools;
+use log::{debug, info, trace};
+use ordered_float::OrderedFloat as OF;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
+
+use crate::{
+    bounding_box::BoundingBox,
+    data_structure::{ListHandle, OrderedList},
+    polygon::Polygon,
+    predicates::orientation,
+    vertex::{Vertex, VertexId},
+};
+
+/// Relative distance between Voronoi vertices for them to be counted as the
+/// same vertex, scaled by the size of the point set. Four or more sites on
+/// a circle give one vertex for every three of them that meet, which are
+/// merged into one.
+const VERTEX_TOLERANCE: f64 = 1e-9;
+
+/// Index of a vertex in a [`Voronoi`] diagram.
+pub type VoronoiVertexId = usize;
+
+#[derive(Clone, Debug)]
+pub struct VoronoiVertex {
+    pub x: f64,
+    pub y: f64,
+    /// Distance to the sites whose cells meet at the vertex, which is the
+    /// radius of the empty circle through them.
+    pub radius: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct VoronoiEdge {
+    /// The sites whose cells the edge separates, with the first on the left
+    /// going from `from` to `to`.
+    pub sites: (VertexId, VertexId),
+    /// `None` where the edge goes off to infinity.
+    pub from: Option<VoronoiVertexId>,
+    pub to: Option<VoronoiVertexId>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VoronoiEvent {
+    /// The sweep line reached a site, adding its arc to the beach line.
+    Site(VertexId),
+    /// An arc shrank away at the bottom of the circle through its site and
+    /// those of its neighbors, whose center is the vertex.
+    Circle(VoronoiVertexId),
+}
+
+/// An arc of the beach line, the part of the parabola of points as far
+/// from the site as from the sweep line that is closest to the sweep line.
+#[derive(Clone, Debug)]
+pub struct BeachArc {
+    pub site: VertexId,
+    /// Range of x the arc covers, which is infinite at either end of the
+    /// beach line.
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct VoronoiTracerStep {
+    /// Height of the sweep line at the event.
+    pub sweep: f64,
+    pub event: VoronoiEvent,
+    /// The beach line after the event, from left to right.
+    pub beach_line: Vec<BeachArc>,
+    /// The parts of the edges traced out so far, ending at their vertices
+    /// or the breakpoints of the beach line tracing them. Parts going off
+    /// to infinity are left out.
+    pub edges: Vec<[(f64, f64); 2]>,
+}
+
+impl fmt::Display for VoronoiTracerStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "\tSweep: {}", self.sweep)?;
+        writeln!(f, "\tEvent: {:?}", self.event)?;
+        writeln!(
+            f,
+            "\tBeach Line: {:?}",
+            self.beach_line.iter().map(|arc| arc.site).collect_vec()
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct VoronoiTracer {
+    pub steps: Vec<VoronoiTracerStep>,
+}
+
+impl fmt::Debug for VoronoiTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(f, "STEP {}:\n{}", i, step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Voronoi diagram of a point set, dividing the plane into a cell for each
+/// site of the points closer to it than to any other site. Cells of sites
+/// on the convex hull are unbounded, so they can only be given as polygons
+/// once clipped to a bounding box.
+///
+/// Computed by Fortune's sweep, which moves a line down over the sites.
+/// Above the sweep line the diagram is settled up to the beach line, made of
+/// parabolic arcs whose breakpoints trace out the edges. A site event adds
+/// an arc to the beach line, and a circle event removes one where its
+/// neighbors' breakpoints meet at a vertex. The beach line is kept in a
+/// balanced tree searched by the breakpoints, the same as the status of the
+/// angular sweep for visibility, with each circle event holding the arc it
+/// removes, and the circle events in a priority queue, so this is
+/// $O(n \log n)$. Duplicate sites are skipped.
+pub struct Voronoi {
+    sites: HashMap<VertexId, Vertex>,
+    vertices: Vec<VoronoiVertex>,
+    edges: Vec<VoronoiEdge>,
+    cell_edges: HashMap<VertexId, Vec<usize>>,
+    duplicates: Vec<VertexId>,
+}
+
+impl Voronoi {
+    pub fn from_vertices<'a>(vertices: impl IntoIterator<Item = &'a Vertex>) -> Self {
+        Self::from_vertices_with_tracer(vertices, &mut None)
+    }
+
+    /// Computes the diagram, recording the beach line and the edges traced
+    /// so far after each event when a tracer is given.
+    pub fn from_vertices_with_tracer<'a>(
+        vertices: impl IntoIterator<Item = &'a Vertex>,
+        tracer: &mut Option<VoronoiTracer>,
+    ) -> Self {
+        info!("Computing Voronoi diagram");
+
+        let mut coords = HashSet::new();
+        let mut points = Vec::new();
+        let mut duplicates = Vec::new();
+        for v in vertices {
+            if coords.insert((OF(v.x), OF(v.y))) {
+                points.push(v.clone());
+            } else {
+                debug!("Skipping duplicate site: {v:?}");
+                duplicates.push(v.id);
+            }
+        }
+        points.sort_by_key(|v| (Reverse(OF(v.y)), OF(v.x)));
+
+        let mut sweep = Sweep::new(&points);
+        sweep.run(tracer);
+
+        let edges = sweep
+            .edges
+            .iter()
+            .filter(|e| !e.removed)
+            .map(|e| VoronoiEdge {
+                sites: (points[e.sites.0].id, points[e.sites.1].id),
+                from: e.from,
+                to: e.to,
+            })
+            .collect_vec();
+        let mut cell_edges: HashMap<VertexId, Vec<usize>> =
+            points.iter().map(|v| (v.id, Vec::new())).collect();
+        for (i, e) in edges.iter().enumerate() {
+            cell_edges.get_mut(&e.sites.0).unwrap().push(i);
+            cell_edges.get_mut(&e.sites.1).unwrap().push(i);
+        }
+        let vertices = sweep.vertices;
+
+        info!(
+            "Computed Voronoi diagram with {} vertices and {} edges",
+            vertices.len(),
+            edges.len()
+        );
+        Voronoi {
+            sites: points.into_iter().map(|v| (v.id, v)).collect(),
+            vertices,
+            edges,
+            cell_edges,
+            duplicates,
+        }
+    }
+
+    pub fn vertices(&self) -> &[VoronoiVertex] {
+        &self.vertices
+    }
+
+    pub fn vertex(&self, id: VoronoiVertexId) -> &VoronoiVertex {
+        &self.vertices[id]
+    }
+
+    pub fn edges(&self) -> &[VoronoiEdge] {
+        &self.edges
+    }
+
+    /// IDs of sites that were skipped because an earlier site had the
+    /// same coordinates.
+    pub fn duplicates(&self) -> &[VertexId] {
+        &self.duplicates
+    }
+
+    /// Sites whose cells share an edge with the cell of the site, sorted by
+    /// ID. Cells only touching at a vertex are not neighbors.
+    pub fn neighbors(&self, site: &VertexId) -> Vec<VertexId> {
+        self.cell_edges
+            .get(site)
+            .into_iter()
+            .flatten()
+            .map(|i| {
+                let (a, b) = self.edges[*i].sites;
+                if a == *site {
+                    b
+                } else {
+                    a
+                }
+            })
+            .sorted()
+            .dedup()
+            .collect_vec()
+    }
+
+    /// The site adjacency graph, which is the dual of the diagram. It is
+    /// the Delaunay triangulation, except that where four or more sites are
+    /// on an empty circle they are only connected around it.
+    pub fn adjacency(&self) -> HashMap<VertexId, Vec<VertexId>> {
+        self.sites
+            .keys()
+            .map(|id| (*id, self.neighbors(id)))
+            .collect()
+    }
+
+    /// The cell of the site when it is bounded, meaning the site is not on
+    /// the convex hull of the sites.
+    pub fn cell(&self, site: &VertexId) -> Option<Polygon> {
+        let center = self.sites.get(site)?;
+        let mut ids = Vec::new();
+        for i in self.cell_edges[site].iter() {
+            let e = &self.edges[*i];
+            ids.extend([e.from?, e.to?]);
+        }
+        // Cells are convex with the site inside, so going around the site
+        // by angle gives the vertices in CCW order
+        let coords = ids
+            .into_iter()
+            .unique()
+            .map(|id| (self.vertices[id].x, self.vertices[id].y))
+            .sorted_by_key(|(x, y)| OF((y - center.y).atan2(x - center.x)))
+            .collect_vec();
+        if coords.len() < 3 {
+            return None;
+        }
+        Some(Polygon::from_coords(coords))
+    }
+
+    /// Every bounded cell by the ID of its site.
+    pub fn cells(&self) -> HashMap<VertexId, Polygon> {
+        self.sites
+            .keys()
+            .filter_map(|id| Some((*id, self.cell(id)?)))
+            .collect()
+    }
+
+    /// The cell of the site clipped to the box, or `None` when none of it
+    /// is inside the box. This is the box clipped to the half-plane closer
+    /// to the site than to each of its neighbors, in $O(k^2)$ for a cell
+    /// with $k$ edges.
+    pub fn clipped_cell(&self, site: &VertexId, bbox: &BoundingBox) -> Option<Polygon> {
+        let s = self.sites.get(site)?;
+        let vertex = |x, y| Vertex::new(VertexId::default(), x, y);
+        let mut cell = vec![
+            vertex(bbox.min_x, bbox.min_y),
+            vertex(bbox.max_x, bbox.min_y),
+            vertex(bbox.max_x, bbox.max_y),
+            vertex(bbox.min_x, bbox.max_y),
+        ];
+        for t in self.neighbors(site).iter().map(|id| &self.sites[id]) {
+            // Positive on the side of the bisector closer to the site
+            let (mx, my) = ((s.x + t.x) / 2.0, (s.y + t.y) / 2.0);
+            let side = |p: &Vertex| (p.x - mx) * (s.x - t.x) + (p.y - my) * (s.y - t.y);
+            let mut clipped = Vec::new();
+            for (p, q) in cell.iter().circular_tuple_windows() {
+                let (sp, sq) = (side(p), side(q));
+                if sp >= 0.0 {
+                    clipped.push(p.clone());
+                }
+                if (sp > 0.0 && sq < 0.0) || (sp < 0.0 && sq > 0.0) {
+                    let t = sp / (sp - sq);
+                    clipped.push(vertex(p.x + t * (q.x - p.x), p.y + t * (q.y - p.y)));
+                }
+            }
+            cell = clipped;
+            cell.dedup_by(|p, q| p.coords() == q.coords());
+            if cell.len() > 1 && cell[0].coords() == cell[cell.len() - 1].coords() {
+                cell.pop();
+            }
+            if cell.len() < 3 {
+                debug!("Cell of site {site} is outside the box");
+                return None;
+            }
+        }
+
+        // Clipping through corners of the cell leaves collinear points
+        let corners = cell
+            .iter()
+            .circular_tuple_windows()
+            .filter(|(p, v, q)| orientation(p, v, q) != 0.0)
+            .map(|(_, v, _)| v.coords())
+            .collect_vec();
+        if corners.len() < 3 {
+            return None;
+        }
+        Some(Polygon::from_coords(corners))
+    }
+
+    /// Every cell clipped to the box by the ID of its site, leaving out
+    /// cells entirely outside the box.
+    pub fn clipped_cells(&self, bbox: &BoundingBox) -> HashMap<VertexId, Polygon> {
+        self.sites
+            .keys()
+            .filter_map(|id| Some((*id, self.clipped_cell(id, bbox)?)))
+            .collect()
+    }
+}
+
+/// Where the parabolas of the sites `p` and `q` meet with `p` on the left
+/// and the sweep line at `sweep`. Each parabola is the set of points as far
+/// from its site as from the sweep line, which for a site on the sweep line
+/// is the vertical line up from it.
+fn breakpoint(p: &Vertex, q: &Vertex, sweep: f64) -> f64 {
+    let (hp, hq) = (p.y - sweep, q.y - sweep);
+    if hp == hq {
+        return (p.x + q.x) / 2.0;
+    }
+    if hp <= 0.0 {
+        return p.x;
+    }
+    if hq <= 0.0 {
+        return q.x;
+    }
+    // Of the two roots of the difference between the parabolas, this is
+    // the one where it goes from `p` being lower to `q` being lower, with
+    // the form picked to avoid cancellation
+    let dx = q.x - p.x;
+    let s = (hp * hq).sqrt() * p.distance_to(q);
+    if dx > 0.0 {
+        p.x + hp * (dx * dx + hq * (hq - hp)) / (hp * dx + s)
+    } else {
+        p.x + (s - hp * dx) / (hq - hp)
+    }
+}
+
+/// Height of the parabola of the site at `x`, or `None` for a site on the
+/// sweep line.
+fn parabola(p: &Vertex, sweep: f64, x: f64) -> Option<f64> {
+    let h = p.y - sweep;
+    (h > 0.0).then(|| sweep + ((x - p.x).powi(2) + h * h) / (2.0 * h))
+}
+
+struct Arc {
+    site: usize,
+    // Edge traced by the breakpoint with the next arc
+    right_edge: Option<usize>,
+    // Circle event where the arc would shrink away
+    event: Option<usize>,
+}
+
+struct Circle {
+    // Arc that shrinks away at the event
+    arc: ListHandle,
+    x: f64,
+    y: f64,
+    radius: f64,
+    valid: bool,
+}
+
+struct Edge {
+    sites: (usize, usize),
+    from: Option<usize>,
+    to: Option<usize>,
+    // Edges between vertices that were merged have no length
+    removed: bool,
+}
+
+struct Sweep<'a> {
+    sites: &'a [Vertex],
+    sweep: f64,
+    tolerance: f64,
+    beach: OrderedList<Arc>,
+    circles: Vec<Circle>,
+    // Circle events by the bottom of the circle, leftmost first on ties
+    queue: BinaryHeap<(OF<f64>, Reverse<OF<f64>>, usize)>,
+    vertices: Vec<VoronoiVertex>,
+    edges: Vec<Edge>,
+}
+
+impl<'a> Sweep<'a> {
+    /// Sites have to be sorted from the top down and left to right.
+    fn new(sites: &'a [Vertex]) -> Self {
+        let extent = |coord: fn(&Vertex) -> f64| {
+            sites
+                .iter()
+                .map(|v| OF(coord(v)))
+                .minmax()
+                .into_option()
+                .map_or(0.0, |(min, max)| max.0 - min.0)
+        };
+        let size = extent(|v| v.x).max(extent(|v| v.y));
+        Sweep {
+            sites,
+            sweep: sites.first().map_or(0.0, |v| v.y),
+            tolerance: VERTEX_TOLERANCE * size,
+            beach: OrderedList::new(),
+            circles: Vec::new(),
+            queue: BinaryHeap::new(),
+            vertices: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    fn run(&mut self, tracer: &mut Option<VoronoiTracer>) {
+        let sites = self.sites;
+        // Sites level with the first have nothing above them, so they start
+        // the beach line side by side with vertical edges between them
+        let mut next = 0;
+        while next < sites.len() && sites[next].y == self.sweep {
+            if let Some(last) = self.beach.last() {
+                let e = self.edges.len();
+                self.beach[last].right_edge = Some(e);
+                self.edges.push(Edge {
+                    sites: (next, next - 1),
+                    from: None,
+                    to: None,
+                    removed: false,
+                });
+            }
+            self.beach.insert_before(
+                None,
+                Arc {
+                    site: next,
+                    right_edge: None,
+                    event: None,
+                },
+            );
+            self.trace(tracer, VoronoiEvent::Site(sites[next].id));
+            next += 1;
+        }
+
+        loop {
+            let site = sites.get(next);
+            let circle = loop {
+                match self.queue.peek() {
+                    Some((_, _, c)) if !self.circles[*c].valid => {
+                        self.queue.pop();
+                    }
+                    top => break top.map(|(y, _, c)| (y.0, *c)),
+                }
+            };
+            match (site, circle) {
+                (Some(v), Some((y, _))) if v.y > y => {
+                    self.site_event(next);
+                    self.trace(tracer, VoronoiEvent::Site(v.id));
+                    next += 1;
+                }
+                (Some(v), None) => {
+                    self.site_event(next);
+                    self.trace(tracer, VoronoiEvent::Site(v.id));
+                    next += 1;
+                }
+                (_, Some((y, c))) => {
+                    self.queue.pop();
+                    self.sweep = self.sweep.min(y);
+                    let vertex = self.circle_event(c);
+                    self.trace(tracer, VoronoiEvent::Circle(vertex));
+                }
+                (None, None) => break,
+            }
+        }
+    }
+
+    fn site_event(&mut self, site: usize) {
+        let s = &self.sites[site];
+        trace!("Site event at {s:?}");
+        self.sweep = s.y;
+        let i = self.search(s.x);
+        let p = self.beach[i].site;
+        self.invalidate(i);
+
+        // The arc above the site is split in two with the new arc between,
+        // and the breakpoints on either side trace out the same edge in
+        // opposite directions
+        let e = self.edges.len();
+        self.edges.push(Edge {
+            sites: (p, site),
+            from: None,
+            to: None,
+            removed: false,
+        });
+        let right_edge = self.beach[i].right_edge.replace(e);
+        let j = self.beach.insert_after(
+            Some(i),
+            Arc {
+                site: p,
+                right_edge,
+                event: None,
+            },
+        );
+        self.beach.insert_after(
+            Some(i),
+            Arc {
+                site,
+                right_edge: Some(e),
+                event: None,
+            },
+        );
+        self.check_circle(i);
+        self.check_circle(j);
+    }
+
+    fn circle_event(&mut self, circle: usize) -> VoronoiVertexId {
+        let Circle {
+            arc: i,
+            x,
+            y,
+            radius,
+            ..
+        } = self.circles[circle];
+        trace!("Circle event at ({x}, {y}) with radius {radius}");
+        // Circle events are only added for arcs with neighbors on both
+        // sides, and dropped when either of them changes
+        let (l, r) = (self.beach.prev(i).unwrap(), self.beach.next(i).unwrap());
+        let (left, right) = (&self.beach[l], &self.beach[r]);
+        let (a, b, c) = (left.site, self.beach[i].site, right.site);
+        let ending = [
+            (left.right_edge.unwrap(), b),
+            (self.beach[i].right_edge.unwrap(), c),
+        ];
+
+        // The breakpoint with its site on the right traces an edge forward
+        let is_forward = |e: &Edge, right_site| e.sites.0 == right_site;
+        let other_end = |e: &Edge, right_site| {
+            if is_forward(e, right_site) {
+                e.from
+            } else {
+                e.to
+            }
+        };
+        let merged = ending.iter().find_map(|(e, r)| {
+            other_end(&self.edges[*e], *r).filter(|u| {
+                let u = &self.vertices[*u];
+                (u.x - x).hypot(u.y - y) <= self.tolerance
+            })
+        });
+        let vertex = merged.unwrap_or_else(|| {
+            self.vertices.push(VoronoiVertex { x, y, radius });
+            self.vertices.len() - 1
+        });
+        for (e, r) in ending {
+            let edge = &mut self.edges[e];
+            if other_end(edge, r) == Some(vertex) {
+                debug!("Merging vertices at ({x}, {y}), removing edge {e}");
+                edge.removed = true;
+            } else if is_forward(edge, r) {
+                edge.to = Some(vertex);
+            } else {
+                edge.from = Some(vertex);
+            }
+        }
+
+        let e = self.edges.len();
+        self.edges.push(Edge {
+            sites: (c, a),
+            from: Some(vertex),
+            to: None,
+            removed: false,
+        });
+        self.invalidate(l);
+        self.invalidate(r);
+        self.beach.remove(i);
+        self.beach[l].right_edge = Some(e);
+        self.check_circle(l);
+        self.check_circle(r);
+        vertex
+    }
+
+    /// The arc above `x`, the first whose right breakpoint is not to the
+    /// left of it. The last arc reaches to infinity, so there is one as
+    /// long as the beach line is not empty.
+    fn search(&self, x: f64) -> ListHandle {
+        self.beach
+            .partition_point(|h| self.right_breakpoint(h) < x)
+            .expect("Beach line should not be empty")
+    }
+
+    fn right_breakpoint(&self, i: ListHandle) -> f64 {
+        match self.beach.next(i) {
+            Some(next) => breakpoint(
+                &self.sites[self.beach[i].site],
+                &self.sites[self.beach[next].site],
+                self.sweep,
+            ),
+            None => f64::INFINITY,
+        }
+    }
+
+    fn left_breakpoint(&self, i: ListHandle) -> f64 {
+        match self.beach.prev(i) {
+            Some(prev) => self.right_breakpoint(prev),
+            None => f64::NEG_INFINITY,
+        }
+    }
+
+    fn invalidate(&mut self, i: ListHandle) {
+        if let Some(c) = self.beach[i].event.take() {
+            self.circles[c].valid = false;
+        }
+    }
+
+    /// Adds a circle event for the arc if the breakpoints on either side of
+    /// it are moving towards each other, which is when its site and those
+    /// of its neighbors turn clockwise.
+    fn check_circle(&mut self, i: ListHandle) {
+        let (Some(l), Some(r)) = (self.beach.prev(i), self.beach.next(i)) else {
+            return;
+        };
+        let [a, b, c] = [l, i, r].map(|j| &self.sites[self.beach[j].site]);
+        if orientation(a, b, c) >= 0.0 {
+            return;
+        }
+        let (bx, by) = (b.x - a.x, b.y - a.y);
+        let (cx, cy) = (c.x - a.x, c.y - a.y);
+        let d = 2.0 * (bx * cy - by * cx);
+        let (b2, c2) = (bx * bx + by * by, cx * cx + cy * cy);
+        let (ux, uy) = ((cy * b2 - by * c2) / d, (bx * c2 - cx * b2) / d);
+        let radius = ux.hypot(uy);
+        let (x, y) = (a.x + ux, a.y + uy);
+
+        let id = self.circles.len();
+        self.circles.push(Circle {
+            arc: i,
+            x,
+            y,
+            radius,
+            valid: true,
+        });
+        self.beach[i].event = Some(id);
+        // The circle can only be entirely above the sweep line by rounding
+        let bottom = (y - radius).min(self.sweep);
+        self.queue.push((OF(bottom), Reverse(OF(x)), id));
+    }
+
+    fn trace(&self, tracer: &mut Option<VoronoiTracer>, event: VoronoiEvent) {
+        let Some(t) = tracer.as_mut() else {
+            return;
+        };
+        let beach_line = self
+            .beach
+            .handles()
+            .map(|i| BeachArc {
+                site: self.sites[self.beach[i].site].id,
+                start: self.left_breakpoint(i),
+                end: self.right_breakpoint(i),
+            })
+            .collect_vec();
+
+        // Ends of edges still being traced are at the breakpoints
+        let mut ends: HashMap<usize, [Option<(f64, f64)>; 2]> = HashMap::new();
+        for (arc, next) in self.beach.iter().tuple_windows() {
+            let (p, q) = (&self.sites[arc.site], &self.sites[next.site]);
+            let x = breakpoint(p, q, self.sweep);
+            let Some(y) = parabola(p, self.sweep, x).or_else(|| parabola(q, self.sweep, x)) else {
+                continue;
+            };
+            let e = arc.right_edge.unwrap();
+            let forward = self.edges[e].sites.0 == next.site;
+            ends.entry(e).or_default()[forward as usize] = Some((x, y));
+        }
+        let edges = self
+            .edges
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !e.removed)
+            .filter_map(|(i, e)| {
+                let end = |vertex: Option<usize>, j: usize| match vertex {
+                    Some(v) => Some((self.vertices[v].x, self.vertices[v].y)),
+                    None => ends.get(&i).and_then(|ends| ends[j]),
+                };
+                Some([end(e.from, 0)?, end(e.to, 1)?])
+            })
+            .collect_vec();
+
+        t.steps.push(VoronoiTracerStep {
+            sweep: self.sweep,
+            event,
+            beach_line,
+            edges,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delaunay::Delaunay;
+    use crate::geometry::Geometry;
+    use crate::test_util::*;
+    use crate::F64_ASSERT_PRECISION;
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::rstest;
+    use rstest_reuse::{self, *};
+
+    #[apply(all_polygons)]
+    fn test_voronoi(case: PolygonTestCase) {
+        let vertices = case.polygon.vertices();
+        let voronoi = Voronoi::from_vertices(vertices.iter().copied());
+        let bbox = case.polygon.bounding_box();
+        let size = (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+        let tolerance = 1e-6 * size;
+
+        // Every vertex is as far from the sites of its edges as its radius
+        for e in voronoi.edges() {
+            for v in [e.from, e.to].into_iter().flatten() {
+                let v = voronoi.vertex(v);
+                for site in [e.sites.0, e.sites.1] {
+                    let s = case.polygon.get_vertex(&site).unwrap();
+                    assert_approx_eq!(
+                        s.distance_to(&Vertex::new(site, v.x, v.y)),
+                        v.radius,
+                        tolerance
+                    );
+                }
+            }
+        }
+
+        // Sites are only adjacent along Delaunay edges
+        let delaunay = Delaunay::from_vertices(vertices.iter().copied());
+        let delaunay_edges: HashSet<_> = delaunay
+            .triangulation()
+            .iter()
+            .flat_map(|t| [(t.0, t.1), (t.1, t.2), (t.2, t.0)])
+            .collect();
+        let adjacency = voronoi.adjacency();
+        assert_eq!(adjacency.len(), vertices.len());
+        for (a, neighbors) in adjacency.iter() {
+            assert!(!neighbors.is_empty());
+            for b in neighbors {
+                assert!(adjacency[b].contains(a));
+                assert!(delaunay_edges.contains(&(*a, *b)) || delaunay_edges.contains(&(*b, *a)));
+            }
+        }
+
+        // Clipped cells cover the box, and points of a sample of them are
+        // no closer to any other site than to their own
+        let margin = 0.1 * size;
+        let bbox = BoundingBox::new(
+            bbox.min_x - margin,
+            bbox.max_x + margin,
+            bbox.min_y - margin,
+            bbox.max_y + margin,
+        );
+        let cells = voronoi.clipped_cells(&bbox);
+        assert_eq!(cells.len(), vertices.len());
+        let area: f64 = cells.values().map(|cell| cell.area()).sum();
+        let box_area = (bbox.max_x - bbox.min_x) * (bbox.max_y - bbox.min_y);
+        assert_approx_eq!(area, box_area, 1e-9 * box_area);
+        for (id, cell) in cells.iter().step_by(vertices.len() / 20 + 1) {
+            let site = case.polygon.get_vertex(id).unwrap();
+            for p in cell.vertices() {
+                let own = p.distance_to(site);
+                assert!(vertices.iter().all(|v| p.distance_to(v) >= own - tolerance));
+            }
+        }
+
+        // Bounded cells inside the box match their clipped cells
+        for (id, cell) in voronoi.cells() {
+            let b = cell.bounding_box();
+            if b.min_x < bbox.min_x
+                || b.max_x > bbox.max_x
+                || b.min_y < bbox.min_y
+                || b.max_y > bbox.max_y
+            {
+                continue;
+            }
+            assert_approx_eq!(cell.area(), cells[&id].area(), tolerance * size);
+        }
+    }
+
+    #[test]
+    fn test_voronoi_cocircular() {
+        // The corners of a square meet at one vertex, and the diagonals
+        // are not adjacent
+        let square = vertices_from_coords(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]);
+        let voronoi = Voronoi::from_vertices(&square);
+        assert_eq!(voronoi.vertices().len(), 1);
+        assert_approx_eq!(voronoi.vertex(0).x, 1.0, F64_ASSERT_PRECISION);
+        assert_approx_eq!(voronoi.vertex(0).y, 1.0, F64_ASSERT_PRECISION);
+        assert_eq!(voronoi.edges().len(), 4);
+        assert_eq!(
+            voronoi.neighbors(&VertexId::from(0usize)),
+            vec![VertexId::from(1usize), VertexId::from(3usize)]
+        );
+        assert!(voronoi.cells().is_empty());
+
+        // With a site in the middle its cell is a diamond
+        let mut points = square.clone();
+        points.push(Vertex::new(VertexId::from(4usize), 1.0, 1.0));
+        let voronoi = Voronoi::from_vertices(&points);
+        assert_eq!(voronoi.vertices().len(), 4);
+        let cells = voronoi.cells();
+        assert_eq!(cells.len(), 1);
+        let diamond = &cells[&VertexId::from(4usize)];
+        assert_eq!(diamond.num_vertices(), 4);
+        assert_approx_eq!(diamond.area(), 2.0, F64_ASSERT_PRECISION);
+        assert_eq!(voronoi.neighbors(&VertexId::from(4usize)).len(), 4);
+    }
+
+    #[test]
+    fn test_voronoi_collinear() {
+        // Sites along a line have parallel edges going off to infinity both
+        // ways, whether the line is level with the sweep or not
+        let bbox = BoundingBox::new(-1.0, 4.0, -1.0, 4.0);
+        for coords in [
+            [(0.0, 0.0), (1.0, 0.0), (3.0, 0.0)],
+            [(0.0, 0.0), (1.0, 1.0), (3.0, 3.0)],
+            [(0.0, 3.0), (0.0, 1.0), (0.0, 0.0)],
+        ] {
+            let voronoi = Voronoi::from_vertices(&vertices_from_coords(&coords));
+            assert!(voronoi.vertices().is_empty());
+            assert_eq!(voronoi.edges().len(), 2);
+            assert!(voronoi
+                .edges()
+                .iter()
+                .all(|e| e.from.is_none() && e.to.is_none()));
+            assert_eq!(voronoi.neighbors(&VertexId::from(1usize)).len(), 2);
+            let cells = voronoi.clipped_cells(&bbox);
+            let area: f64 = cells.values().map(|cell| cell.area()).sum();
+            assert_approx_eq!(area, 25.0, F64_ASSERT_PRECISION);
+        }
+
+        // A single site has the whole box, and a site outside it nothing
+        let voronoi = Voronoi::from_vertices(&vertices_from_coords(&[(1.0, 1.0), (10.0, 1.0)]));
+        let cells = voronoi.clipped_cells(&bbox);
+        assert_eq!(cells.len(), 1);
+        assert_approx_eq!(
+            cells[&VertexId::from(0usize)].area(),
+            25.0,
+            F64_ASSERT_PRECISION
+        );
+        let voronoi = Voronoi::from_vertices(&vertices_from_coords(&[(1.0, 1.0), (1.0, 1.0)]));
+        assert_eq!(voronoi.duplicates(), &[VertexId::from(1usize)]);
+        assert!(voronoi.edges().is_empty());
+    }
+
+    #[test]
+    fn test_voronoi_edge_orientation() {
+        // Going along an edge, its first site is on the left
+        let points =
+            vertices_from_coords(&[(0.0, 0.0), (4.0, 1.0), (1.0, 3.0), (5.0, 5.0), (2.0, -2.0)]);
+        let voronoi = Voronoi::from_vertices(&points);
+        for e in voronoi.edges() {
+            let (a, b) = (
+                &points[usize::from(e.sites.0)],
+                &points[usize::from(e.sites.1)],
+            );
+            let (dx, dy) = (a.y - b.y, b.x - a.x);
+            let direction = match (e.from, e.to) {
+                (Some(u), Some(v)) => {
+                    let (u, v) = (voronoi.vertex(u), voronoi.vertex(v));
+                    (v.x - u.x, v.y - u.y)
+                }
+                _ => continue,
+            };
+            assert!(direction.0 * dx + direction.1 * dy > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_voronoi_tracer() {
+        let points =
+            vertices_from_coords(&[(0.0, 0.0), (4.0, 1.0), (1.0, 3.0), (5.0, 5.0), (2.0, -2.0)]);
+        let tracer = &mut Some(VoronoiTracer::default());
+        let voronoi = Voronoi::from_vertices_with_tracer(&points, tracer);
+        let steps = &tracer.as_ref().unwrap().steps;
+        assert_eq!(steps.len(), points.len() + voronoi.vertices().len());
+        for (prev, step) in steps.iter().tuple_windows() {
+            assert!(step.sweep <= prev.sweep);
+        }
+        for step in steps {
+            assert!(step.beach_line.iter().all(|arc| arc.start <= arc.end));
+        }
+        let finished = voronoi
+            .edges()
+            .iter()
+            .filter(|e| e.from.is_some() && e.to.is_some())
+            .count();
+        let last = steps.last().unwrap();
+        assert!(finished <= last.edges.len() && last.edges.len() <= voronoi.edges().len());
+    }
+}